@@ -7,7 +7,9 @@ use std::fs;
 use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
 
+use crate::base::changelog;
 use crate::base::fileset::copy_filesets;
+use crate::base::strip;
 use crate::base::utils;
 use crate::base::Arch;
 use crate::config::{get_binary_dir, Config, LinuxConfig};
@@ -27,6 +29,13 @@ pub fn build_app_image(conf: &Config, linux_conf: &LinuxConfig, arch: Arch) -> R
         ));
     };
 
+    let app_image_arch = arch_name(arch).ok_or_else(|| {
+        Error::from_string(
+            ErrorKind::AppImageCompilerError,
+            format!("Architecture {arch} is not supported by `appimagetool`"),
+        )
+    })?;
+
     let workdir = Path::new(&conf.metadata.workdir);
     let app_image_dir_name = "app_image";
     let app_image_dir = workdir.join(app_image_dir_name);
@@ -36,6 +45,11 @@ pub fn build_app_image(conf: &Config, linux_conf: &LinuxConfig, arch: Arch) -> R
     fs::create_dir_all(&app_image_dir)?;
 
     copy_filesets(files, &conf.metadata.src_dir, &app_image_dir)?;
+    let releases = changelog::read_releases(&conf.metadata)?;
+    changelog::update_metainfo_files(&app_image_dir, &releases)?;
+    if linux_conf.need_strip() {
+        strip::strip_elf_files(&app_image_dir, arch.into(), None)?;
+    }
 
     if app_image_conf.embed_libs {
         fs::create_dir_all(&libs_dir)?;
@@ -46,7 +60,18 @@ pub fn build_app_image(conf: &Config, linux_conf: &LinuxConfig, arch: Arch) -> R
         )?;
     }
 
-    compile_app_image(workdir, &app_image_dir_name, arch)
+    compile_app_image(workdir, &app_image_dir_name, arch, app_image_arch)
+}
+
+/// Get value of `ARCH` environment of `appimagetool`, `None` if `arch` is not supported.
+const fn arch_name(arch: Arch) -> Option<&'static str> {
+    match arch {
+        Arch::X86 => Some("i686"),
+        Arch::X86_64 => Some("x86_64"),
+        Arch::AArch64 => Some("aarch64"),
+        Arch::Armv7 => Some("armhf"),
+        Arch::Mips64 | Arch::Riscv64 | Arch::Ppc64le | Arch::S390x | Arch::LoongArch64 => None,
+    }
 }
 
 fn copy_libraries(
@@ -74,7 +99,12 @@ fn get_appimage_tool(arch: Arch) -> Result<PathBuf, Error> {
     Ok(binary_dir)
 }
 
-fn compile_app_image<P: AsRef<Path>>(workdir: &Path, dir: &P, arch: Arch) -> Result<(), Error> {
+fn compile_app_image<P: AsRef<Path>>(
+    workdir: &Path,
+    dir: &P,
+    arch: Arch,
+    app_image_arch: &str,
+) -> Result<(), Error> {
     let appimage_tool = get_appimage_tool(arch)?;
     log::info!("Using appimagetool: {:?}", &appimage_tool);
     let mut cmd = Command::new(appimage_tool.as_os_str());
@@ -82,7 +112,7 @@ fn compile_app_image<P: AsRef<Path>>(workdir: &Path, dir: &P, arch: Arch) -> Res
         cmd.stdout(Stdio::null()).stderr(Stdio::null());
     }
     let status = cmd
-        .env("ARCH", arch.to_string())
+        .env("ARCH", app_image_arch)
         .current_dir(workdir)
         .arg(dir.as_ref())
         .stdout(Stdio::null())
@@ -3,14 +3,18 @@
 // in the LICENSE file.
 
 use std::fmt::Debug;
-use std::fs::{self, File};
-use std::io;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::iter;
 #[cfg(not(target_os = "windows"))]
 use std::os::unix::fs::PermissionsExt;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::time::UNIX_EPOCH;
 use walkdir::WalkDir;
 
+use crate::base::fileset::FileAttr;
+use crate::base::payload::{EntryKind, Payload};
+use crate::base::utils;
 use crate::error::{Error, ErrorKind};
 
 /// # Errors
@@ -19,6 +23,7 @@ pub fn create_tar(dir: &Path, to: &Path) -> Result<(), Error> {
     log::info!("create_tar_chown(), dir: {:?}, to: {:?}", dir, to);
     let to_file = File::create(to)?;
     let mut builder = tar::Builder::new(to_file);
+    builder.follow_symlinks(false);
     if let Some(dirname) = dir.file_name() {
         builder.append_dir_all(dirname, dir)?;
         builder.finish()?;
@@ -37,66 +42,241 @@ pub fn create_tar_without_rootdir(dir: &Path, to: &Path) -> Result<(), Error> {
     log::info!("create_tar_without_rootdir() {:?} > {:?}", dir, to);
     let to_file = File::create(to)?;
     let mut builder = tar::Builder::new(to_file);
+    builder.follow_symlinks(false);
     builder.append_dir_all(".", dir)?;
     builder.finish()?;
 
     Ok(())
 }
 
+/// Write `dir` recursively to tarball, with entry names prefixed by `prefix`.
+///
+/// Symbolic links are kept as is. Entries are skipped if `filter` returns false
+/// for their path relative to `dir`.
+///
 /// # Errors
-/// Returns error if failed to create ar file.
-pub fn create_tar_chown(dir: &Path, to: &Path) -> Result<(), Error> {
-    log::info!("create_tar_chown() {:?} > {:?}", dir, to);
-    let to_file = File::create(to)?;
-    let mut builder = tar::Builder::new(to_file);
-
-    for entry in WalkDir::new(dir) {
+/// Returns error if failed to walk through `dir` or failed to write tarball.
+pub fn create_tar_with_prefix<W, F>(
+    dir: &Path,
+    prefix: &Path,
+    mut filter: F,
+    writer: W,
+) -> Result<W, Error>
+where
+    W: Write,
+    F: FnMut(&Path) -> bool,
+{
+    log::info!(
+        "create_tar_with_prefix() dir: {}, prefix: {}",
+        dir.display(),
+        prefix.display()
+    );
+    let mut builder = tar::Builder::new(writer);
+    builder.follow_symlinks(false);
+    let walker = WalkDir::new(dir)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_entry(|entry| {
+            entry
+                .path()
+                .strip_prefix(dir)
+                .is_ok_and(|path| path.as_os_str().is_empty() || filter(path))
+        });
+    for entry in walker {
         let entry = entry?;
-        let path = entry.path();
+        let name = prefix.join(entry.path().strip_prefix(dir)?);
+        builder.append_path_with_name(entry.path(), name)?;
+    }
+    Ok(builder.into_inner()?)
+}
 
-        let metadata = fs::metadata(path)?;
-        let mtime = metadata.modified()?;
-        let mtime = mtime.duration_since(UNIX_EPOCH)?.as_secs();
-        let filename = path.strip_prefix(dir)?;
+/// Digest of files written by [`create_payload_tar`].
+#[derive(Debug, Clone, Default)]
+pub struct TarSummary {
+    /// Md5 hash of each regular file, with its path in package.
+    pub md5sums: Vec<(PathBuf, String)>,
 
-        #[cfg(not(target_os = "windows"))]
-        let mode = metadata.permissions().mode();
+    /// Installed size in KiB, like `dpkg-gencontrol` computes.
+    ///
+    /// Size of each file is rounded up to KiB, and other entries take 1 KiB.
+    pub installed_size: u64,
+}
 
-        if path.is_file() {
-            let mut header = tar::Header::new_gnu();
-            header.set_mtime(mtime);
-            header.set_size(metadata.len());
-
-            #[cfg(not(target_os = "windows"))]
-            header.set_mode(mode);
-
-            let path_str = filename.to_string_lossy().to_string();
-            header.set_path(&path_str)?;
-            header.set_cksum();
-            let fd = File::open(path)?;
-            builder.append(&header, fd)?;
-        } else if path.is_dir() {
-            let mut header = tar::Header::new_gnu();
-            header.set_mtime(mtime);
-            header.set_size(0);
-            header.set_mode(0o755);
-            if filename.as_os_str().is_empty() {
-                continue;
+/// Write entries of `payload` to tarball, reading files from their sources.
+///
+/// Entry names are prefixed with `./`, as required by dpkg.
+/// Md5 hash and installed size are computed in the same pass.
+///
+/// # Errors
+/// Returns error if failed to read source files or failed to write tarball.
+pub fn create_payload_tar<W: Write>(
+    payload: &Payload,
+    writer: W,
+) -> Result<(W, TarSummary), Error> {
+    log::info!("create_payload_tar()");
+    let mut builder = tar::Builder::new(writer);
+    let mut summary = TarSummary::default();
+    let entry_mtime = utils::entry_mtime();
+
+    let root_attr = FileAttr::default();
+    let root = iter::once((Path::new(""), &EntryKind::Directory, &root_attr));
+    let entries = payload
+        .iter()
+        .map(|(path, entry)| (path, &entry.kind, &entry.attr));
+    for (path, kind, attr) in root.chain(entries) {
+        let mut header = tar::Header::new_gnu();
+        header.set_uid(resolve_id(&attr.owner));
+        header.set_gid(resolve_id(&attr.group));
+        header.set_username(&attr.owner)?;
+        header.set_groupname(&attr.group)?;
+        let mut path_str = Path::new(".").join(path).to_string_lossy().to_string();
+
+        match kind {
+            EntryKind::Directory => {
+                if !path_str.ends_with('/') {
+                    path_str += "/";
+                }
+                header.set_mtime(entry_mtime);
+                header.set_size(0);
+                header.set_mode(attr.mode.unwrap_or(0o755));
+                header.set_entry_type(tar::EntryType::Directory);
+                append_gnu_entry(&mut builder, &mut header, &path_str, io::empty())?;
+                if !path.as_os_str().is_empty() {
+                    summary.installed_size += 1;
+                }
             }
-            let dir_path = Path::new("./.").join(filename);
-            let mut path_str = dir_path.to_string_lossy().to_string();
-            if !path_str.ends_with('/') {
-                path_str += "/";
+            EntryKind::Symlink(target) => {
+                header.set_mtime(entry_mtime);
+                header.set_size(0);
+                header.set_mode(0o777);
+                header.set_entry_type(tar::EntryType::Symlink);
+                set_link_name(&mut builder, &mut header, target)?;
+                append_gnu_entry(&mut builder, &mut header, &path_str, io::empty())?;
+                summary.installed_size += 1;
+            }
+            EntryKind::File(source) => {
+                let fd = File::open(source).map_err(|err| {
+                    Error::from_string(
+                        ErrorKind::IoError,
+                        format!("Failed to open {}, err: {err}", source.display()),
+                    )
+                })?;
+                let metadata = fd.metadata()?;
+                let mtime = metadata.modified()?.duration_since(UNIX_EPOCH)?.as_secs();
+                header.set_mtime(utils::clamp_mtime(mtime));
+                header.set_size(metadata.len());
+                #[cfg(not(target_os = "windows"))]
+                let source_mode = metadata.permissions().mode() & 0o7777;
+                #[cfg(target_os = "windows")]
+                let source_mode = 0o644;
+                header.set_mode(attr.mode.unwrap_or(source_mode));
+                header.set_entry_type(tar::EntryType::Regular);
+
+                let mut reader = Md5Reader {
+                    inner: fd,
+                    context: md5::Context::new(),
+                };
+                append_gnu_entry(&mut builder, &mut header, &path_str, &mut reader)?;
+                let digest = reader.context.compute();
+                summary
+                    .md5sums
+                    .push((path.to_path_buf(), format!("{digest:x}")));
+                summary.installed_size += metadata.len().div_ceil(1024);
             }
-            header.set_path(filename)?;
-            header.set_entry_type(tar::EntryType::Directory);
-            header.set_cksum();
-            builder.append(&header, &mut io::empty())?;
         }
     }
 
-    builder.finish()?;
+    let writer = builder.into_inner()?;
+    Ok((writer, summary))
+}
+
+/// Reader which computes md5 hash of data read through it.
+struct Md5Reader<R: Read> {
+    inner: R,
+    context: md5::Context,
+}
+
+impl<R: Read> Read for Md5Reader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n_read = self.inner.read(buf)?;
+        self.context.consume(&buf[..n_read]);
+        Ok(n_read)
+    }
+}
 
+/// Get numeric id of user or group `name`.
+///
+/// Target system uses `name` if it exists there. Ids of build host are not used,
+/// so that the same config produces the same tarball, and `0` is written
+/// unless `name` is a numeric id.
+fn resolve_id(name: &str) -> u64 {
+    name.parse::<u64>().unwrap_or(0)
+}
+
+/// Set target of symbolic link to `header`.
+///
+/// GNU long link extension is used if target does not fit in header.
+fn set_link_name<W: Write>(
+    builder: &mut tar::Builder<W>,
+    header: &mut tar::Header,
+    target: &Path,
+) -> Result<(), Error> {
+    let target_str = target.to_string_lossy();
+    let target_bytes = target_str.as_bytes();
+    let link_name = &mut header.as_old_mut().linkname;
+    if target_bytes.len() > link_name.len() {
+        append_long_link(builder, tar::EntryType::GNULongLink, target_bytes)?;
+    }
+    let len = target_bytes.len().min(link_name.len());
+    link_name.fill(0);
+    link_name[..len].copy_from_slice(&target_bytes[..len]);
+    Ok(())
+}
+
+/// Append a GNU long name (`L`) or long link (`K`) entry, which holds `value`
+/// for the next entry.
+fn append_long_link<W: Write>(
+    builder: &mut tar::Builder<W>,
+    entry_type: tar::EntryType,
+    value: &[u8],
+) -> Result<(), Error> {
+    let mut long_header = tar::Header::new_gnu();
+    let long_name = b"././@LongLink";
+    long_header.as_old_mut().name[..long_name.len()].copy_from_slice(long_name);
+    long_header.set_mode(0o644);
+    long_header.set_uid(0);
+    long_header.set_gid(0);
+    long_header.set_mtime(0);
+    long_header.set_size(value.len() as u64 + 1);
+    long_header.set_entry_type(entry_type);
+    long_header.set_cksum();
+    builder.append(&long_header, value.chain(&[0_u8][..]))?;
+    Ok(())
+}
+
+/// Append an entry to tarball, keeping its path as is.
+///
+/// `tar::Header::set_path()` strips the leading `./` component, so entry name
+/// is written to header directly. GNU long name extension is used if path
+/// does not fit in header.
+fn append_gnu_entry<W: Write, R: Read>(
+    builder: &mut tar::Builder<W>,
+    header: &mut tar::Header,
+    path: &str,
+    data: R,
+) -> Result<(), Error> {
+    let path_bytes = path.as_bytes();
+    let name_len = header.as_old().name.len();
+    if path_bytes.len() > name_len {
+        append_long_link(builder, tar::EntryType::GNULongName, path_bytes)?;
+    }
+
+    let name = &mut header.as_old_mut().name;
+    let len = path_bytes.len().min(name.len());
+    name.fill(0);
+    name[..len].copy_from_slice(&path_bytes[..len]);
+    header.set_cksum();
+    builder.append(header, data)?;
     Ok(())
 }
 
@@ -119,22 +299,38 @@ pub fn create_ar(dir: &Path, to: &Path) -> Result<(), Error> {
     Ok(())
 }
 
-/// # Errors
-/// Returns error if failed to create ar file.
-pub fn create_ar_files<P>(files: &[&P], to: &Path) -> Result<(), Error>
-where
-    P: AsRef<Path> + Debug,
-{
-    log::info!("create_ar_files() files: {:?}, to: {:?}", files, to);
-    let to_file = File::create(to)?;
-    let mut builder = ar::Builder::new(to_file);
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    for path in files {
-        let path = path.as_ref();
-        if path.is_file() {
-            builder.append_path(path)?;
-        }
-    }
+    #[test]
+    fn test_long_link_name() {
+        let target = format!("/opt/{}/bin/hello", "x".repeat(120));
+        let mut payload = Payload::default();
+        payload.add_directory(PathBuf::from("usr/bin"), FileAttr::default());
+        payload.add_symlink(
+            PathBuf::from("usr/bin/hello"),
+            PathBuf::from(&target),
+            FileAttr::default(),
+        );
+        let (tarball, _summary) = create_payload_tar(&payload, Vec::new()).unwrap();
+        let (tarball2, _summary) = create_payload_tar(&payload, Vec::new()).unwrap();
+        assert_eq!(tarball, tarball2);
 
-    Ok(())
+        let mut archive = tar::Archive::new(tarball.as_slice());
+        let entries = archive
+            .entries()
+            .unwrap()
+            .map(|entry| {
+                let entry = entry.unwrap();
+                let link_name = entry.link_name().unwrap().map(|link| link.into_owned());
+                (entry.path().unwrap().into_owned(), link_name)
+            })
+            .collect::<Vec<_>>();
+        let link = entries
+            .iter()
+            .find(|(path, _link_name)| path == Path::new("./usr/bin/hello"))
+            .unwrap();
+        assert_eq!(link.1.as_deref(), Some(Path::new(&target)));
+    }
 }
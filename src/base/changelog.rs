@@ -0,0 +1,581 @@
+// Copyright (c) 2021 Xu Shaohua <shaohua@biofan.org>. All rights reserved.
+// Use of this source is governed by General Public License that can be found
+// in the LICENSE file.
+
+//! Changelog of package, rendered as Debian changelog, rpm `%changelog`
+//! and `<releases>` of `AppStream` metainfo files.
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use time::format_description::well_known::Rfc2822;
+use time::{format_description, Date, Time};
+
+use crate::base::compress;
+use crate::base::fileset::FileAttr;
+use crate::base::payload::Payload;
+use crate::base::utils::{self, default_false};
+use crate::base::Metadata;
+use crate::error::{Error, ErrorKind};
+
+/// Folder of `AppStream` metainfo files.
+pub const METAINFO_DIR: &str = "usr/share/metainfo";
+
+/// Sources of changelog, checked in order of `releases`, `file` and `git`.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct Changelog {
+    /// Releases written in config, newest first.
+    #[serde(default = "Vec::new")]
+    pub releases: Vec<Release>,
+
+    /// Path to changelog file in Keep a Changelog format, like `CHANGELOG.md`,
+    /// relative to `src_dir`.
+    pub file: Option<String>,
+
+    /// Boolean - whether to generate releases from `git log` between tags
+    /// in `src_dir`, if no release is found in `releases` or `file`.
+    #[serde(default = "default_false")]
+    pub git: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Release {
+    pub version: String,
+
+    /// Date of release, like `"2024-05-01"`.
+    #[serde(
+        deserialize_with = "deserialize_date",
+        serialize_with = "serialize_date"
+    )]
+    pub date: Date,
+
+    /// Changes in this release, one item per line.
+    #[serde(default = "Vec::new")]
+    pub changes: Vec<String>,
+}
+
+impl Release {
+    /// Get a release of current version, dated today.
+    #[must_use]
+    pub fn current(metadata: &Metadata) -> Self {
+        Self {
+            version: metadata.version.clone(),
+            date: time::OffsetDateTime::now_utc().date(),
+            changes: vec![default_change(&metadata.version)],
+        }
+    }
+
+    /// Get author line of rpm changelog entry, like `Name <email> - 1.0.0-1`.
+    ///
+    /// `full_version` converts version of release to `epoch:version-release`.
+    #[must_use]
+    pub fn rpm_name(&self, packager: &str, full_version: impl Fn(&str) -> String) -> String {
+        format!("{packager} - {}", full_version(&self.version))
+    }
+
+    /// Get text of rpm changelog entry, with a `- ` prefixed line of each change.
+    #[must_use]
+    pub fn rpm_text(&self) -> String {
+        let mut text = String::new();
+        for change in &self.changes {
+            for (index, line) in change.lines().enumerate() {
+                let prefix = if index == 0 { "- " } else { "  " };
+                if !text.is_empty() {
+                    text.push('\n');
+                }
+                text.push_str(prefix);
+                text.push_str(line.trim());
+            }
+        }
+        text
+    }
+
+    /// Get timestamp of rpm changelog entry.
+    ///
+    /// Time is set to noon like `rpmbuild` does, to keep the date in most time zones.
+    #[must_use]
+    pub const fn rpm_time(&self) -> i64 {
+        self.date
+            .with_time(Time::MIDNIGHT)
+            .assume_utc()
+            .unix_timestamp()
+            + 12 * 3600
+    }
+}
+
+fn default_change(version: &str) -> String {
+    format!("New upstream release {version}.")
+}
+
+/// Read releases of package, newest first.
+///
+/// Returns an empty list if changelog is not set.
+///
+/// # Errors
+/// Returns error if failed to read changelog file or run `git` command.
+pub fn read_releases(metadata: &Metadata) -> Result<Vec<Release>, Error> {
+    log::info!("read_releases()");
+    let Some(changelog) = metadata.changelog.as_ref() else {
+        return Ok(Vec::new());
+    };
+    let mut releases = changelog.releases.clone();
+    if releases.is_empty() {
+        if let Some(file) = changelog.file.as_ref() {
+            let path = Path::new(&metadata.src_dir).join(file);
+            let content = fs::read_to_string(&path).map_err(|err| {
+                Error::from_string(
+                    ErrorKind::IoError,
+                    format!(
+                        "Failed to read changelog file {}, err: {err}",
+                        path.display()
+                    ),
+                )
+            })?;
+            releases = parse_keep_a_changelog(&content)?;
+        }
+    }
+    if releases.is_empty() && changelog.git {
+        releases = read_git_releases(metadata)?;
+    }
+    for release in &mut releases {
+        if release.changes.is_empty() {
+            release.changes.push(default_change(&release.version));
+        }
+    }
+    Ok(releases)
+}
+
+/// Parse changelog in [Keep a Changelog](https://keepachangelog.com) format.
+///
+/// Headings of release are like `## [1.0.0] - 2024-05-01`, and `Unreleased` section
+/// is skipped. Items of all categories are merged.
+///
+/// # Errors
+/// Returns error if version or date of release is invalid.
+pub fn parse_keep_a_changelog(content: &str) -> Result<Vec<Release>, Error> {
+    let mut releases = Vec::new();
+    let mut current: Option<Release> = None;
+    for line in content.lines() {
+        if let Some(heading) = line.strip_prefix("## ") {
+            releases.extend(current.take());
+            current = parse_heading(heading)?;
+            continue;
+        }
+        let Some(release) = current.as_mut() else {
+            continue;
+        };
+        let item = line.trim_start();
+        if let Some(change) = item.strip_prefix("- ").or_else(|| item.strip_prefix("* ")) {
+            release.changes.push(change.trim().to_owned());
+        } else if line.starts_with(' ') && !item.is_empty() {
+            // Continuation of previous item.
+            if let Some(change) = release.changes.last_mut() {
+                change.push(' ');
+                change.push_str(item.trim_end());
+            }
+        }
+    }
+    releases.extend(current);
+    Ok(releases)
+}
+
+/// Parse heading like `[1.0.0] - 2024-05-01`, returns `None` if it is unreleased.
+fn parse_heading(heading: &str) -> Result<Option<Release>, Error> {
+    let heading = heading.trim();
+    let (version, date) = heading.split_once(" - ").unwrap_or((heading, ""));
+    let version = version.trim_start_matches('[');
+    let version = version.split(']').next().unwrap_or_default().trim();
+    if version.eq_ignore_ascii_case("unreleased") {
+        return Ok(None);
+    }
+    let date = date.split_whitespace().next().unwrap_or_default();
+    Ok(Some(Release {
+        version: tag_version(version).to_owned(),
+        date: parse_date(date).map_err(|err| {
+            Error::from_string(
+                ErrorKind::InvalidConfError,
+                format!("Invalid release heading `{heading}` in changelog, {err}"),
+            )
+        })?,
+        changes: Vec::new(),
+    }))
+}
+
+/// Generate releases from `git log` between tags, newest first.
+///
+/// Commits after the latest tag belong to current version,
+/// unless the latest tag is current version.
+fn read_git_releases(metadata: &Metadata) -> Result<Vec<Release>, Error> {
+    log::info!("read_git_releases()");
+    let src_dir = &metadata.src_dir;
+    let tags = run_git(
+        src_dir,
+        &["tag", "--merged", "HEAD", "--sort=-version:refname"],
+    )?;
+    let mut revisions: Vec<(&str, &str)> =
+        tags.lines().map(|tag| (tag, tag_version(tag))).collect();
+    if revisions
+        .first()
+        .is_none_or(|(_tag, version)| *version != metadata.version)
+    {
+        revisions.insert(0, ("HEAD", &metadata.version));
+    }
+
+    let mut releases = Vec::with_capacity(revisions.len());
+    for (index, (revision, version)) in revisions.iter().enumerate() {
+        let range = revisions.get(index + 1).map_or_else(
+            || (*revision).to_owned(),
+            |(previous, _version)| format!("{previous}..{revision}"),
+        );
+        let log = run_git(src_dir, &["log", "--no-merges", "--format=%s", &range])?;
+        let date = run_git(src_dir, &["log", "-1", "--format=%cs", revision])?;
+        releases.push(Release {
+            version: (*version).to_owned(),
+            date: parse_date(date.trim())?,
+            changes: log.lines().map(ToOwned::to_owned).collect(),
+        });
+    }
+    Ok(releases)
+}
+
+fn run_git(dir: &str, args: &[&str]) -> Result<String, Error> {
+    let output = Command::new("git")
+        .current_dir(dir)
+        .args(args)
+        .output()
+        .map_err(|err| {
+            Error::from_string(
+                ErrorKind::GitHashError,
+                format!("Failed to run `git` command, err: {err}"),
+            )
+        })?;
+    if !output.status.success() {
+        return Err(Error::from_string(
+            ErrorKind::GitHashError,
+            format!(
+                "Failed to run `git {}`, err: {}",
+                args.join(" "),
+                String::from_utf8_lossy(&output.stderr).trim()
+            ),
+        ));
+    }
+    Ok(String::from_utf8(output.stdout)?)
+}
+
+/// Get version in tag name, like `1.0.0` in `v1.0.0`.
+fn tag_version(tag: &str) -> &str {
+    tag.strip_prefix('v')
+        .filter(|version| version.starts_with(|c: char| c.is_ascii_digit()))
+        .unwrap_or(tag)
+}
+
+fn parse_date(date: &str) -> Result<Date, Error> {
+    let format = format_description::parse("[year]-[month]-[day]")?;
+    Date::parse(date, &format).map_err(|err| {
+        Error::from_string(
+            ErrorKind::TimeError,
+            format!("Invalid date `{date}`, expected `YYYY-MM-DD`, err: {err}"),
+        )
+    })
+}
+
+fn deserialize_date<'de, D>(deserializer: D) -> Result<Date, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let date = String::deserialize(deserializer)?;
+    parse_date(&date).map_err(|err| serde::de::Error::custom(err.to_string()))
+}
+
+// Signature is required by `serialize_with`.
+#[allow(clippy::trivially_copy_pass_by_ref)]
+fn serialize_date<S>(date: &Date, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.collect_str(date)
+}
+
+/// Render releases as Debian changelog.
+///
+/// `full_version` converts version of each release to `epoch:version-revision`.
+///
+/// # Errors
+/// Returns error if failed to format date.
+pub fn to_debian(
+    releases: &[Release],
+    name: &str,
+    maintainer: &str,
+    full_version: impl Fn(&str) -> String,
+) -> Result<String, Error> {
+    let mut content = String::new();
+    for (index, release) in releases.iter().enumerate() {
+        if index > 0 {
+            content.push('\n');
+        }
+        writeln!(
+            content,
+            "{name} ({}) unstable; urgency=medium\n",
+            full_version(&release.version)
+        )?;
+        for change in &release.changes {
+            for (index, line) in change.lines().enumerate() {
+                let prefix = if index == 0 { "  * " } else { "    " };
+                writeln!(content, "{prefix}{}", line.trim())?;
+            }
+        }
+        let date = release
+            .date
+            .with_time(Time::MIDNIGHT)
+            .assume_utc()
+            .format(&Rfc2822)?;
+        writeln!(content, "\n -- {maintainer}  {date}")?;
+    }
+    Ok(content)
+}
+
+/// Render releases as entries of `%changelog` section in spec file.
+///
+/// `full_version` converts version of each release to `epoch:version-release`.
+///
+/// # Errors
+/// Returns error if failed to format date.
+pub fn to_rpm_spec(
+    releases: &[Release],
+    packager: &str,
+    full_version: impl Fn(&str) -> String,
+) -> Result<String, Error> {
+    let format = format_description::parse("[weekday repr:short] [month repr:short] [day] [year]")?;
+    let mut content = String::new();
+    for entry in releases {
+        writeln!(
+            content,
+            "* {} {}\n{}\n",
+            entry.date.format(&format)?,
+            entry.rpm_name(packager, &full_version),
+            entry.rpm_text()
+        )?;
+    }
+    Ok(content.replace('%', "%%"))
+}
+
+/// Render releases as `<releases>` element of `AppStream` metainfo.
+#[must_use]
+pub fn to_appstream(releases: &[Release]) -> String {
+    let mut content = String::from("<releases>\n");
+    for release in releases {
+        let _ = writeln!(
+            content,
+            "    <release version=\"{}\" date=\"{}\">\n      <description>\n        <ul>",
+            escape_xml(&release.version),
+            release.date
+        );
+        for change in &release.changes {
+            let _ = writeln!(content, "          <li>{}</li>", escape_xml(change.trim()));
+        }
+        content.push_str("        </ul>\n      </description>\n    </release>\n");
+    }
+    content.push_str("  </releases>");
+    content
+}
+
+/// Escape special characters of `text` in xml content and attribute values.
+#[must_use]
+pub fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Add `<releases>` to content of metainfo file.
+///
+/// An empty `<releases/>` element is replaced, and existing releases are kept.
+/// Otherwise releases are inserted at the end of `<component>`.
+#[must_use]
+pub fn update_metainfo(content: &str, releases: &[Release]) -> String {
+    let releases = to_appstream(releases);
+    for placeholder in ["<releases/>", "<releases />"] {
+        if content.contains(placeholder) {
+            return content.replacen(placeholder, &releases, 1);
+        }
+    }
+    if content.contains("<releases") {
+        return content.to_owned();
+    }
+    content.rfind("</component>").map_or_else(
+        || content.to_owned(),
+        |pos| format!("{}  {releases}\n{}", &content[..pos], &content[pos..]),
+    )
+}
+
+/// Add releases to metainfo files in `usr/share/metainfo` of staged tree `dir`.
+///
+/// # Errors
+/// Returns error if failed to read or write metainfo files.
+pub fn update_metainfo_files(dir: &Path, releases: &[Release]) -> Result<(), Error> {
+    let metainfo_dir = dir.join(METAINFO_DIR);
+    if releases.is_empty() || !metainfo_dir.is_dir() {
+        return Ok(());
+    }
+    for entry in fs::read_dir(&metainfo_dir)? {
+        let path = entry?.path();
+        if is_metainfo_file(&path) && fs::symlink_metadata(&path)?.is_file() {
+            let content = fs::read_to_string(&path)?;
+            fs::write(&path, update_metainfo(&content, releases))?;
+        }
+    }
+    Ok(())
+}
+
+/// Add releases to metainfo files in `payload`, updated files are written to `dir`.
+///
+/// # Errors
+/// Returns error if failed to read or write metainfo files.
+pub fn update_payload_metainfo(
+    payload: &mut Payload,
+    releases: &[Release],
+    dir: &Path,
+) -> Result<(), Error> {
+    if releases.is_empty() {
+        return Ok(());
+    }
+    let files: Vec<(PathBuf, PathBuf)> = payload
+        .files()
+        .filter(|(path, _source)| path.starts_with(METAINFO_DIR) && is_metainfo_file(path))
+        .map(|(path, source)| (path.to_path_buf(), source.to_path_buf()))
+        .collect();
+    for (path, source) in files {
+        let content = fs::read_to_string(&source)?;
+        let new_source = dir.join(&path);
+        if let Some(parent) = new_source.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&new_source, update_metainfo(&content, releases))?;
+        utils::set_entry_mtime(&new_source)?;
+        payload.set_source(&path, new_source);
+    }
+    Ok(())
+}
+
+fn is_metainfo_file(path: &Path) -> bool {
+    path.extension().is_some_and(|extension| extension == "xml")
+}
+
+/// Write gzip compressed Debian changelog to `dir`, and add it to `payload`
+/// as `/usr/share/doc/<name>/changelog.Debian.gz`.
+///
+/// # Errors
+/// Returns error if failed to write changelog file.
+pub fn add_debian_changelog(
+    payload: &mut Payload,
+    metadata: &Metadata,
+    releases: &[Release],
+    full_version: impl Fn(&str) -> String,
+    dir: &Path,
+) -> Result<(), Error> {
+    if releases.is_empty() {
+        return Ok(());
+    }
+    let content = to_debian(releases, &metadata.name, &metadata.author, full_version)?;
+    let changelog_file = dir.join("changelog.Debian");
+    fs::create_dir_all(dir)?;
+    fs::write(&changelog_file, content)?;
+    let gz_file = dir.join("changelog.Debian.gz");
+    compress::create_gz(&changelog_file, &gz_file, 9)?;
+    utils::set_entry_mtime(&gz_file)?;
+    payload.add_file(
+        Path::new("usr/share/doc")
+            .join(&metadata.name)
+            .join("changelog.Debian.gz"),
+        gz_file,
+        FileAttr::default(),
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CHANGELOG: &str = "# Changelog
+
+## [Unreleased]
+- Work in progress
+
+## [1.1.0] - 2024-05-01
+### Added
+- Support rpm,
+  with native writer
+- Escape <xml> & 100%
+
+### Fixed
+* Crash on start
+
+## [v1.0.0](https://example.com) - 2024-01-02
+- Initial release
+
+[1.1.0]: https://example.com/compare/v1.0.0...v1.1.0
+";
+
+    fn releases() -> Vec<Release> {
+        parse_keep_a_changelog(CHANGELOG).unwrap()
+    }
+
+    #[test]
+    fn test_parse_keep_a_changelog() {
+        let releases = releases();
+        assert_eq!(releases.len(), 2);
+        assert_eq!(releases[0].version, "1.1.0");
+        assert_eq!(releases[0].date.to_string(), "2024-05-01");
+        assert_eq!(
+            releases[0].changes,
+            [
+                "Support rpm, with native writer",
+                "Escape <xml> & 100%",
+                "Crash on start"
+            ]
+        );
+        assert_eq!(releases[1].version, "1.0.0");
+        assert_eq!(releases[1].changes, ["Initial release"]);
+        assert!(parse_keep_a_changelog("## [1.0.0] - 2024-13-01\n").is_err());
+    }
+
+    #[test]
+    fn test_render_changelog() {
+        let releases = releases();
+        let debian = to_debian(
+            &releases[1..],
+            "hello",
+            "Hello <hello@example.com>",
+            |version| format!("1:{version}-1"),
+        )
+        .unwrap();
+        assert_eq!(
+            debian,
+            "hello (1:1.0.0-1) unstable; urgency=medium\n\n  * Initial release\n\n \
+             -- Hello <hello@example.com>  Tue, 02 Jan 2024 00:00:00 +0000\n"
+        );
+
+        let spec = to_rpm_spec(&releases, "Hello <hello@example.com>", |version| {
+            format!("{version}-1")
+        })
+        .unwrap();
+        assert!(spec.starts_with(
+            "* Wed May 01 2024 Hello <hello@example.com> - 1.1.0-1\n- Support rpm, with native writer\n"
+        ));
+        assert!(spec.contains("- Escape <xml> & 100%%\n"));
+        assert_eq!(releases[1].rpm_time(), 1_704_196_800);
+
+        let metainfo = "<component>\n  <id>hello</id>\n</component>\n";
+        let updated = update_metainfo(metainfo, &releases[..1]);
+        assert!(
+            updated.contains("  <releases>\n    <release version=\"1.1.0\" date=\"2024-05-01\">")
+        );
+        assert!(updated.contains("<li>Escape &lt;xml&gt; &amp; 100%</li>"));
+        assert!(updated.ends_with("  </releases>\n</component>\n"));
+        assert_eq!(update_metainfo(&updated, &releases), updated);
+    }
+}
@@ -3,21 +3,219 @@
 // in the LICENSE file.
 
 use flate2::{Compression, GzBuilder};
+use serde::{Deserialize, Serialize};
+use std::ffi::OsStr;
+use std::fmt;
 use std::fs::File;
-use std::io;
-use std::path::Path;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use xz2::read::XzDecoder;
 use xz2::stream::MtStreamBuilder;
 use xz2::write::XzEncoder;
 
 use crate::error::{Error, ErrorKind};
 
+pub const DEFAULT_GZ_LEVEL: u32 = 6;
+pub const DEFAULT_XZ_LEVEL: u32 = 6;
+pub const DEFAULT_ZSTD_LEVEL: u32 = 3;
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq)]
+pub enum CompressFormat {
+    /// Do not compress.
+    #[serde(alias = "none")]
+    None,
+
+    #[serde(alias = "gzip", alias = "gz")]
+    Gzip,
+
+    #[serde(alias = "xz")]
+    Xz,
+
+    #[serde(alias = "zstd", alias = "zst")]
+    Zstd,
+}
+
+impl fmt::Display for CompressFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::None => write!(f, "none"),
+            Self::Gzip => write!(f, "gzip"),
+            Self::Xz => write!(f, "xz"),
+            Self::Zstd => write!(f, "zstd"),
+        }
+    }
+}
+
+impl CompressFormat {
+    /// Returns extension name of compressed file, like `.xz`.
+    #[must_use]
+    pub const fn extension(self) -> &'static str {
+        match self {
+            Self::None => "",
+            Self::Gzip => ".gz",
+            Self::Xz => ".xz",
+            Self::Zstd => ".zst",
+        }
+    }
+
+    /// Get compress format from extension name of `filename`, like `data.tar.xz`.
+    #[must_use]
+    pub fn from_filename(filename: &str) -> Self {
+        match Path::new(filename).extension().and_then(OsStr::to_str) {
+            Some("gz") => Self::Gzip,
+            Some("xz") => Self::Xz,
+            Some("zst") => Self::Zstd,
+            _ => Self::None,
+        }
+    }
+
+    /// Returns default compression level.
+    #[must_use]
+    pub const fn default_level(self) -> u32 {
+        match self {
+            Self::None => 0,
+            Self::Gzip => DEFAULT_GZ_LEVEL,
+            Self::Xz => DEFAULT_XZ_LEVEL,
+            Self::Zstd => DEFAULT_ZSTD_LEVEL,
+        }
+    }
+
+    /// Returns max compression level.
+    #[must_use]
+    pub const fn max_level(self) -> u32 {
+        match self {
+            Self::None => 0,
+            Self::Gzip | Self::Xz => 9,
+            Self::Zstd => 22,
+        }
+    }
+
+    /// Compress `in_path` to a new file with extension name appended.
+    ///
+    /// Returns path to compressed file, or `in_path` itself if format is `None`.
+    ///
+    /// # Errors
+    /// Returns error if compression level is out of range or failed to compress file.
+    pub fn compress(self, in_path: &Path, level: Option<u32>) -> Result<PathBuf, Error> {
+        let level = self.check_level(level)?;
+        let mut out_path = in_path.as_os_str().to_owned();
+        out_path.push(self.extension());
+        let out_path = PathBuf::from(out_path);
+        match self {
+            Self::None => {}
+            Self::Gzip => create_gz(in_path, &out_path, level)?,
+            Self::Xz => create_xz2(in_path, &out_path, level)?,
+            Self::Zstd => create_zstd(in_path, &out_path, level)?,
+        }
+        Ok(out_path)
+    }
+
+    /// Wrap `writer` with an encoder of this format, to compress data on the fly.
+    ///
+    /// # Errors
+    /// Returns error if compression level is out of range or failed to create encoder.
+    #[allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap)]
+    pub fn encoder<W: Write>(self, writer: W, level: Option<u32>) -> Result<Encoder<W>, Error> {
+        let level = self.check_level(level)?;
+        let encoder = match self {
+            Self::None => Encoder::None(writer),
+            Self::Gzip => Encoder::Gzip(GzBuilder::new().write(writer, Compression::new(level))),
+            Self::Xz => {
+                let stream = MtStreamBuilder::new()
+                    .preset(level)
+                    .threads(num_cpus::get() as u32)
+                    .encoder()?;
+                Encoder::Xz(XzEncoder::new_stream(writer, stream))
+            }
+            Self::Zstd => Encoder::Zstd(zstd::Encoder::new(writer, level as i32)?),
+        };
+        Ok(encoder)
+    }
+
+    /// Returns `level` or default level, if it is in range.
+    fn check_level(self, level: Option<u32>) -> Result<u32, Error> {
+        let level = level.unwrap_or_else(|| self.default_level());
+        if level > self.max_level() {
+            return Err(Error::from_string(
+                ErrorKind::InvalidConfError,
+                format!(
+                    "Invalid {self} compression level {level}, max level is {}",
+                    self.max_level()
+                ),
+            ));
+        }
+        Ok(level)
+    }
+
+    /// Wrap `reader` with a decoder of this format.
+    ///
+    /// # Errors
+    /// Returns error if failed to create decoder.
+    pub fn decoder<'a, R: Read + 'a>(self, reader: R) -> Result<Box<dyn Read + 'a>, Error> {
+        let decoder: Box<dyn Read + 'a> = match self {
+            Self::None => Box::new(reader),
+            Self::Gzip => Box::new(flate2::read::GzDecoder::new(reader)),
+            Self::Xz => Box::new(XzDecoder::new(reader)),
+            Self::Zstd => Box::new(zstd::Decoder::new(reader)?),
+        };
+        Ok(decoder)
+    }
+}
+
+/// Streaming encoder created by [`CompressFormat::encoder`].
+pub enum Encoder<W: Write> {
+    None(W),
+    Gzip(flate2::write::GzEncoder<W>),
+    Xz(XzEncoder<W>),
+    Zstd(zstd::Encoder<'static, W>),
+}
+
+impl<W: Write> Encoder<W> {
+    /// Flush remaining compressed data, and returns the inner writer.
+    ///
+    /// # Errors
+    /// Returns error if failed to write data.
+    pub fn finish(self) -> Result<W, Error> {
+        let writer = match self {
+            Self::None(writer) => writer,
+            Self::Gzip(encoder) => encoder.finish()?,
+            Self::Xz(encoder) => encoder.finish()?,
+            Self::Zstd(encoder) => encoder.finish()?,
+        };
+        Ok(writer)
+    }
+}
+
+impl<W: Write> Write for Encoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Self::None(writer) => writer.write(buf),
+            Self::Gzip(encoder) => encoder.write(buf),
+            Self::Xz(encoder) => encoder.write(buf),
+            Self::Zstd(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Self::None(writer) => writer.flush(),
+            Self::Gzip(encoder) => encoder.flush(),
+            Self::Xz(encoder) => encoder.flush(),
+            Self::Zstd(encoder) => encoder.flush(),
+        }
+    }
+}
+
 /// # Errors
 /// Returns error if failed to create archive file.
-#[allow(dead_code)]
-pub fn create_gz(in_path: &Path, out_path: &Path) -> Result<(), Error> {
-    log::info!("create_gz(), in: {:?}, out: {:?}", in_path, out_path);
+pub fn create_gz(in_path: &Path, out_path: &Path, level: u32) -> Result<(), Error> {
+    log::info!(
+        "create_gz(), in: {}, out: {}, level: {level}",
+        in_path.display(),
+        out_path.display()
+    );
     let out_file = File::create(out_path)?;
-    let mut encoder = GzBuilder::new().write(out_file, Compression::default());
+    let mut encoder = GzBuilder::new().write(out_file, Compression::new(level));
     let mut in_file = File::open(in_path)?;
     io::copy(&mut in_file, &mut encoder)?;
     encoder.finish()?;
@@ -28,11 +226,14 @@ pub fn create_gz(in_path: &Path, out_path: &Path) -> Result<(), Error> {
 /// # Errors
 /// Returns error if failed to create archive file.
 #[allow(clippy::cast_possible_truncation)]
-pub fn create_xz2(in_path: &Path, out_path: &Path) -> Result<(), Error> {
-    log::info!("create_xz2(), in: {:?}, out: {:?}", in_path, out_path);
-    let xz_level = 6;
+pub fn create_xz2(in_path: &Path, out_path: &Path, level: u32) -> Result<(), Error> {
+    log::info!(
+        "create_xz2(), in: {}, out: {}, level: {level}",
+        in_path.display(),
+        out_path.display()
+    );
     let stream = MtStreamBuilder::new()
-        .preset(xz_level)
+        .preset(level)
         .threads(num_cpus::get() as u32)
         .encoder()?;
 
@@ -61,3 +262,21 @@ pub fn create_xz2(in_path: &Path, out_path: &Path) -> Result<(), Error> {
 
     Ok(())
 }
+
+/// # Errors
+/// Returns error if failed to create archive file.
+#[allow(clippy::cast_possible_wrap)]
+pub fn create_zstd(in_path: &Path, out_path: &Path, level: u32) -> Result<(), Error> {
+    log::info!(
+        "create_zstd(), in: {}, out: {}, level: {level}",
+        in_path.display(),
+        out_path.display()
+    );
+    let out_file = File::create(out_path)?;
+    let mut encoder = zstd::Encoder::new(out_file, level as i32)?;
+    let mut in_file = File::open(in_path)?;
+    io::copy(&mut in_file, &mut encoder)?;
+    encoder.finish()?;
+
+    Ok(())
+}
@@ -6,6 +6,8 @@ use serde::{Deserialize, Serialize};
 use std::fmt;
 use std::str::FromStr;
 
+use crate::base::changelog::Changelog;
+
 #[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq)]
 pub enum Arch {
     #[serde(alias = "x86")]
@@ -17,8 +19,45 @@ pub enum Arch {
     #[serde(alias = "aarch64")]
     AArch64,
 
-    #[serde(alias = "mips64")]
+    /// 64-bit little endian MIPS, like `mips64el` in Debian.
+    #[serde(alias = "mips64", alias = "mips64el")]
     Mips64,
+
+    /// 32-bit `ARMv7` with hard float, like `armhf` in Debian.
+    #[serde(alias = "armv7", alias = "armhf")]
+    Armv7,
+
+    #[serde(alias = "riscv64")]
+    Riscv64,
+
+    /// 64-bit little endian `PowerPC`.
+    #[serde(alias = "ppc64le", alias = "ppc64el")]
+    Ppc64le,
+
+    #[serde(alias = "s390x")]
+    S390x,
+
+    #[serde(alias = "loongarch64", alias = "loong64")]
+    LoongArch64,
+}
+
+/// Architecture of deb and rpm packages.
+///
+/// Arch independent packages are built once, and never reach builders of
+/// `AppImage` and nsis, which accept [`Arch`] only.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PackageArch {
+    /// Package for a build target.
+    Target(Arch),
+
+    /// Architecture independent, like deb `all` and rpm `noarch`.
+    All,
+}
+
+impl From<Arch> for PackageArch {
+    fn from(arch: Arch) -> Self {
+        Self::Target(arch)
+    }
 }
 
 #[must_use]
@@ -35,6 +74,21 @@ pub const fn get_target_arch() -> Option<Arch> {
     if cfg!(target_arch = "mips64") {
         return Some(Arch::Mips64);
     }
+    if cfg!(target_arch = "arm") {
+        return Some(Arch::Armv7);
+    }
+    if cfg!(target_arch = "riscv64") {
+        return Some(Arch::Riscv64);
+    }
+    if cfg!(all(target_arch = "powerpc64", target_endian = "little")) {
+        return Some(Arch::Ppc64le);
+    }
+    if cfg!(target_arch = "s390x") {
+        return Some(Arch::S390x);
+    }
+    if cfg!(target_arch = "loongarch64") {
+        return Some(Arch::LoongArch64);
+    }
     None
 }
 
@@ -45,6 +99,20 @@ impl fmt::Display for Arch {
             Self::X86_64 => write!(f, "x86_64"),
             Self::AArch64 => write!(f, "aarch64"),
             Self::Mips64 => write!(f, "mips64"),
+            Self::Armv7 => write!(f, "armv7"),
+            Self::Riscv64 => write!(f, "riscv64"),
+            Self::Ppc64le => write!(f, "ppc64le"),
+            Self::S390x => write!(f, "s390x"),
+            Self::LoongArch64 => write!(f, "loongarch64"),
+        }
+    }
+}
+
+impl fmt::Display for PackageArch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Target(arch) => arch.fmt(f),
+            Self::All => write!(f, "all"),
         }
     }
 }
@@ -57,7 +125,12 @@ impl FromStr for Arch {
             "x86" | "i386" | "i686" => Ok(Self::X86),
             "x86_64" | "amd64" => Ok(Self::X86_64),
             "arm64" | "aarch64" => Ok(Self::AArch64),
-            "mips64" => Ok(Self::Mips64),
+            "mips64" | "mips64el" => Ok(Self::Mips64),
+            "armv7" | "armhf" | "armv7hl" => Ok(Self::Armv7),
+            "riscv64" => Ok(Self::Riscv64),
+            "ppc64le" | "ppc64el" => Ok(Self::Ppc64le),
+            "s390x" => Ok(Self::S390x),
+            "loongarch64" | "loong64" => Ok(Self::LoongArch64),
             _ => Err(()),
         }
     }
@@ -67,11 +140,23 @@ impl FromStr for Arch {
 pub struct GlobPatterns(Vec<String>);
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(test, derive(Default))]
 pub struct Metadata {
     pub name: String,
     pub product_name: String,
     pub app_id: String,
+
+    /// One line summary of package.
+    ///
+    /// First line of `description` is used if not set.
+    pub short_description: Option<String>,
+
+    /// Long description of package.
+    ///
+    /// Paragraphs are separated by empty lines, and lines starting with
+    /// spaces are displayed verbatim.
     pub description: String,
+
     pub homepage: String,
     pub author: String,
     pub copyright: Option<String>,
@@ -81,15 +166,68 @@ pub struct Metadata {
     pub license: String,
     pub license_file: Option<String>,
 
+    /// Changelog of package, written to deb, rpm and `AppStream` metainfo files.
+    pub changelog: Option<Changelog>,
+
     pub workdir: String,
     pub src_dir: String,
 }
 
+impl Metadata {
+    /// Returns one line summary of package.
+    #[must_use]
+    pub fn summary(&self) -> String {
+        let summary = self.short_description.as_deref().unwrap_or_else(|| {
+            self.description
+                .trim_start()
+                .lines()
+                .next()
+                .unwrap_or_default()
+        });
+        summary.split_whitespace().collect::<Vec<_>>().join(" ")
+    }
+
+    /// Returns long description of package, excluding the summary line.
+    ///
+    /// Trailing spaces of each line are removed, and consecutive empty lines
+    /// are merged.
+    #[must_use]
+    pub fn long_description(&self) -> Option<String> {
+        let description = self.description.trim_end();
+        let description = if self.short_description.is_some() {
+            description
+        } else {
+            description
+                .trim_start()
+                .split_once('\n')
+                .map_or("", |(_summary, rest)| rest)
+        };
+
+        let mut lines: Vec<&str> = Vec::new();
+        for line in description.lines() {
+            let line = line.trim_end();
+            if line.is_empty() && lines.last().is_none_or(|last| last.is_empty()) {
+                continue;
+            }
+            lines.push(line);
+        }
+        if lines.is_empty() || (lines.len() == 1 && lines[0].trim() == self.summary()) {
+            None
+        } else {
+            Some(lines.join("\n"))
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq)]
 pub enum PlatformTarget {
     #[serde(alias = "deb")]
     Deb,
 
+    /// Debian source package, with `.dsc` file and tarballs.
+    #[serde(alias = "deb_source")]
+    DebSource,
+
     #[serde(alias = "rpm")]
     Rpm,
 
@@ -107,6 +245,7 @@ impl FromStr for PlatformTarget {
     fn from_str(input: &str) -> Result<Self, Self::Err> {
         match input {
             "deb" => Ok(Self::Deb),
+            "deb_source" => Ok(Self::DebSource),
             "rpm" => Ok(Self::Rpm),
             "app_image" | "appImage" | "AppImage" => Ok(Self::AppImage),
             "nsis" => Ok(Self::Nsis),
@@ -121,6 +260,7 @@ impl PlatformTarget {
     pub const fn extension(&self) -> &'static str {
         match self {
             Self::Deb => "deb",
+            Self::DebSource => "dsc",
             Self::Rpm => "rpm",
             Self::AppImage => "AppImage",
             Self::Nsis => "exe",
@@ -0,0 +1,75 @@
+// Copyright (c) 2021 Xu Shaohua <shaohua@biofan.org>. All rights reserved.
+// Use of this source is governed by General Public License that can be found
+// in the LICENSE file.
+
+use goblin::elf::note::NT_GNU_BUILD_ID;
+use goblin::elf::Elf;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::Path;
+
+use crate::error::Error;
+
+const ELF_MAGIC: &[u8; 4] = b"\x7fELF";
+
+/// Check whether `path` is an ELF file, by reading its magic number.
+#[must_use]
+pub fn is_elf(path: &Path) -> bool {
+    let mut magic = [0_u8; 4];
+    File::open(path)
+        .and_then(|mut fd| fd.read_exact(&mut magic))
+        .is_ok_and(|()| &magic == ELF_MAGIC)
+}
+
+/// Get names of shared libraries required by ELF file, in `DT_NEEDED` entries.
+///
+/// # Errors
+/// Returns error if failed to read file or file is not a valid ELF file.
+pub fn get_needed_libs(path: &Path) -> Result<Vec<String>, Error> {
+    let content = fs::read(path)?;
+    let elf = Elf::parse(&content)?;
+    Ok(elf.libraries.iter().map(|lib| (*lib).to_string()).collect())
+}
+
+/// Properties of ELF file used to strip it.
+#[derive(Debug, Clone)]
+pub struct ElfInfo {
+    /// ELF file type, like `ET_EXEC` or `ET_DYN`.
+    pub elf_type: u16,
+
+    /// Hex encoded build-id, in `.note.gnu.build-id` section.
+    pub build_id: Option<String>,
+
+    /// Whether this file contains `.debug_*` sections.
+    pub has_debug_info: bool,
+}
+
+/// Read build-id and debug sections of ELF file.
+///
+/// # Errors
+/// Returns error if failed to read file or file is not a valid ELF file.
+pub fn read_elf_info(path: &Path) -> Result<ElfInfo, Error> {
+    let content = fs::read(path)?;
+    let elf = Elf::parse(&content)?;
+
+    let build_id = elf
+        .iter_note_sections(&content, Some(".note.gnu.build-id"))
+        .and_then(|mut notes| {
+            notes.find_map(|note| {
+                note.ok()
+                    .filter(|note| note.n_type == NT_GNU_BUILD_ID && note.name == "GNU")
+                    .map(|note| hex::encode(note.desc))
+            })
+        });
+    let has_debug_info = elf.section_headers.iter().any(|section| {
+        elf.shdr_strtab
+            .get_at(section.sh_name)
+            .is_some_and(|name| name.starts_with(".debug_") || name.starts_with(".zdebug_"))
+    });
+
+    Ok(ElfInfo {
+        elf_type: elf.header.e_type,
+        build_id,
+        has_debug_info,
+    })
+}
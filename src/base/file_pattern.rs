@@ -7,7 +7,7 @@ use std::env;
 use std::process::Command;
 use time::{format_description, OffsetDateTime};
 
-use super::config::{Arch, PlatformTarget};
+use super::config::{PackageArch, PlatformTarget};
 use crate::config::Config;
 use crate::error::{Error, ErrorKind};
 
@@ -102,7 +102,7 @@ pub fn expand_file_macro_simple(s: &str) -> Result<String, Error> {
 pub fn expand_file_macro(
     s: &str,
     conf: &Config,
-    arch: Arch,
+    arch: PackageArch,
     target: PlatformTarget,
 ) -> Result<String, Error> {
     let mut content = expand_file_macro_simple(s)?;
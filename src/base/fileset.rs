@@ -2,9 +2,13 @@
 // Use of this source is governed by General Public License that can be found
 // in the LICENSE file.
 
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::BTreeMap;
 use std::fs;
-use std::path::Path;
+#[cfg(not(target_os = "windows"))]
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Component, Path, PathBuf};
+use walkdir::WalkDir;
 
 use crate::base::GlobPatterns;
 use crate::error::{Error, ErrorKind};
@@ -14,13 +18,188 @@ pub struct FileSet {
     pub from: String,
     pub to: String,
     pub filter: Option<GlobPatterns>,
-    pub mode: Option<i32>,
+
+    /// Permission bits of files, as octal string like `"0755"` or `"4755"`,
+    /// or unambiguous octal integer like `0o755`,
+    /// including setuid, setgid and sticky bits.
+    ///
+    /// If `from` is a directory, it applies to all files in that directory.
+    #[serde(default, deserialize_with = "deserialize_mode")]
+    pub mode: Option<u32>,
+
+    /// Owner of files and directories, default is `root`.
+    pub owner: Option<String>,
+
+    /// Group of files and directories, default is `root`.
+    pub group: Option<String>,
+}
+
+/// Mode, owner and group of a file in package.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileAttr {
+    /// Permission bits, `None` to keep mode of source file.
+    pub mode: Option<u32>,
+    pub owner: String,
+    pub group: String,
+}
+
+impl Default for FileAttr {
+    fn default() -> Self {
+        Self {
+            mode: None,
+            owner: DEFAULT_OWNER.to_owned(),
+            group: DEFAULT_OWNER.to_owned(),
+        }
+    }
+}
+
+/// Attributes of files set explicitly, keyed by path relative to package root.
+pub type FileAttrs = BTreeMap<PathBuf, FileAttr>;
+
+const DEFAULT_OWNER: &str = "root";
+
+/// Symbolic link in package.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct Symlink {
+    /// Path of link in package, like `usr/lib/libhello.so`.
+    pub path: String,
+
+    /// Target of link, like `libhello.so.1` or `/opt/hello/bin/hello`.
+    /// It is not required to exist.
+    pub target: String,
+}
+
+impl Symlink {
+    /// Get path of link relative to root of package.
+    #[must_use]
+    pub fn package_path(&self) -> PathBuf {
+        package_path(&self.path)
+    }
+
+    /// # Errors
+    /// Returns error if path or target is empty.
+    pub fn validate(&self) -> Result<(), Error> {
+        validate_package_path(&self.path)?;
+        if self.target.is_empty() {
+            return Err(Error::from_string(
+                ErrorKind::InvalidConfError,
+                format!("Target of symbolic link `{}` is empty", self.path),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Directory in package, which is created even if it is empty.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct Directory {
+    /// Path of directory in package, like `var/lib/hello`.
+    pub path: String,
+
+    /// Permission bits of directory as octal integer or string, default is `"0755"`.
+    #[serde(default, deserialize_with = "deserialize_mode")]
+    pub mode: Option<u32>,
+
+    /// Owner of directory, default is `root`.
+    pub owner: Option<String>,
+
+    /// Group of directory, default is `root`.
+    pub group: Option<String>,
+}
+
+impl Directory {
+    /// Get path of directory relative to root of package.
+    #[must_use]
+    pub fn package_path(&self) -> PathBuf {
+        package_path(&self.path)
+    }
+
+    #[must_use]
+    pub fn attr(&self) -> FileAttr {
+        let default_attr = FileAttr::default();
+        FileAttr {
+            mode: self.mode,
+            owner: self.owner.clone().unwrap_or(default_attr.owner),
+            group: self.group.clone().unwrap_or(default_attr.group),
+        }
+    }
+
+    /// # Errors
+    /// Returns error if path is empty.
+    pub fn validate(&self) -> Result<(), Error> {
+        validate_package_path(&self.path)
+    }
+}
+
+/// Paths in package may be written as absolute paths, like `/usr/bin/hello`.
+fn package_path(path: &str) -> PathBuf {
+    PathBuf::from(path.trim_start_matches('/'))
+}
+
+fn validate_package_path(path: &str) -> Result<(), Error> {
+    let package_path = package_path(path);
+    let is_normal = package_path
+        .components()
+        .all(|component| matches!(component, Component::Normal(_)));
+    if package_path.as_os_str().is_empty() || !is_normal {
+        return Err(Error::from_string(
+            ErrorKind::InvalidConfError,
+            format!("Invalid path `{path}` in package"),
+        ));
+    }
+    Ok(())
+}
+
+/// Accept mode as octal string like `"0755"`, `"0o755"` or `"4755"`, or as integer like `0o755`.
+///
+/// `755` in toml is a decimal number, and its radix is lost in deserializer.
+/// So integers whose decimal digits are all octal digits are rejected,
+/// as they are likely octal modes written without `0o` prefix.
+fn deserialize_mode<'de, D>(deserializer: D) -> Result<Option<u32>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum ModeRepr {
+        Int(i64),
+        Str(String),
+    }
+
+    let mode = match Option::<ModeRepr>::deserialize(deserializer)? {
+        None => return Ok(None),
+        Some(ModeRepr::Int(mode)) => {
+            if mode.to_string().chars().all(|c| ('0'..='7').contains(&c)) {
+                return Err(serde::de::Error::custom(format!(
+                    "ambiguous file mode `{mode}`, integer in toml is decimal, \
+                     please write mode as octal string like \"0755\""
+                )));
+            }
+            u32::try_from(mode)
+                .map_err(|_err| serde::de::Error::custom(format!("invalid file mode `{mode}`")))?
+        }
+        Some(ModeRepr::Str(s)) => {
+            let digits = s.trim().trim_start_matches("0o");
+            u32::from_str_radix(digits, 8)
+                .map_err(|_err| serde::de::Error::custom(format!("invalid file mode `{s}`")))?
+        }
+    };
+    if mode > 0o7777 {
+        return Err(serde::de::Error::custom(format!(
+            "invalid file mode `{mode:o}`"
+        )));
+    }
+    Ok(Some(mode))
 }
 
 impl FileSet {
+    /// Copy files to `dest` folder, and apply `mode` to copied files.
+    ///
+    /// Returns paths of copied files and directories, relative to `dest`.
+    ///
     /// # Errors
     /// Returns error if failed to copy files.
-    pub fn copy_to(&self, src: &str, dest: &Path) -> Result<(), Error> {
+    pub fn copy_to(&self, src: &str, dest: &Path) -> Result<Vec<PathBuf>, Error> {
         log::info!("FileSet::copy_to() src: {:?}, dest: {:?}", src, dest);
         let dest_path = dest.join(&self.to);
         let dest_dir = dest_path.parent().ok_or_else(|| {
@@ -46,10 +225,11 @@ impl FileSet {
             )
         })?;
         let mut entry_not_match = true;
+        let mut copied_paths = Vec::new();
         for entry in entries {
             entry_not_match = false;
             let entry = entry?;
-            let metadata = fs::metadata(&entry)?;
+            let metadata = fs::symlink_metadata(&entry)?;
             if metadata.is_file() {
                 fs::copy(&entry, &dest_path).map_err(|err| {
                     Error::from_string(
@@ -60,6 +240,8 @@ impl FileSet {
                         ),
                     )
                 })?;
+                apply_mode(&dest_path, self.mode)?;
+                copied_paths.push(dest_path.strip_prefix(dest)?.to_path_buf());
             } else if metadata.is_dir() {
                 // `dest_path` must be a directory.
                 fs::create_dir_all(&dest_path).map_err(|err| {
@@ -71,19 +253,16 @@ impl FileSet {
                         ),
                     )
                 })?;
-
-                let mut options = fs_extra::dir::CopyOptions::new();
-                options.overwrite = true;
-                options.copy_inside = true;
-                fs_extra::dir::copy(&entry, &dest_path, &options).map_err(|err| {
-                    Error::from_string(
-                        ErrorKind::IoError,
-                        format!(
-                            "Failed to copy folder from `{:?}` to `{:?}`, err: {:?}",
-                            &entry, &dest_path, err
-                        ),
-                    )
-                })?;
+                let dir_name = entry.file_name().unwrap_or_default();
+                for copied in copy_dir(&entry, &dest_path.join(dir_name), dest)? {
+                    if dest.join(&copied).is_file() {
+                        apply_mode(&dest.join(&copied), self.mode)?;
+                    }
+                    copied_paths.push(copied);
+                }
+            } else if metadata.is_symlink() {
+                copy_symlink(&entry, &dest_path)?;
+                copied_paths.push(dest_path.strip_prefix(dest)?.to_path_buf());
             } else {
                 return Err(Error::from_string(
                     ErrorKind::IoError,
@@ -97,23 +276,200 @@ impl FileSet {
                 format!("No file is matched with pattern `{src_pattern}`"),
             ))
         } else {
-            Ok(())
+            Ok(copied_paths)
+        }
+    }
+
+    /// Get attribute of files in this set, or `None` if not set explicitly.
+    #[must_use]
+    pub fn attr(&self) -> Option<FileAttr> {
+        if self.mode.is_none() && self.owner.is_none() && self.group.is_none() {
+            return None;
+        }
+        let default_attr = FileAttr::default();
+        Some(FileAttr {
+            mode: self.mode,
+            owner: self.owner.clone().unwrap_or(default_attr.owner),
+            group: self.group.clone().unwrap_or(default_attr.group),
+        })
+    }
+}
+
+/// Copy folder `src` to `to` recursively, symbolic links are copied as is.
+///
+/// Returns paths of copied entries, relative to `root`.
+fn copy_dir(src: &Path, to: &Path, root: &Path) -> Result<Vec<PathBuf>, Error> {
+    let mut copied_paths = Vec::new();
+    for entry in WalkDir::new(src).sort_by_file_name() {
+        let entry = entry?;
+        let dest_path = to.join(entry.path().strip_prefix(src)?);
+        let file_type = entry.file_type();
+        if file_type.is_dir() {
+            fs::create_dir_all(&dest_path)?;
+        } else if file_type.is_symlink() {
+            copy_symlink(entry.path(), &dest_path)?;
+        } else {
+            fs::copy(entry.path(), &dest_path).map_err(|err| {
+                Error::from_string(
+                    ErrorKind::IoError,
+                    format!(
+                        "Failed to copy file from `{}` to `{}`, err: {err}",
+                        entry.path().display(),
+                        dest_path.display()
+                    ),
+                )
+            })?;
         }
+        copied_paths.push(dest_path.strip_prefix(root)?.to_path_buf());
     }
+    Ok(copied_paths)
 }
 
+/// Create symbolic link at `dest_path` with the same target as `src`.
+fn copy_symlink(src: &Path, dest_path: &Path) -> Result<(), Error> {
+    let target = fs::read_link(src)?;
+    create_symlink(&target, dest_path)
+}
+
+/// Create symbolic link at `path` pointing to `target`, replacing existing file.
+///
+/// # Errors
+/// Returns error if failed to create symbolic link.
+#[cfg(not(target_os = "windows"))]
+pub fn create_symlink(target: &Path, path: &Path) -> Result<(), Error> {
+    if fs::symlink_metadata(path).is_ok() {
+        fs::remove_file(path)?;
+    }
+    std::os::unix::fs::symlink(target, path).map_err(|err| {
+        Error::from_string(
+            ErrorKind::IoError,
+            format!(
+                "Failed to create symbolic link `{}` -> `{}`, err: {err}",
+                path.display(),
+                target.display()
+            ),
+        )
+    })
+}
+
+/// # Errors
+/// Symbolic links are not supported on windows.
+#[cfg(target_os = "windows")]
+pub fn create_symlink(_target: &Path, path: &Path) -> Result<(), Error> {
+    Err(Error::from_string(
+        ErrorKind::IoError,
+        format!("Symbolic link is not supported: {}", path.display()),
+    ))
+}
+
+#[cfg(not(target_os = "windows"))]
+fn apply_mode(path: &Path, mode: Option<u32>) -> Result<(), Error> {
+    if let Some(mode) = mode {
+        fs::set_permissions(path, fs::Permissions::from_mode(mode))?;
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "windows")]
+const fn apply_mode(_path: &Path, _mode: Option<u32>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Copy files to `dest` folder.
+///
+/// Returns paths of copied files, symbolic links and directories relative to `dest`,
+/// and attributes of files with `mode`, `owner` or `group` set.
+///
 /// # Errors
 /// Returns error if failed to copy files.
-pub fn copy_filesets(files: &[FileSet], src: &str, dest: &Path) -> Result<(), Error> {
+pub fn copy_filesets(
+    files: &[FileSet],
+    src: &str,
+    dest: &Path,
+) -> Result<(Vec<PathBuf>, FileAttrs), Error> {
     log::info!(
         "copy_filesets() files: {:?}, src: {:?}, dest: {:?}",
         files,
         src,
         dest
     );
+    let mut paths = Vec::new();
+    let mut attrs = FileAttrs::new();
     for file in files {
-        file.copy_to(src, dest)?;
+        let copied_paths = file.copy_to(src, dest)?;
+        if let Some(attr) = file.attr() {
+            for path in &copied_paths {
+                let mut path_attr = attr.clone();
+                // Mode is applied to files only.
+                if !fs::symlink_metadata(dest.join(path))?.is_file() {
+                    path_attr.mode = None;
+                }
+                attrs.insert(path.clone(), path_attr);
+            }
+        }
+        paths.extend(copied_paths);
     }
 
+    Ok((paths, attrs))
+}
+
+/// Create symbolic links in `dest` folder.
+///
+/// # Errors
+/// Returns error if failed to create symbolic links.
+pub fn install_symlinks(symlinks: &[Symlink], dest: &Path) -> Result<(), Error> {
+    for symlink in symlinks {
+        let path = dest.join(symlink.package_path());
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        create_symlink(Path::new(&symlink.target), &path)?;
+    }
     Ok(())
 }
+
+/// Create directories in `dest` folder.
+///
+/// Returns attributes of these directories.
+///
+/// # Errors
+/// Returns error if failed to create directories.
+pub fn install_directories(directories: &[Directory], dest: &Path) -> Result<FileAttrs, Error> {
+    let mut attrs = FileAttrs::new();
+    for directory in directories {
+        let path = directory.package_path();
+        fs::create_dir_all(dest.join(&path))?;
+        attrs.insert(path, directory.attr());
+    }
+    Ok(attrs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_mode(mode: &str) -> Result<Option<u32>, toml::de::Error> {
+        let content = format!("from = \"hello\"\nto = \"usr/bin/hello\"\n{mode}");
+        toml::from_str::<FileSet>(&content).map(|file_set| file_set.mode)
+    }
+
+    #[test]
+    fn test_deserialize_mode() {
+        assert_eq!(parse_mode("").unwrap(), None);
+        assert_eq!(parse_mode(r#"mode = "0755""#).unwrap(), Some(0o755));
+        assert_eq!(parse_mode(r#"mode = "0o755""#).unwrap(), Some(0o755));
+        assert_eq!(parse_mode(r#"mode = "4755""#).unwrap(), Some(0o4755));
+        assert!(parse_mode(r#"mode = "0789""#).is_err());
+        assert!(parse_mode(r#"mode = "17777""#).is_err());
+
+        // `755` is decimal in toml, integers with only octal digits are rejected.
+        assert_eq!(parse_mode("mode = 0o755").unwrap(), Some(0o755));
+        assert_eq!(parse_mode("mode = 0o600").unwrap(), Some(0o600));
+        assert_eq!(parse_mode("mode = 493").unwrap(), Some(0o755));
+        assert!(parse_mode("mode = 755").is_err());
+        assert!(parse_mode("mode = 644").is_err());
+        assert!(parse_mode("mode = 0o644").is_err());
+        assert!(parse_mode("mode = -1").is_err());
+        assert!(parse_mode("mode = 0o17777").is_err());
+    }
+}
@@ -8,6 +8,16 @@ use std::io;
 use std::io::Read;
 use std::path::Path;
 
+/// # Errors
+/// Returns error if failed to open file or read its content.
+pub fn md5sum<P: AsRef<Path>>(file: P) -> Result<String, io::Error> {
+    let mut reader = File::open(&file)?;
+    let mut context = md5::Context::new();
+    io::copy(&mut reader, &mut context)?;
+    let digest = context.compute();
+    Ok(format!("{digest:x}"))
+}
+
 /// # Errors
 /// Returns error if failed to open file or read its content.
 pub fn sha256sum<P: AsRef<Path>>(file: P) -> Result<String, io::Error> {
@@ -3,12 +3,19 @@
 // in the LICENSE file.
 
 pub mod archive;
+pub mod changelog;
 pub mod compress;
 pub mod config;
+pub mod elf;
 mod file_pattern;
 pub mod fileset;
 pub mod hash;
+pub mod payload;
+pub mod relation;
+pub mod script;
+pub mod strip;
+pub mod systemd;
 pub mod utils;
 
-pub use config::{Arch, GlobPatterns, Metadata, PlatformTarget};
+pub use config::{Arch, GlobPatterns, Metadata, PackageArch, PlatformTarget};
 pub use file_pattern::{expand_file_macro, expand_file_macro_simple};
@@ -0,0 +1,300 @@
+// Copyright (c) 2021 Xu Shaohua <shaohua@biofan.org>. All rights reserved.
+// Use of this source is governed by General Public License that can be found
+// in the LICENSE file.
+
+//! File list of package payload, mapped to source files on disk.
+//!
+//! Files are read from their sources when archive is written,
+//! instead of being copied to a staging folder first.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+use crate::base::fileset::{Directory, FileAttr, FileSet, Symlink};
+use crate::error::{Error, ErrorKind};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EntryKind {
+    Directory,
+
+    /// Regular file, with path to its source file.
+    File(PathBuf),
+
+    /// Symbolic link, with its target.
+    Symlink(PathBuf),
+}
+
+#[derive(Debug, Clone)]
+pub struct PayloadEntry {
+    pub kind: EntryKind,
+
+    /// Mode, owner and group. If mode is not set, mode of source file
+    /// is used for files, and `0o755` for directories.
+    pub attr: FileAttr,
+}
+
+/// Entries of payload, keyed by path relative to root of package.
+///
+/// Parent directories are always listed before their children.
+#[derive(Debug, Clone, Default)]
+pub struct Payload {
+    entries: BTreeMap<PathBuf, PayloadEntry>,
+}
+
+impl Payload {
+    /// Collect files matched by `files`, like `copy_filesets()` does.
+    ///
+    /// # Errors
+    /// Returns error if glob pattern is invalid or no file is matched.
+    pub fn from_filesets(files: &[FileSet], src: &str) -> Result<Self, Error> {
+        log::info!("Payload::from_filesets() src: {src}");
+        let mut payload = Self::default();
+        for file in files {
+            payload.add_fileset(file, src)?;
+        }
+        Ok(payload)
+    }
+
+    /// Collect all files and directories in `dir`, owned by `root`.
+    ///
+    /// # Errors
+    /// Returns error if failed to walk through `dir`.
+    pub fn from_dir(dir: &Path) -> Result<Self, Error> {
+        let mut payload = Self::default();
+        payload.add_dir(dir, Path::new(""), &FileAttr::default())?;
+        Ok(payload)
+    }
+
+    fn add_fileset(&mut self, file: &FileSet, src: &str) -> Result<(), Error> {
+        let src_pattern = format!("{src}/{}", &file.from);
+        let entries = glob::glob(&src_pattern).map_err(|err| {
+            Error::from_string(
+                ErrorKind::GlobPatternError,
+                format!("Failed to crate glob pattern for {src_pattern}, error: {err}"),
+            )
+        })?;
+        let attr = file.attr().unwrap_or_default();
+        let dest_path = PathBuf::from(&file.to);
+        let mut entry_not_match = true;
+        for entry in entries {
+            entry_not_match = false;
+            let entry = entry?;
+            let metadata = fs::symlink_metadata(&entry)?;
+            if metadata.is_file() {
+                self.add_file(dest_path.clone(), entry, attr.clone());
+            } else if metadata.is_symlink() {
+                self.add_symlink(dest_path.clone(), fs::read_link(&entry)?, attr.clone());
+            } else if metadata.is_dir() {
+                // Folder is copied into `to` folder, like `fs_extra::dir::copy()`.
+                let dir_name = entry.file_name().unwrap_or_default();
+                self.add_dir(&entry, &dest_path.join(dir_name), &attr)?;
+            } else {
+                return Err(Error::from_string(
+                    ErrorKind::IoError,
+                    format!("Unsupported file type: {}", entry.display()),
+                ));
+            }
+        }
+        if entry_not_match {
+            Err(Error::from_string(
+                ErrorKind::GlobError,
+                format!("No file is matched with pattern `{src_pattern}`"),
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn add_dir(&mut self, dir: &Path, dest_path: &Path, attr: &FileAttr) -> Result<(), Error> {
+        let dir_attr = FileAttr {
+            mode: None,
+            ..attr.clone()
+        };
+        for entry in WalkDir::new(dir).sort_by_file_name() {
+            let entry = entry?;
+            let path = dest_path.join(entry.path().strip_prefix(dir)?);
+            let file_type = entry.file_type();
+            if file_type.is_dir() {
+                self.add_directory(path, dir_attr.clone());
+            } else if file_type.is_symlink() {
+                self.add_symlink(path, fs::read_link(entry.path())?, dir_attr.clone());
+            } else {
+                self.add_file(path, entry.path().to_path_buf(), attr.clone());
+            }
+        }
+        Ok(())
+    }
+
+    /// Add a regular file at `path` read from `source`, and its parent directories.
+    pub fn add_file(&mut self, path: PathBuf, source: PathBuf, attr: FileAttr) {
+        self.add_parents(&path);
+        self.entries.insert(
+            path,
+            PayloadEntry {
+                kind: EntryKind::File(source),
+                attr,
+            },
+        );
+    }
+
+    /// Add a directory at `path`, and its parent directories.
+    pub fn add_directory(&mut self, path: PathBuf, attr: FileAttr) {
+        if path.as_os_str().is_empty() {
+            return;
+        }
+        self.add_parents(&path);
+        self.entries.insert(
+            path,
+            PayloadEntry {
+                kind: EntryKind::Directory,
+                attr,
+            },
+        );
+    }
+
+    /// Add a symbolic link at `path` pointing to `target`, and its parent directories.
+    pub fn add_symlink(&mut self, path: PathBuf, target: PathBuf, attr: FileAttr) {
+        self.add_parents(&path);
+        self.entries.insert(
+            path,
+            PayloadEntry {
+                kind: EntryKind::Symlink(target),
+                attr,
+            },
+        );
+    }
+
+    /// Add symbolic links declared in config.
+    pub fn add_symlinks(&mut self, symlinks: &[Symlink]) {
+        for symlink in symlinks {
+            self.add_symlink(
+                symlink.package_path(),
+                PathBuf::from(&symlink.target),
+                FileAttr::default(),
+            );
+        }
+    }
+
+    /// Add directories declared in config, with their mode, owner and group.
+    pub fn add_directories(&mut self, directories: &[Directory]) {
+        for directory in directories {
+            self.add_directory(directory.package_path(), directory.attr());
+        }
+    }
+
+    fn add_parents(&mut self, path: &Path) {
+        for parent in path.ancestors().skip(1) {
+            if parent.as_os_str().is_empty() {
+                break;
+            }
+            self.entries
+                .entry(parent.to_path_buf())
+                .or_insert_with(|| PayloadEntry {
+                    kind: EntryKind::Directory,
+                    attr: FileAttr::default(),
+                });
+        }
+    }
+
+    /// Replace source of file at `path`, like a stripped copy of that file.
+    pub fn set_source(&mut self, path: &Path, source: PathBuf) {
+        if let Some(entry) = self.entries.get_mut(path) {
+            entry.kind = EntryKind::File(source);
+        }
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Check whether `path` is a regular file in payload.
+    #[must_use]
+    pub fn is_file(&self, path: &Path) -> bool {
+        self.entries
+            .get(path)
+            .is_some_and(|entry| matches!(entry.kind, EntryKind::File(_)))
+    }
+
+    /// Iterate over all entries, parent directories first.
+    pub fn iter(&self) -> impl Iterator<Item = (&Path, &PayloadEntry)> {
+        self.entries
+            .iter()
+            .map(|(path, entry)| (path.as_path(), entry))
+    }
+
+    /// Iterate over regular files, with path in package and path to source file.
+    pub fn files(&self) -> impl Iterator<Item = (&Path, &Path)> {
+        self.iter().filter_map(|(path, entry)| match &entry.kind {
+            EntryKind::File(source) => Some((path, source.as_path())),
+            EntryKind::Directory | EntryKind::Symlink(_) => None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parent_directories() {
+        let mut payload = Payload::default();
+        payload.add_file(
+            PathBuf::from("usr/share/hello/hello.png"),
+            PathBuf::from("hello.png"),
+            FileAttr::default(),
+        );
+        payload.add_file(
+            PathBuf::from("usr/bin/hello"),
+            PathBuf::from("target/hello"),
+            FileAttr::default(),
+        );
+        let paths: Vec<&Path> = payload.iter().map(|(path, _entry)| path).collect();
+        assert_eq!(
+            paths,
+            [
+                "usr",
+                "usr/bin",
+                "usr/bin/hello",
+                "usr/share",
+                "usr/share/hello",
+                "usr/share/hello/hello.png"
+            ]
+            .map(Path::new)
+        );
+        assert!(payload.is_file(Path::new("usr/bin/hello")));
+        assert!(!payload.is_file(Path::new("usr/bin")));
+        assert_eq!(payload.files().count(), 2);
+    }
+
+    #[test]
+    fn test_symlinks_and_directories() {
+        let symlinks = [Symlink {
+            path: "/usr/lib/libhello.so".to_owned(),
+            target: "libhello.so.1".to_owned(),
+        }];
+        let directories = [Directory {
+            path: "var/lib/hello".to_owned(),
+            mode: Some(0o750),
+            owner: Some("hello".to_owned()),
+            group: None,
+        }];
+        let mut payload = Payload::default();
+        payload.add_symlinks(&symlinks);
+        payload.add_directories(&directories);
+
+        let entries: BTreeMap<&Path, &PayloadEntry> = payload.iter().collect();
+        assert_eq!(
+            entries[Path::new("usr/lib/libhello.so")].kind,
+            EntryKind::Symlink(PathBuf::from("libhello.so.1"))
+        );
+        let dir_entry = entries[Path::new("var/lib/hello")];
+        assert_eq!(dir_entry.kind, EntryKind::Directory);
+        assert_eq!(dir_entry.attr.mode, Some(0o750));
+        assert_eq!(dir_entry.attr.group, "root");
+        assert!(entries.contains_key(Path::new("var/lib")));
+        assert_eq!(payload.files().count(), 0);
+    }
+}
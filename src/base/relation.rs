@@ -0,0 +1,674 @@
+// Copyright (c) 2021 Xu Shaohua <shaohua@biofan.org>. All rights reserved.
+// Use of this source is governed by General Public License that can be found
+// in the LICENSE file.
+
+//! Package relations, like `Depends` of deb and `Requires` of rpm.
+//!
+//! Relations are written in Debian syntax, as a comma separated string
+//! or a list of strings:
+//!
+//! ```toml
+//! depends = "libc6 (>= 2.36), default-mta | mail-transport-agent"
+//! recommends = ["python3:any", "libfoo1 [amd64 arm64]"]
+//! ```
+//!
+//! Rpm style version constraints without parentheses, like `foo >= 1.0`,
+//! and rpm capabilities like `libc.so.6()(64bit)` are also accepted.
+
+use serde::{Deserialize, Serialize};
+use std::fmt::{self, Write as _};
+use std::str::FromStr;
+
+use super::config::{Arch, PackageArch};
+use crate::error::{Error, ErrorKind};
+
+/// Version comparison operator.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VersionOp {
+    Less,
+    LessEqual,
+    Equal,
+    GreaterEqual,
+    Greater,
+}
+
+impl VersionOp {
+    /// Operators sorted by length, so that `<<` is matched before `<`.
+    const ALL: [(&'static str, Self); 7] = [
+        ("<<", Self::Less),
+        ("<=", Self::LessEqual),
+        (">=", Self::GreaterEqual),
+        (">>", Self::Greater),
+        ("=", Self::Equal),
+        ("<", Self::Less),
+        (">", Self::Greater),
+    ];
+
+    /// Operator in deb control file, like `>>`.
+    #[must_use]
+    pub const fn deb_str(self) -> &'static str {
+        match self {
+            Self::Less => "<<",
+            Self::LessEqual => "<=",
+            Self::Equal => "=",
+            Self::GreaterEqual => ">=",
+            Self::Greater => ">>",
+        }
+    }
+
+    /// Operator in rpm spec file, like `>`.
+    #[must_use]
+    pub const fn rpm_str(self) -> &'static str {
+        match self {
+            Self::Less => "<",
+            Self::LessEqual => "<=",
+            Self::Equal => "=",
+            Self::GreaterEqual => ">=",
+            Self::Greater => ">",
+        }
+    }
+
+    /// Sense flags of rpm dependency, like `RPMSENSE_LESS | RPMSENSE_EQUAL`.
+    #[must_use]
+    pub const fn rpm_flags(self) -> u32 {
+        const LESS: u32 = 0x02;
+        const GREATER: u32 = 0x04;
+        const EQUAL: u32 = 0x08;
+        match self {
+            Self::Less => LESS,
+            Self::LessEqual => LESS | EQUAL,
+            Self::Equal => EQUAL,
+            Self::GreaterEqual => GREATER | EQUAL,
+            Self::Greater => GREATER,
+        }
+    }
+
+    /// Parse operator at start of `s`, returns operator and the rest of `s`.
+    fn parse_prefix(s: &str) -> Option<(Self, &str)> {
+        Self::ALL
+            .iter()
+            .find_map(|(token, op)| s.strip_prefix(token).map(|rest| (*op, rest)))
+    }
+}
+
+/// A single package in relation, like `libc6:amd64 (>= 2.36) [amd64]`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dependency {
+    pub name: String,
+
+    /// Multiarch qualifier, like `any` in `python3:any`.
+    pub arch_qualifier: Option<String>,
+
+    /// Version constraint.
+    pub version: Option<(VersionOp, String)>,
+
+    /// Architectures this dependency applies to, like `[amd64 arm64]`.
+    pub arches: Vec<Arch>,
+
+    /// Whether `arches` are excluded, like `[!i386]`.
+    pub arches_negated: bool,
+}
+
+impl Dependency {
+    /// Check whether this dependency applies to `arch`.
+    ///
+    /// Arch independent packages match negated architecture lists only.
+    #[must_use]
+    pub fn matches_arch(&self, arch: PackageArch) -> bool {
+        let contains = match arch {
+            PackageArch::Target(arch) => self.arches.contains(&arch),
+            PackageArch::All => false,
+        };
+        self.arches.is_empty() || contains != self.arches_negated
+    }
+
+    fn to_deb(&self) -> String {
+        let mut out = self.name.clone();
+        if let Some(qualifier) = &self.arch_qualifier {
+            out.push(':');
+            out.push_str(qualifier);
+        }
+        if let Some((op, version)) = &self.version {
+            let _ = write!(out, " ({} {version})", op.deb_str());
+        }
+        out
+    }
+
+    fn to_rpm(&self) -> String {
+        match &self.version {
+            Some((op, version)) => format!("{} {} {version}", self.name, op.rpm_str()),
+            None => self.name.clone(),
+        }
+    }
+}
+
+impl Dependency {
+    /// Format dependency with architecture list, named by `arch_name`.
+    fn to_deb_with_arches<F: Fn(Arch) -> String>(&self, arch_name: &F) -> String {
+        let mut out = self.to_deb();
+        if !self.arches.is_empty() {
+            let prefix = if self.arches_negated { "!" } else { "" };
+            let arches = self
+                .arches
+                .iter()
+                .map(|arch| format!("{prefix}{}", arch_name(*arch)))
+                .collect::<Vec<_>>();
+            let _ = write!(out, " [{}]", arches.join(" "));
+        }
+        out
+    }
+}
+
+/// Architectures are written with names in pifu config, like `armv7`.
+impl fmt::Display for Dependency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.to_deb_with_arches(&|arch: Arch| arch.to_string()))
+    }
+}
+
+impl FromStr for Dependency {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = |reason: &str| {
+            Error::from_string(
+                ErrorKind::InvalidConfError,
+                format!("Invalid relation `{}`: {reason}", s.trim()),
+            )
+        };
+
+        let s = s.trim();
+        let (name, arch_qualifier, mut rest) = split_name(s).map_err(&invalid)?;
+        if name.is_empty() {
+            return Err(invalid("package name is empty"));
+        }
+
+        rest = rest.trim_start();
+        let constraint = if let Some(inner) = rest.strip_prefix('(') {
+            let (inner, after) = inner
+                .split_once(')')
+                .ok_or_else(|| invalid("missing `)`"))?;
+            rest = after;
+            let (op, value) = VersionOp::parse_prefix(inner.trim_start())
+                .ok_or_else(|| invalid("invalid operator"))?;
+            Some((op, value.trim()))
+        } else if let Some((op, after)) = VersionOp::parse_prefix(rest) {
+            // Rpm style, like `foo >= 1.0`.
+            let after = after.trim_start();
+            let len = after.find([' ', '\t', '[']).unwrap_or(after.len());
+            rest = &after[len..];
+            Some((op, &after[..len]))
+        } else {
+            None
+        };
+        let version = if let Some((op, value)) = constraint {
+            if value.is_empty() {
+                return Err(invalid("version is empty"));
+            }
+            if !value
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || ".+~:_^-".contains(c))
+            {
+                return Err(invalid("invalid character in version"));
+            }
+            Some((op, value.to_owned()))
+        } else {
+            None
+        };
+
+        let mut arches = Vec::new();
+        let mut arches_negated = false;
+        rest = rest.trim_start();
+        if let Some(inner) = rest.strip_prefix('[') {
+            let (inner, after) = inner
+                .split_once(']')
+                .ok_or_else(|| invalid("missing `]`"))?;
+            rest = after;
+            let tokens: Vec<&str> = inner.split_whitespace().collect();
+            if tokens.is_empty() {
+                return Err(invalid("empty architecture list"));
+            }
+            arches_negated = tokens[0].starts_with('!');
+            for token in tokens {
+                let (negated, arch) = token
+                    .strip_prefix('!')
+                    .map_or((false, token), |arch| (true, arch));
+                if negated != arches_negated {
+                    return Err(invalid("mixed positive and negated architectures"));
+                }
+                let arch = Arch::from_str(arch)
+                    .map_err(|()| invalid(&format!("unknown architecture `{arch}`")))?;
+                arches.push(arch);
+            }
+        }
+
+        if !rest.trim().is_empty() {
+            return Err(invalid(&format!("unexpected `{}`", rest.trim())));
+        }
+
+        Ok(Self {
+            name: name.to_owned(),
+            arch_qualifier: arch_qualifier.map(ToOwned::to_owned),
+            version,
+            arches,
+            arches_negated,
+        })
+    }
+}
+
+/// Split package name, multiarch qualifier and the rest of `s`.
+///
+/// Parentheses attached to name are part of name, like `libc.so.6()(64bit)`,
+/// unless they contain a version constraint.
+fn split_name(s: &str) -> Result<(&str, Option<&str>, &str), &'static str> {
+    let mut end = 0;
+    let mut colon = None;
+    let bytes = s.as_bytes();
+    while end < bytes.len() {
+        match bytes[end] {
+            b'(' => {
+                let inner = s[end + 1..].trim_start();
+                if VersionOp::parse_prefix(inner).is_some() {
+                    break;
+                }
+                let close = s[end..].find(')').ok_or("missing `)`")?;
+                end += close + 1;
+                continue;
+            }
+            b':' => colon = Some(end),
+            b' ' | b'\t' | b'[' | b'<' | b'>' | b'=' => break,
+            b',' | b'|' | b')' | b']' => return Err("unexpected separator"),
+            _ => {}
+        }
+        end += 1;
+    }
+
+    let (name, rest) = s.split_at(end);
+    let valid_char = |c: char| c.is_ascii_alphanumeric() || "+._-/".contains(c);
+    if let Some(colon) = colon {
+        let qualifier = &name[colon + 1..];
+        let name = &name[..colon];
+        if qualifier.is_empty() || !qualifier.chars().all(|c| c.is_ascii_alphanumeric()) {
+            return Err("invalid architecture qualifier");
+        }
+        if !name.chars().all(valid_char) {
+            return Err("invalid character in package name");
+        }
+        Ok((name, Some(qualifier), rest))
+    } else {
+        let outer = name.split('(').next().unwrap_or_default();
+        if !outer.chars().all(valid_char) {
+            return Err("invalid character in package name");
+        }
+        Ok((name, None, rest))
+    }
+}
+
+/// Alternatives of a relation, like `default-mta | mail-transport-agent`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Relation {
+    pub alternatives: Vec<Dependency>,
+}
+
+impl Relation {
+    /// Get alternatives applied to `arch`, or `None` if no alternative is left.
+    fn filter_arch(&self, arch: PackageArch) -> Option<Vec<&Dependency>> {
+        let alternatives: Vec<&Dependency> = self
+            .alternatives
+            .iter()
+            .filter(|dep| dep.matches_arch(arch))
+            .collect();
+        if alternatives.is_empty() {
+            None
+        } else {
+            Some(alternatives)
+        }
+    }
+}
+
+impl fmt::Display for Relation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let alternatives = self
+            .alternatives
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        write!(f, "{}", alternatives.join(" | "))
+    }
+}
+
+impl FromStr for Relation {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let alternatives = s
+            .split('|')
+            .map(Dependency::from_str)
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self { alternatives })
+    }
+}
+
+/// List of relations, like value of `Depends` field.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(try_from = "RelationsRepr", into = "String")]
+pub struct Relations(Vec<Relation>);
+
+/// Relations in config file, as a comma separated string or a list.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RelationsRepr {
+    String(String),
+    List(Vec<String>),
+}
+
+impl TryFrom<RelationsRepr> for Relations {
+    type Error = Error;
+
+    fn try_from(repr: RelationsRepr) -> Result<Self, Self::Error> {
+        match repr {
+            RelationsRepr::String(s) => s.parse(),
+            RelationsRepr::List(list) => {
+                let mut relations = Self::default();
+                for item in list {
+                    relations.0.extend(item.parse::<Self>()?.0);
+                }
+                Ok(relations)
+            }
+        }
+    }
+}
+
+impl From<Relations> for String {
+    fn from(relations: Relations) -> Self {
+        relations.to_string()
+    }
+}
+
+impl FromStr for Relations {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let relations = s
+            .split(',')
+            .filter(|item| !item.trim().is_empty())
+            .map(Relation::from_str)
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self(relations))
+    }
+}
+
+impl fmt::Display for Relations {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let relations = self.0.iter().map(ToString::to_string).collect::<Vec<_>>();
+        write!(f, "{}", relations.join(", "))
+    }
+}
+
+impl<'a> IntoIterator for &'a Relations {
+    type Item = &'a Relation;
+    type IntoIter = std::slice::Iter<'a, Relation>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter()
+    }
+}
+
+impl Relations {
+    pub fn iter(&self) -> std::slice::Iter<'_, Relation> {
+        self.0.iter()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn push(&mut self, relation: Relation) {
+        self.0.push(relation);
+    }
+
+    /// Format relations in Debian syntax with architecture lists kept,
+    /// like `Build-Depends` field in source package.
+    ///
+    /// Architectures are named by `arch_name`, like `deb::arch_name`.
+    #[must_use]
+    pub fn to_deb_with_arches<F: Fn(Arch) -> String>(&self, arch_name: F) -> String {
+        self.0
+            .iter()
+            .map(|relation| {
+                relation
+                    .alternatives
+                    .iter()
+                    .map(|dep| dep.to_deb_with_arches(&arch_name))
+                    .collect::<Vec<_>>()
+                    .join(" | ")
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// Check whether package `name` is listed in any relation.
+    #[must_use]
+    pub fn contains_package(&self, name: &str) -> bool {
+        self.0
+            .iter()
+            .flat_map(|relation| &relation.alternatives)
+            .any(|dep| dep.name == name)
+    }
+
+    /// Validate relations of deb `field`, like `Provides`.
+    ///
+    /// # Errors
+    /// Returns error if package name is invalid in Debian policy,
+    /// or alternatives and version operators are not allowed in `field`.
+    pub fn validate_deb(&self, field: &str) -> Result<(), Error> {
+        let no_alternatives = matches!(field, "Conflicts" | "Breaks" | "Replaces" | "Provides");
+        for relation in &self.0 {
+            if no_alternatives && relation.alternatives.len() > 1 {
+                return Err(Error::from_string(
+                    ErrorKind::InvalidConfError,
+                    format!("Alternatives are not allowed in {field}: `{relation}`"),
+                ));
+            }
+            for dep in &relation.alternatives {
+                let valid_name = dep.name.len() > 1
+                    && dep
+                        .name
+                        .starts_with(|c: char| c.is_ascii_lowercase() || c.is_ascii_digit())
+                    && dep
+                        .name
+                        .chars()
+                        .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || "+.-".contains(c));
+                if !valid_name {
+                    return Err(Error::from_string(
+                        ErrorKind::InvalidConfError,
+                        format!("Invalid deb package name in {field}: `{}`", dep.name),
+                    ));
+                }
+                if field == "Provides"
+                    && dep
+                        .version
+                        .as_ref()
+                        .is_some_and(|(op, _)| *op != VersionOp::Equal)
+                {
+                    return Err(Error::from_string(
+                        ErrorKind::InvalidConfError,
+                        format!("Only `=` is allowed in Provides: `{dep}`"),
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Render relations applied to `arch` as value of deb control field.
+    ///
+    /// Returns `None` if no relation is left.
+    #[must_use]
+    pub fn to_deb(&self, arch: PackageArch) -> Option<String> {
+        let relations = self
+            .0
+            .iter()
+            .filter_map(|relation| relation.filter_arch(arch))
+            .map(|alternatives| {
+                alternatives
+                    .iter()
+                    .map(|dep| dep.to_deb())
+                    .collect::<Vec<_>>()
+                    .join(" | ")
+            })
+            .collect::<Vec<_>>();
+        if relations.is_empty() {
+            None
+        } else {
+            Some(relations.join(", "))
+        }
+    }
+
+    /// Render relations applied to `arch` as values of rpm tag, one per relation.
+    ///
+    /// Alternatives are written as rich dependency, like `(foo or bar)`.
+    #[must_use]
+    pub fn to_rpm(&self, arch: PackageArch) -> Vec<String> {
+        self.to_rpm_deps(arch)
+            .into_iter()
+            .map(|(name, version)| match version {
+                Some((op, version)) => format!("{name} {} {version}", op.rpm_str()),
+                None => name,
+            })
+            .collect()
+    }
+
+    /// Get relations applied to `arch` as name and version constraint of rpm header.
+    ///
+    /// Rich dependency of alternatives is kept in name, without version.
+    #[must_use]
+    pub fn to_rpm_deps(&self, arch: PackageArch) -> Vec<(String, Option<(VersionOp, String)>)> {
+        self.0
+            .iter()
+            .filter_map(|relation| relation.filter_arch(arch))
+            .map(|alternatives| {
+                if alternatives.len() == 1 {
+                    (
+                        alternatives[0].name.clone(),
+                        alternatives[0].version.clone(),
+                    )
+                } else {
+                    let alternatives = alternatives
+                        .iter()
+                        .map(|dep| dep.to_rpm())
+                        .collect::<Vec<_>>();
+                    (format!("({})", alternatives.join(" or ")), None)
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_relations() {
+        let relations: Relations =
+            "libc6 (>= 2.36), default-mta | mail-transport-agent, python3:any"
+                .parse()
+                .unwrap();
+        assert_eq!(
+            relations.to_deb(Arch::X86_64.into()).unwrap(),
+            "libc6 (>= 2.36), default-mta | mail-transport-agent, python3:any"
+        );
+        assert_eq!(
+            relations.to_rpm(Arch::X86_64.into()),
+            [
+                "libc6 >= 2.36",
+                "(default-mta or mail-transport-agent)",
+                "python3"
+            ]
+        );
+
+        let relations: Relations = "foo >= 1.0, bar(<< 2~rc1), libc.so.6()(64bit)"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            relations.to_rpm(Arch::X86_64.into()),
+            ["foo >= 1.0", "bar < 2~rc1", "libc.so.6()(64bit)"]
+        );
+        assert_eq!(
+            relations.to_string(),
+            "foo (>= 1.0), bar (<< 2~rc1), libc.so.6()(64bit)"
+        );
+    }
+
+    #[test]
+    fn test_arch_restrictions() {
+        let relations: Relations = "libfoo [amd64 arm64] | libbar, libbaz [!i386]"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            relations.to_deb(Arch::X86_64.into()).unwrap(),
+            "libfoo | libbar, libbaz"
+        );
+        assert_eq!(relations.to_deb(Arch::X86.into()).unwrap(), "libbar");
+        // Display uses names in pifu config, which are parsed back.
+        assert_eq!(
+            relations.to_string(),
+            "libfoo [x86_64 aarch64] | libbar, libbaz [!x86]"
+        );
+        assert_eq!(relations.to_string().parse::<Relations>().unwrap(), relations);
+        assert_eq!(
+            relations.to_deb_with_arches(|arch| match arch {
+                Arch::X86 => "i386".to_owned(),
+                _ => arch.to_string(),
+            }),
+            "libfoo [x86_64 aarch64] | libbar, libbaz [!i386]"
+        );
+    }
+
+    #[test]
+    fn test_invalid_relations() {
+        for input in [
+            "foo (>= )",
+            "foo (~ 1.0)",
+            "foo (>= 1.0",
+            "foo [amd64 !i386]",
+            "foo [sparc]",
+            "foo bar",
+            "foo:",
+            "| foo",
+        ] {
+            assert!(input.parse::<Relations>().is_err(), "{input}");
+        }
+
+        let relations: Relations = "Foo_Bar".parse().unwrap();
+        assert!(relations.validate_deb("Depends").is_err());
+        let relations: Relations = "foo | bar".parse().unwrap();
+        assert!(relations.validate_deb("Conflicts").is_err());
+        let relations: Relations = "foo (>= 1.0)".parse().unwrap();
+        assert!(relations.validate_deb("Provides").is_err());
+        assert!(relations.validate_deb("Depends").is_ok());
+    }
+
+    #[test]
+    fn test_arch_names() {
+        let relations: Relations = "libfoo [armhf ppc64el], libbar [!loong64]".parse().unwrap();
+        assert_eq!(
+            relations.to_deb(Arch::Armv7.into()).unwrap(),
+            "libfoo, libbar"
+        );
+        assert_eq!(
+            relations.to_deb(Arch::Ppc64le.into()).unwrap(),
+            "libfoo, libbar"
+        );
+        assert_eq!(relations.to_deb(Arch::Riscv64.into()).unwrap(), "libbar");
+        assert!(relations.to_deb(Arch::LoongArch64.into()).is_none());
+
+        let relations: Relations = "libfoo [mips64el], libbar [!mips64el]".parse().unwrap();
+        assert_eq!(relations.to_deb(Arch::Mips64.into()).unwrap(), "libfoo");
+        assert_eq!(relations.to_deb(Arch::X86_64.into()).unwrap(), "libbar");
+        // Only negated lists match arch independent packages.
+        assert_eq!(relations.to_deb(PackageArch::All).unwrap(), "libbar");
+    }
+}
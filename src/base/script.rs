@@ -0,0 +1,174 @@
+// Copyright (c) 2021 Xu Shaohua <shaohua@biofan.org>. All rights reserved.
+// Use of this source is governed by General Public License that can be found
+// in the LICENSE file.
+
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+use super::config::{PackageArch, PlatformTarget};
+use super::file_pattern::expand_file_macro;
+use crate::config::Config;
+use crate::error::{Error, ErrorKind};
+
+/// Token in script to be replaced with generated snippets, as debhelper does.
+pub const SNIPPET_TOKEN: &str = "#DEBHELPER#";
+
+const SCRIPT_HEADER: &str = "#!/bin/sh\nset -e\n\n";
+
+/// Shell script embedded into package, like deb maintainer scripts.
+///
+/// ```toml
+/// postinst = { file = "pkg/postinst.sh" }
+/// prerm = { inline = "systemctl stop ${name}" }
+/// ```
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Script {
+    /// Path to script file, relative to `src_dir`.
+    File(String),
+
+    /// Script content.
+    Inline(String),
+}
+
+impl Script {
+    /// Read content of script and expand pifu macros in it.
+    ///
+    /// `#!/bin/sh` is prepended if script has no shebang line.
+    ///
+    /// # Errors
+    /// Returns error if failed to read script file or failed to expand macros.
+    pub fn read(
+        &self,
+        conf: &Config,
+        arch: PackageArch,
+        target: PlatformTarget,
+    ) -> Result<String, Error> {
+        let mut content = self.expand(conf, arch, target)?;
+        if !content.starts_with("#!") {
+            content.insert_str(0, SCRIPT_HEADER);
+        }
+        if !content.ends_with('\n') {
+            content.push('\n');
+        }
+        Ok(content)
+    }
+
+    /// Read content of script and expand pifu macros in it, without shebang line added.
+    ///
+    /// # Errors
+    /// Returns error if failed to read script file or failed to expand macros.
+    pub fn expand(
+        &self,
+        conf: &Config,
+        arch: PackageArch,
+        target: PlatformTarget,
+    ) -> Result<String, Error> {
+        let content = self.content(conf)?;
+        expand_file_macro(&content, conf, arch, target)
+    }
+
+    /// Read content of script, without macros expanded.
+    ///
+    /// # Errors
+    /// Returns error if failed to read script file.
+    pub fn content(&self, conf: &Config) -> Result<String, Error> {
+        let content = match self {
+            Self::File(path) => {
+                let path = Path::new(&conf.metadata.src_dir).join(path);
+                fs::read_to_string(&path).map_err(|err| {
+                    Error::from_string(
+                        ErrorKind::IoError,
+                        format!("Failed to read script file {}, err: {err}", path.display()),
+                    )
+                })?
+            }
+            Self::Inline(content) => content.clone(),
+        };
+        Ok(content)
+    }
+}
+
+/// Insert generated `snippet` into `script` at `#DEBHELPER#` token.
+///
+/// If token is not found, snippet is inserted after shebang and `set` lines
+/// at top of script, so that it runs even if script ends with `exit 0`.
+/// Script without token is returned unchanged if `snippet` is empty.
+/// A new script is created if `script` is `None`.
+#[must_use]
+pub fn insert_snippet(script: Option<String>, snippet: &str) -> String {
+    match script {
+        Some(script) if script.contains(SNIPPET_TOKEN) => {
+            script.replacen(SNIPPET_TOKEN, snippet.trim_end(), 1)
+        }
+        Some(script) if snippet.is_empty() => script,
+        Some(mut script) => {
+            log::warn!(
+                "{SNIPPET_TOKEN} not found in script, generated snippet is inserted at top of it"
+            );
+            let offset = header_len(&script);
+            script.insert_str(offset, snippet);
+            script
+        }
+        None => format!("{SCRIPT_HEADER}{snippet}"),
+    }
+}
+
+/// Get length of shebang line and the following `set` lines, like `set -e`.
+fn header_len(script: &str) -> usize {
+    let mut offset = 0;
+    for (index, line) in script.split_inclusive('\n').enumerate() {
+        let trimmed = line.trim();
+        let is_header = if index == 0 {
+            trimmed.starts_with("#!") || trimmed.starts_with("set ")
+        } else {
+            trimmed.starts_with("set ")
+        };
+        if !is_header || !line.ends_with('\n') {
+            break;
+        }
+        offset += line.len();
+    }
+    offset
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SNIPPET: &str = "deb-systemd-helper enable 'hello.service'\n";
+
+    #[test]
+    fn test_insert_snippet() {
+        assert_eq!(
+            insert_snippet(Some("#!/bin/sh\n#DEBHELPER#\nexit 0\n".to_owned()), SNIPPET),
+            "#!/bin/sh\ndeb-systemd-helper enable 'hello.service'\nexit 0\n"
+        );
+        assert_eq!(
+            insert_snippet(None, SNIPPET),
+            "#!/bin/sh\nset -e\n\ndeb-systemd-helper enable 'hello.service'\n"
+        );
+
+        // Snippet runs before `exit 0` at end of script.
+        assert_eq!(
+            insert_snippet(
+                Some("#!/bin/sh\nset -e\necho configured\nexit 0\n".to_owned()),
+                SNIPPET
+            ),
+            "#!/bin/sh\nset -e\ndeb-systemd-helper enable 'hello.service'\necho configured\nexit 0\n"
+        );
+        assert_eq!(
+            insert_snippet(Some("#!/bin/sh\necho configured\n".to_owned()), ""),
+            "#!/bin/sh\necho configured\n"
+        );
+        assert_eq!(
+            insert_snippet(Some("#!/bin/sh\n#DEBHELPER#\n".to_owned()), ""),
+            "#!/bin/sh\n\n"
+        );
+        assert_eq!(
+            insert_snippet(Some("echo configured\nexit 0\n".to_owned()), SNIPPET),
+            "deb-systemd-helper enable 'hello.service'\necho configured\nexit 0\n"
+        );
+    }
+}
@@ -0,0 +1,276 @@
+// Copyright (c) 2021 Xu Shaohua <shaohua@biofan.org>. All rights reserved.
+// Use of this source is governed by General Public License that can be found
+// in the LICENSE file.
+
+//! Strip ELF files and split their debug info, in the spirit of `dh_strip`.
+
+use goblin::elf::header::{ET_DYN, ET_EXEC};
+use std::ffi::OsString;
+use std::fs::{self, File};
+#[cfg(not(target_os = "windows"))]
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use walkdir::WalkDir;
+
+use super::config::{get_target_arch, Arch, PackageArch};
+use super::elf;
+use super::payload::Payload;
+use crate::error::{Error, ErrorKind};
+
+/// Directory of detached debug files, relative to root of package.
+pub const DEBUG_DIR: &str = "usr/lib/debug";
+
+/// Debug info saved from an ELF file.
+#[derive(Debug, Clone)]
+pub struct DebugInfo {
+    /// Path of ELF file, relative to stripped folder.
+    pub path: PathBuf,
+
+    pub build_id: Option<String>,
+}
+
+/// `strip` and `objcopy` programs of binutils for target arch.
+struct Binutils {
+    strip: String,
+    objcopy: String,
+}
+
+impl Binutils {
+    /// Use host tools for host arch and arch independent packages,
+    /// and cross tools like `aarch64-linux-gnu-strip` for other arches.
+    fn new(arch: PackageArch) -> Result<Self, Error> {
+        let prefix = match arch {
+            PackageArch::Target(arch) if get_target_arch() != Some(arch) => {
+                format!("{}-", gnu_triple(arch))
+            }
+            _ => String::new(),
+        };
+        let binutils = Self {
+            strip: format!("{prefix}strip"),
+            objcopy: format!("{prefix}objcopy"),
+        };
+        let package = if prefix.is_empty() {
+            "binutils".to_owned()
+        } else {
+            format!("binutils-{}", prefix.trim_end_matches('-'))
+        };
+        for program in [&binutils.strip, &binutils.objcopy] {
+            if Command::new(program).arg("--version").output().is_err() {
+                return Err(Error::from_string(
+                    ErrorKind::StripError,
+                    format!(
+                        "`{program}` is required to strip ELF files for {arch}, please install `{package}` package"
+                    ),
+                ));
+            }
+        }
+        Ok(binutils)
+    }
+}
+
+/// Get GNU target triple of `arch`, used as prefix of cross binutils.
+const fn gnu_triple(arch: Arch) -> &'static str {
+    match arch {
+        Arch::X86 => "i686-linux-gnu",
+        Arch::X86_64 => "x86_64-linux-gnu",
+        Arch::AArch64 => "aarch64-linux-gnu",
+        Arch::Mips64 => "mips64el-linux-gnuabi64",
+        Arch::Armv7 => "arm-linux-gnueabihf",
+        Arch::Riscv64 => "riscv64-linux-gnu",
+        Arch::Ppc64le => "powerpc64le-linux-gnu",
+        Arch::S390x => "s390x-linux-gnu",
+        Arch::LoongArch64 => "loongarch64-linux-gnu",
+    }
+}
+
+/// Strip ELF executables and shared libraries in `dir`, built for `arch`.
+///
+/// If `debug_dir` is set, debug info of each file is saved into
+/// `debug_dir/usr/lib/debug/.build-id/xx/yyyy.debug` before stripping,
+/// and linked back to the stripped file with `.gnu_debuglink` section.
+/// Files without build-id are saved to `debug_dir/usr/lib/debug/<path>.debug`.
+///
+/// Returns files whose debug info is saved.
+///
+/// # Errors
+/// Returns error if failed to parse ELF files, or `strip` and `objcopy` of `arch`
+/// are not found, or failed to run them.
+pub fn strip_elf_files(
+    dir: &Path,
+    arch: PackageArch,
+    debug_dir: Option<&Path>,
+) -> Result<Vec<DebugInfo>, Error> {
+    log::info!("strip_elf_files() dir: {}", dir.display());
+    let mut elf_files = Vec::new();
+    for entry in WalkDir::new(dir).sort_by_file_name() {
+        let entry = entry?;
+        let path = entry.path();
+        if !entry.file_type().is_file() || path.strip_prefix(dir)?.starts_with(DEBUG_DIR) {
+            continue;
+        }
+        if elf::is_elf(path) {
+            elf_files.push(path.to_path_buf());
+        }
+    }
+
+    if elf_files.is_empty() {
+        return Ok(Vec::new());
+    }
+    let binutils = Binutils::new(arch)?;
+
+    let mut debug_infos = Vec::new();
+    for path in &elf_files {
+        let info = elf::read_elf_info(path)?;
+        if info.elf_type != ET_EXEC && info.elf_type != ET_DYN {
+            continue;
+        }
+
+        let debug_file = match debug_dir {
+            Some(debug_dir) if info.has_debug_info => {
+                let debug_file = get_debug_file(dir, path, info.build_id.as_deref(), debug_dir)?;
+                save_debug_info(&binutils, path, &debug_file)?;
+                debug_infos.push(DebugInfo {
+                    path: path.strip_prefix(dir)?.to_path_buf(),
+                    build_id: info.build_id,
+                });
+                Some(debug_file)
+            }
+            _ => None,
+        };
+
+        strip_file(&binutils, path)?;
+
+        if let Some(debug_file) = debug_file {
+            let mut debuglink = OsString::from("--add-gnu-debuglink=");
+            debuglink.push(&debug_file);
+            run_tool(Command::new(&binutils.objcopy).arg(debuglink).arg(path))?;
+        }
+    }
+
+    Ok(debug_infos)
+}
+
+/// Strip ELF files in `payload`, like [`strip_elf_files`].
+///
+/// Source files are kept untouched. ELF files are copied to `work_dir` and stripped
+/// there, and payload is updated to read those copies.
+///
+/// # Errors
+/// Returns error if failed to copy files or failed to strip them.
+pub fn strip_payload(
+    payload: &mut Payload,
+    work_dir: &Path,
+    arch: PackageArch,
+    debug_dir: Option<&Path>,
+) -> Result<Vec<DebugInfo>, Error> {
+    log::info!("strip_payload() work dir: {}", work_dir.display());
+    let mut copies = Vec::new();
+    for (path, source) in payload.files() {
+        if path.starts_with(DEBUG_DIR) || !elf::is_elf(source) {
+            continue;
+        }
+        let copy = work_dir.join(path);
+        if let Some(parent) = copy.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::copy(source, &copy)?;
+        let mtime = fs::metadata(source)?.modified()?;
+        copies.push((path.to_path_buf(), copy, mtime));
+    }
+    if copies.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let debug_infos = strip_elf_files(work_dir, arch, debug_dir)?;
+    for (path, copy, mtime) in copies {
+        // Keep mtime of source file, so that packages are reproducible.
+        File::open(&copy)?.set_modified(mtime)?;
+        payload.set_source(&path, copy);
+    }
+    Ok(debug_infos)
+}
+
+/// Get path to debug file of `path`.
+fn get_debug_file(
+    dir: &Path,
+    path: &Path,
+    build_id: Option<&str>,
+    debug_dir: &Path,
+) -> Result<PathBuf, Error> {
+    let root = debug_dir.join(DEBUG_DIR);
+    match build_id {
+        Some(build_id) if build_id.len() > 2 => {
+            let (prefix, rest) = build_id.split_at(2);
+            Ok(root
+                .join(".build-id")
+                .join(prefix)
+                .join(format!("{rest}.debug")))
+        }
+        _ => {
+            let mut debug_file = root.join(path.strip_prefix(dir)?).into_os_string();
+            debug_file.push(".debug");
+            Ok(PathBuf::from(debug_file))
+        }
+    }
+}
+
+/// Copy debug sections of `path` to `debug_file`.
+fn save_debug_info(binutils: &Binutils, path: &Path, debug_file: &Path) -> Result<(), Error> {
+    log::info!(
+        "save_debug_info() {} to {}",
+        path.display(),
+        debug_file.display()
+    );
+    if let Some(parent) = debug_file.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    run_tool(
+        Command::new(&binutils.objcopy)
+            .arg("--only-keep-debug")
+            .arg("--compress-debug-sections")
+            .arg(path)
+            .arg(debug_file),
+    )?;
+
+    #[cfg(not(target_os = "windows"))]
+    fs::set_permissions(debug_file, fs::Permissions::from_mode(0o644))?;
+
+    Ok(())
+}
+
+/// Strip symbols of `path`, like `dh_strip` does.
+fn strip_file(binutils: &Binutils, path: &Path) -> Result<(), Error> {
+    log::info!("strip_file() {}", path.display());
+    let mut cmd = Command::new(&binutils.strip);
+    cmd.arg("--remove-section=.comment")
+        .arg("--remove-section=.note");
+    let is_shared_lib = path
+        .file_name()
+        .is_some_and(|name| name.to_string_lossy().contains(".so"));
+    if is_shared_lib {
+        cmd.arg("--strip-unneeded");
+    }
+    run_tool(cmd.arg(path))
+}
+
+fn run_tool(cmd: &mut Command) -> Result<(), Error> {
+    let program = cmd.get_program().to_string_lossy().to_string();
+    let output = cmd.output().map_err(|err| {
+        Error::from_string(
+            ErrorKind::StripError,
+            format!("Failed to run `{program}` command, error: {err}, please check `binutils` package is installed"),
+        )
+    })?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(Error::from_string(
+            ErrorKind::StripError,
+            format!(
+                "`{program}` failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ),
+        ))
+    }
+}
@@ -0,0 +1,559 @@
+// Copyright (c) 2021 Xu Shaohua <shaohua@biofan.org>. All rights reserved.
+// Use of this source is governed by General Public License that can be found
+// in the LICENSE file.
+
+//! Install systemd unit files and generate scripts to enable and restart them,
+//! like `dh_installsystemd` and `systemd-rpm-macros` do.
+
+use serde::{Deserialize, Serialize};
+use std::fmt::Write as _;
+use std::fs;
+#[cfg(not(target_os = "windows"))]
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+
+use super::fileset::FileAttr;
+use super::payload::Payload;
+use super::utils::default_true;
+use crate::error::{Error, ErrorKind};
+
+/// Directory of systemd system units, relative to root of package.
+pub const SYSTEMD_UNIT_DIR: &str = "usr/lib/systemd/system";
+
+/// Systemd unit shipped in package.
+///
+/// ```toml
+/// [[linux.systemd_units]]
+/// file = "pkg/hello.service"
+/// restart_on_upgrade = false
+/// ```
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SystemdUnit {
+    /// Path to unit file, relative to `src_dir`.
+    pub file: String,
+
+    /// Unit name, like `hello.service`. Default is filename of `file`.
+    pub name: Option<String>,
+
+    /// Boolean - whether to enable and start the unit on install.
+    #[serde(default = "default_true")]
+    pub enable: bool,
+
+    /// Boolean - whether to restart the unit on upgrade, if it is running.
+    #[serde(default = "default_true")]
+    pub restart_on_upgrade: bool,
+}
+
+impl SystemdUnit {
+    /// Get unit name.
+    ///
+    /// # Errors
+    /// Returns error if unit name is invalid.
+    pub fn unit_name(&self) -> Result<String, Error> {
+        let name = self.name.clone().unwrap_or_else(|| {
+            Path::new(&self.file)
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default()
+        });
+        let is_valid = !name.is_empty()
+            && name.contains('.')
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || ":-_.\\@".contains(c));
+        if is_valid {
+            Ok(name)
+        } else {
+            Err(Error::from_string(
+                ErrorKind::InvalidConfError,
+                format!("Invalid systemd unit name: {name:?}"),
+            ))
+        }
+    }
+}
+
+/// Copy unit files to `usr/lib/systemd/system` in `dest_dir`.
+///
+/// # Errors
+/// Returns error if unit name is invalid or failed to copy unit files.
+pub fn install_units(units: &[SystemdUnit], src_dir: &str, dest_dir: &Path) -> Result<(), Error> {
+    if units.is_empty() {
+        return Ok(());
+    }
+    let unit_dir = dest_dir.join(SYSTEMD_UNIT_DIR);
+    fs::create_dir_all(&unit_dir)?;
+    for unit in units {
+        let src_file = Path::new(src_dir).join(&unit.file);
+        let dest_file = unit_dir.join(unit.unit_name()?);
+        log::info!(
+            "install_units() {} to {}",
+            src_file.display(),
+            dest_file.display()
+        );
+        fs::copy(&src_file, &dest_file).map_err(|err| {
+            Error::from_string(
+                ErrorKind::IoError,
+                format!(
+                    "Failed to copy systemd unit {}, err: {err}",
+                    src_file.display()
+                ),
+            )
+        })?;
+
+        #[cfg(not(target_os = "windows"))]
+        fs::set_permissions(&dest_file, fs::Permissions::from_mode(0o644))?;
+    }
+    Ok(())
+}
+
+/// Add unit files to `payload`, at `/usr/lib/systemd/system/<name>` with mode `0644`.
+///
+/// # Errors
+/// Returns error if unit name is invalid.
+pub fn add_units(units: &[SystemdUnit], src_dir: &str, payload: &mut Payload) -> Result<(), Error> {
+    for unit in units {
+        let path = Path::new(SYSTEMD_UNIT_DIR).join(unit.unit_name()?);
+        let attr = FileAttr {
+            mode: Some(0o644),
+            ..FileAttr::default()
+        };
+        payload.add_file(path, Path::new(src_dir).join(&unit.file), attr);
+    }
+    Ok(())
+}
+
+/// Get unit names, separated by space and quoted.
+fn quoted_names<'a>(units: impl Iterator<Item = &'a SystemdUnit>) -> Result<String, Error> {
+    let names = units
+        .map(|unit| unit.unit_name().map(|name| format!("'{name}'")))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(names.join(" "))
+}
+
+/// Generate snippet of deb maintainer script `name`, like `postinst`.
+///
+/// Returns `None` if nothing to do in this script.
+///
+/// # Errors
+/// Returns error if unit name is invalid.
+pub fn deb_script_snippet(units: &[SystemdUnit], name: &str) -> Result<Option<String>, Error> {
+    if units.is_empty() {
+        return Ok(None);
+    }
+    let all_units = quoted_names(units.iter())?;
+    let enable_units = quoted_names(units.iter().filter(|unit| unit.enable))?;
+    let restart_units = quoted_names(units.iter().filter(|unit| unit.restart_on_upgrade))?;
+
+    let mut snippet = String::new();
+    match name {
+        "postinst" => {
+            writeln!(
+                snippet,
+                r#"if [ "$1" = "configure" ] || [ "$1" = "abort-upgrade" ] || [ "$1" = "abort-deconfigure" ] || [ "$1" = "abort-remove" ] ; then"#
+            )?;
+            for unit in units {
+                let unit_name = unit.unit_name()?;
+                writeln!(
+                    snippet,
+                    "\tdeb-systemd-helper unmask '{unit_name}' >/dev/null || true"
+                )?;
+                if unit.enable {
+                    // `was-enabled` defaults to true, so new installations run `enable`.
+                    writeln!(
+                        snippet,
+                        "\tif deb-systemd-helper --quiet was-enabled '{unit_name}'; then\n\
+                         \t\tdeb-systemd-helper enable '{unit_name}' >/dev/null || true\n\
+                         \telse\n\
+                         \t\tdeb-systemd-helper update-state '{unit_name}' >/dev/null || true\n\
+                         \tfi"
+                    )?;
+                } else {
+                    writeln!(
+                        snippet,
+                        "\tdeb-systemd-helper update-state '{unit_name}' >/dev/null || true"
+                    )?;
+                }
+            }
+
+            // `$2` is the previously configured version on upgrade.
+            let invoke = |action: &str, units: &str| {
+                if units.is_empty() {
+                    ":".to_owned()
+                } else {
+                    format!("deb-systemd-invoke {action} {units} >/dev/null || true")
+                }
+            };
+            writeln!(
+                snippet,
+                "\tif [ -d /run/systemd/system ]; then\n\
+                 \t\tsystemctl --system daemon-reload >/dev/null || true\n\
+                 \t\tif [ -n \"$2\" ]; then\n\
+                 \t\t\t{}\n\
+                 \t\telse\n\
+                 \t\t\t{}\n\
+                 \t\tfi\n\
+                 \tfi\n\
+                 fi",
+                invoke("try-restart", &restart_units),
+                invoke("start", &enable_units),
+            )?;
+        }
+        "prerm" => {
+            writeln!(
+                snippet,
+                "if [ -d /run/systemd/system ] && [ \"$1\" = remove ]; then\n\
+                 \tdeb-systemd-invoke stop {all_units} >/dev/null || true\n\
+                 fi"
+            )?;
+        }
+        "postrm" => {
+            writeln!(
+                snippet,
+                "if [ -d /run/systemd/system ] && [ \"$1\" = remove ]; then\n\
+                 \tsystemctl --system daemon-reload >/dev/null || true\n\
+                 fi\n\
+                 if [ \"$1\" = \"remove\" ] && [ -x \"/usr/bin/deb-systemd-helper\" ]; then\n\
+                 \tdeb-systemd-helper mask {all_units} >/dev/null || true\n\
+                 fi\n\
+                 if [ \"$1\" = \"purge\" ] && [ -x \"/usr/bin/deb-systemd-helper\" ]; then\n\
+                 \tdeb-systemd-helper purge {all_units} >/dev/null || true\n\
+                 \tdeb-systemd-helper unmask {all_units} >/dev/null || true\n\
+                 fi"
+            )?;
+        }
+        _ => return Ok(None),
+    }
+    Ok(Some(snippet))
+}
+
+/// Snippet of `systemd-rpm-macros` in rpm scriptlets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RpmSnippet {
+    /// Apply preset policy of units on install.
+    Post,
+
+    /// Disable and stop units on removal.
+    Preun,
+
+    /// Reload systemd on upgrade and removal.
+    Postun,
+
+    /// Reload systemd and restart units on upgrade.
+    PostunWithRestart,
+}
+
+impl RpmSnippet {
+    /// Name of macro, like `systemd_post`.
+    const fn macro_name(self) -> &'static str {
+        match self {
+            Self::Post => "systemd_post",
+            Self::Preun => "systemd_preun",
+            Self::Postun => "systemd_postun",
+            Self::PostunWithRestart => "systemd_postun_with_restart",
+        }
+    }
+
+    /// Get macro call, or shell commands which macro expands to.
+    fn generate(self, units: &str, use_macros: bool) -> String {
+        if use_macros {
+            return format!("%{} {units}\n", self.macro_name());
+        }
+        let reload = "systemctl daemon-reload >/dev/null 2>&1 || :\n";
+        match self {
+            Self::Post => format!(
+                "if [ $1 -eq 1 ]; then\n\
+                 \tsystemctl --no-reload preset {units} >/dev/null 2>&1 || :\n\
+                 fi\n"
+            ),
+            Self::Preun => format!(
+                "if [ $1 -eq 0 ]; then\n\
+                 \tsystemctl --no-reload disable --now {units} >/dev/null 2>&1 || :\n\
+                 fi\n"
+            ),
+            Self::Postun => reload.to_owned(),
+            Self::PostunWithRestart => format!(
+                "{reload}\
+                 if [ $1 -ge 1 ]; then\n\
+                 \tsystemctl try-restart {units} >/dev/null 2>&1 || :\n\
+                 fi\n"
+            ),
+        }
+    }
+}
+
+/// Generate rpm scriptlets with `systemd-rpm-macros`, in order of
+/// `%post`, `%preun` and `%postun`.
+///
+/// Units with `enable` set are enabled and started explicitly on install,
+/// besides preset policy, while other units are left disabled.
+/// If `use_macros` is false, macros are expanded to shell commands,
+/// as no macro is expanded by native rpm writer.
+///
+/// # Errors
+/// Returns error if unit name is invalid.
+pub fn rpm_scriptlets(units: &[SystemdUnit], use_macros: bool) -> Result<[String; 3], Error> {
+    if units.is_empty() {
+        return Ok([String::new(), String::new(), String::new()]);
+    }
+    let names = |filter: fn(&SystemdUnit) -> bool| -> Result<String, Error> {
+        let names = units
+            .iter()
+            .filter(|unit| filter(unit))
+            .map(SystemdUnit::unit_name)
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(names.join(" "))
+    };
+    let all_units = names(|_unit| true)?;
+    let enable_units = names(|unit| unit.enable)?;
+    let restart_units = names(|unit| unit.restart_on_upgrade)?;
+
+    let mut post = String::new();
+    if !enable_units.is_empty() {
+        post += &RpmSnippet::Post.generate(&enable_units, use_macros);
+        writeln!(
+            post,
+            "if [ $1 -eq 1 ]; then\n\
+             \tsystemctl enable --now {enable_units} >/dev/null 2>&1 || :\n\
+             fi"
+        )?;
+    }
+    let preun = RpmSnippet::Preun.generate(&all_units, use_macros);
+    // Both snippets reload systemd, so only one of them is used.
+    let postun = if restart_units.is_empty() {
+        RpmSnippet::Postun.generate(&all_units, use_macros)
+    } else {
+        RpmSnippet::PostunWithRestart.generate(&restart_units, use_macros)
+    };
+    Ok([post, preun, postun])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new_unit(name: &str, enable: bool, restart_on_upgrade: bool) -> SystemdUnit {
+        SystemdUnit {
+            file: format!("pkg/{name}"),
+            name: None,
+            enable,
+            restart_on_upgrade,
+        }
+    }
+
+    const DEB_POSTINST_HEAD: &str = "\
+if [ \"$1\" = \"configure\" ] || [ \"$1\" = \"abort-upgrade\" ] || [ \"$1\" = \"abort-deconfigure\" ] || [ \"$1\" = \"abort-remove\" ] ; then
+	deb-systemd-helper unmask 'hello.service' >/dev/null || true
+";
+
+    #[test]
+    fn test_deb_postinst_enable() {
+        let units = [new_unit("hello.service", true, false)];
+        let snippet = deb_script_snippet(&units, "postinst").unwrap().unwrap();
+        let expected = DEB_POSTINST_HEAD.to_owned()
+            + "	\
+if deb-systemd-helper --quiet was-enabled 'hello.service'; then
+		deb-systemd-helper enable 'hello.service' >/dev/null || true
+	else
+		deb-systemd-helper update-state 'hello.service' >/dev/null || true
+	fi
+	if [ -d /run/systemd/system ]; then
+		systemctl --system daemon-reload >/dev/null || true
+		if [ -n \"$2\" ]; then
+			:
+		else
+			deb-systemd-invoke start 'hello.service' >/dev/null || true
+		fi
+	fi
+fi
+";
+        assert_eq!(snippet, expected);
+    }
+
+    #[test]
+    fn test_deb_postinst_restart() {
+        let units = [new_unit("hello.service", false, true)];
+        let snippet = deb_script_snippet(&units, "postinst").unwrap().unwrap();
+        let expected = DEB_POSTINST_HEAD.to_owned()
+            + "	\
+deb-systemd-helper update-state 'hello.service' >/dev/null || true
+	if [ -d /run/systemd/system ]; then
+		systemctl --system daemon-reload >/dev/null || true
+		if [ -n \"$2\" ]; then
+			deb-systemd-invoke try-restart 'hello.service' >/dev/null || true
+		else
+			:
+		fi
+	fi
+fi
+";
+        assert_eq!(snippet, expected);
+    }
+
+    #[test]
+    fn test_deb_postinst_neither() {
+        let units = [new_unit("hello.service", false, false)];
+        let snippet = deb_script_snippet(&units, "postinst").unwrap().unwrap();
+        let expected = DEB_POSTINST_HEAD.to_owned()
+            + "	\
+deb-systemd-helper update-state 'hello.service' >/dev/null || true
+	if [ -d /run/systemd/system ]; then
+		systemctl --system daemon-reload >/dev/null || true
+		if [ -n \"$2\" ]; then
+			:
+		else
+			:
+		fi
+	fi
+fi
+";
+        assert_eq!(snippet, expected);
+    }
+
+    #[test]
+    fn test_deb_prerm_postrm() {
+        // Units are stopped and masked on removal, whatever `enable` is.
+        for (enable, restart_on_upgrade) in [(true, false), (false, true), (false, false)] {
+            let units = [new_unit("hello.service", enable, restart_on_upgrade)];
+            let prerm = deb_script_snippet(&units, "prerm").unwrap().unwrap();
+            assert_eq!(
+                prerm,
+                "\
+if [ -d /run/systemd/system ] && [ \"$1\" = remove ]; then
+	deb-systemd-invoke stop 'hello.service' >/dev/null || true
+fi
+"
+            );
+            let postrm = deb_script_snippet(&units, "postrm").unwrap().unwrap();
+            assert_eq!(
+                postrm,
+                "\
+if [ -d /run/systemd/system ] && [ \"$1\" = remove ]; then
+	systemctl --system daemon-reload >/dev/null || true
+fi
+if [ \"$1\" = \"remove\" ] && [ -x \"/usr/bin/deb-systemd-helper\" ]; then
+	deb-systemd-helper mask 'hello.service' >/dev/null || true
+fi
+if [ \"$1\" = \"purge\" ] && [ -x \"/usr/bin/deb-systemd-helper\" ]; then
+	deb-systemd-helper purge 'hello.service' >/dev/null || true
+	deb-systemd-helper unmask 'hello.service' >/dev/null || true
+fi
+"
+            );
+        }
+        assert_eq!(deb_script_snippet(&[], "postinst").unwrap(), None);
+        assert_eq!(
+            deb_script_snippet(&[new_unit("hello.service", true, true)], "preinst").unwrap(),
+            None
+        );
+    }
+
+    const RPM_POST: &str = "\
+if [ $1 -eq 1 ]; then
+	systemctl --no-reload preset hello.service >/dev/null 2>&1 || :
+fi
+if [ $1 -eq 1 ]; then
+	systemctl enable --now hello.service >/dev/null 2>&1 || :
+fi
+";
+
+    const RPM_PREUN: &str = "\
+if [ $1 -eq 0 ]; then
+	systemctl --no-reload disable --now hello.service >/dev/null 2>&1 || :
+fi
+";
+
+    #[test]
+    fn test_rpm_scriptlets_enable() {
+        let units = [new_unit("hello.service", true, false)];
+        let [post, preun, postun] = rpm_scriptlets(&units, false).unwrap();
+        assert_eq!(post, RPM_POST);
+        assert_eq!(preun, RPM_PREUN);
+        assert_eq!(postun, "systemctl daemon-reload >/dev/null 2>&1 || :\n");
+
+        let [post, preun, postun] = rpm_scriptlets(&units, true).unwrap();
+        assert_eq!(
+            post,
+            "\
+%systemd_post hello.service
+if [ $1 -eq 1 ]; then
+	systemctl enable --now hello.service >/dev/null 2>&1 || :
+fi
+"
+        );
+        assert_eq!(preun, "%systemd_preun hello.service\n");
+        assert_eq!(postun, "%systemd_postun hello.service\n");
+    }
+
+    #[test]
+    fn test_rpm_scriptlets_restart() {
+        // Units without `enable` are neither preset nor enabled.
+        let units = [new_unit("hello.service", false, true)];
+        let [post, preun, postun] = rpm_scriptlets(&units, false).unwrap();
+        assert_eq!(post, "");
+        assert_eq!(preun, RPM_PREUN);
+        assert_eq!(
+            postun,
+            "\
+systemctl daemon-reload >/dev/null 2>&1 || :
+if [ $1 -ge 1 ]; then
+	systemctl try-restart hello.service >/dev/null 2>&1 || :
+fi
+"
+        );
+
+        let [post, preun, postun] = rpm_scriptlets(&units, true).unwrap();
+        assert_eq!(post, "");
+        assert_eq!(preun, "%systemd_preun hello.service\n");
+        assert_eq!(postun, "%systemd_postun_with_restart hello.service\n");
+    }
+
+    #[test]
+    fn test_rpm_scriptlets_neither() {
+        let units = [new_unit("hello.service", false, false)];
+        let [post, preun, postun] = rpm_scriptlets(&units, false).unwrap();
+        assert_eq!(post, "");
+        assert_eq!(preun, RPM_PREUN);
+        assert_eq!(postun, "systemctl daemon-reload >/dev/null 2>&1 || :\n");
+
+        let [post, preun, postun] = rpm_scriptlets(&units, true).unwrap();
+        assert_eq!(post, "");
+        assert_eq!(preun, "%systemd_preun hello.service\n");
+        assert_eq!(postun, "%systemd_postun hello.service\n");
+
+        assert_eq!(
+            rpm_scriptlets(&[], false).unwrap(),
+            [String::new(), String::new(), String::new()]
+        );
+    }
+
+    #[test]
+    fn test_rpm_scriptlets_mixed() {
+        let units = [
+            new_unit("hello.service", true, true),
+            new_unit("hello.socket", false, false),
+        ];
+        let [post, preun, postun] = rpm_scriptlets(&units, false).unwrap();
+        assert_eq!(post, RPM_POST);
+        assert_eq!(
+            preun,
+            "\
+if [ $1 -eq 0 ]; then
+	systemctl --no-reload disable --now hello.service hello.socket >/dev/null 2>&1 || :
+fi
+"
+        );
+        // Systemd is reloaded only once.
+        assert_eq!(
+            postun,
+            "\
+systemctl daemon-reload >/dev/null 2>&1 || :
+if [ $1 -ge 1 ]; then
+	systemctl try-restart hello.service >/dev/null 2>&1 || :
+fi
+"
+        );
+
+        let [_post, _preun, postun] = rpm_scriptlets(&units, true).unwrap();
+        assert_eq!(postun, "%systemd_postun_with_restart hello.service\n");
+    }
+}
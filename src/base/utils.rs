@@ -2,8 +2,10 @@
 // Use of this source is governed by General Public License that can be found
 // in the LICENSE file.
 
-use std::fs;
-use std::path::Path;
+use std::env;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, UNIX_EPOCH};
 use walkdir::WalkDir;
 
 use crate::Error;
@@ -23,6 +25,40 @@ pub fn get_folder_size(dir: &Path) -> Result<u64, Error> {
     Ok(total_size)
 }
 
+/// Get timestamp in `SOURCE_DATE_EPOCH` environment variable, used for reproducible builds.
+///
+/// See [SOURCE_DATE_EPOCH](https://reproducible-builds.org/docs/source-date-epoch/).
+#[must_use]
+pub fn source_date_epoch() -> Option<u64> {
+    env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|value| value.trim().parse().ok())
+}
+
+/// Get mtime of generated entries in packages, like directories and symbolic links.
+///
+/// Entries have the same mtime in each build, `SOURCE_DATE_EPOCH` or `0`.
+#[must_use]
+pub fn entry_mtime() -> u64 {
+    source_date_epoch().unwrap_or(0)
+}
+
+/// Set mtime of file generated in build, like control files, to [`entry_mtime`].
+///
+/// # Errors
+/// Returns error if failed to open `path` or failed to update its mtime.
+pub fn set_entry_mtime(path: &Path) -> Result<(), Error> {
+    let mtime = UNIX_EPOCH + Duration::from_secs(entry_mtime());
+    File::open(path)?.set_modified(mtime)?;
+    Ok(())
+}
+
+/// Clamp mtime of files to `SOURCE_DATE_EPOCH` if it is set.
+#[must_use]
+pub fn clamp_mtime(mtime: u64) -> u64 {
+    source_date_epoch().map_or(mtime, |epoch| mtime.min(epoch))
+}
+
 #[must_use]
 pub const fn default_true() -> bool {
     true
@@ -40,8 +76,37 @@ pub fn rmdir<P: AsRef<Path>>(p: P) -> Result<(), Error> {
     Ok(())
 }
 
+/// Move files matching glob `src_pattern` into `dest` folder.
+///
+/// Returns new paths of moved files, in order of glob results.
+///
 /// # Errors
-/// todo
-pub fn mv<P: AsRef<Path>>(_src_pathtern: &str, _dest: P) -> Result<(), Error> {
-    Ok(())
+/// Returns error if pattern is invalid or failed to move some file.
+pub fn mv<P: AsRef<Path>>(src_pattern: &str, dest: P) -> Result<Vec<PathBuf>, Error> {
+    let dest = dest.as_ref();
+    fs::create_dir_all(dest)?;
+    let mut moved_files = Vec::new();
+    for src in glob::glob(src_pattern)? {
+        let src = src?;
+        let Some(file_name) = src.file_name() else {
+            continue;
+        };
+        let dest_file = dest.join(file_name);
+        if fs::rename(&src, &dest_file).is_err() {
+            // Files can not be renamed across file systems.
+            fs::copy(&src, &dest_file)?;
+            fs::remove_file(&src)?;
+        }
+        moved_files.push(dest_file);
+    }
+    Ok(moved_files)
+}
+
+/// Create an empty folder in temp folder of system, used by tests.
+#[cfg(test)]
+pub fn test_dir(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("pifu-test-{name}-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
 }
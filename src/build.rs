@@ -8,7 +8,7 @@ use crate::app_image::build_app_image;
 use crate::base::config::get_target_arch;
 use crate::base::{Arch, PlatformTarget};
 use crate::config::Config;
-use crate::deb::build_deb;
+use crate::deb::{build_deb, build_deb_source};
 use crate::nsis::build_nsis;
 use crate::rpm::build_rpm;
 use crate::Error;
@@ -73,8 +73,9 @@ fn build_linux(conf: &Config, options: &BuildOptions) -> Result<(), Error> {
         .copied()
         .collect::<Vec<PlatformTarget>>();
 
+    let package_arches = linux_conf.package_arches(&arches);
     if targets.contains(&PlatformTarget::Deb) {
-        for arch in &arches {
+        for arch in &package_arches {
             print!("Build deb package for {arch}...");
             match build_deb(conf, linux_conf, *arch) {
                 Ok(()) => println!(" {}", "Ok".green()),
@@ -89,8 +90,28 @@ fn build_linux(conf: &Config, options: &BuildOptions) -> Result<(), Error> {
             }
         }
     }
+    // Source package is opt-in, it is built with `--target deb_source`,
+    // or along with deb packages if `deb_source` is in `targets` of config.
+    // It is built once, if any arch is selected.
+    let build_source = options.targets.contains(&PlatformTarget::DebSource)
+        || (linux_conf.targets.contains(&PlatformTarget::DebSource)
+            && targets.contains(&PlatformTarget::Deb));
+    if build_source && !arches.is_empty() {
+        print!("Build deb source package...");
+        match build_deb_source(conf, linux_conf) {
+            Ok(()) => println!(" {}", "Ok".green()),
+            Err(err) => {
+                println!(" {}", "Failed".red());
+                if options.ignore_error {
+                    println!("{} {:?}", "Error:".red(), err);
+                } else {
+                    return Err(err);
+                }
+            }
+        }
+    }
     if targets.contains(&PlatformTarget::Rpm) {
-        for arch in &arches {
+        for arch in &package_arches {
             print!("Build rpm package for {arch}...");
             match build_rpm(conf, linux_conf, *arch) {
                 Ok(()) => println!(" {}", "Ok".green()),
@@ -2,16 +2,19 @@
 // Use of this source is governed by General Public License that can be found
 // in the LICENSE file.
 
-use clap::{value_parser, Arg, ArgAction, Command};
+use clap::{value_parser, Arg, ArgAction, ArgMatches, Command};
 use std::fs;
-use std::path::{Path, PathBuf};
+use std::path::PathBuf;
 use std::str::FromStr;
 
 use crate::base::{expand_file_macro_simple, Arch, PlatformTarget};
 use crate::build;
 use crate::config::Config;
+use crate::deb;
 use crate::download;
 use crate::error::{Error, ErrorKind};
+use crate::inspect;
+use crate::rpm;
 
 const OPT_CONFIG: &str = "config";
 const OPT_OS: &str = "os";
@@ -19,6 +22,14 @@ const OPT_TARGET: &str = "target";
 const OPT_ARCH: &str = "arch";
 const OPT_DOWNLOAD: &str = "download";
 const OPT_IGNORE_ERROR: &str = "ignore-error";
+const OPT_DIR: &str = "dir";
+const OPT_OUTPUT: &str = "output";
+const OPT_FILE: &str = "file";
+const OPT_JSON: &str = "json";
+
+const CMD_APT_REPO: &str = "apt-repo";
+const CMD_YUM_REPO: &str = "yum-repo";
+const CMD_INSPECT: &str = "inspect";
 
 /// # Errors
 /// Returns error if failed to parse cmdline or failed to read config file.
@@ -33,6 +44,7 @@ pub fn read_cmdline() -> Result<(), Error> {
                 .short('c')
                 .long(OPT_CONFIG)
                 .action(ArgAction::Set)
+                .global(true)
                 .value_name("toml file")
                 .help("Specify a custom toml config file")
                 .value_parser(value_parser!(PathBuf)),
@@ -60,50 +72,74 @@ pub fn read_cmdline() -> Result<(), Error> {
         .arg(
             Arg::new(OPT_DOWNLOAD)
                 .long(OPT_DOWNLOAD)
+                .action(ArgAction::SetTrue)
                 .help("Download required tools from github"),
         )
         .arg(
             Arg::new(OPT_IGNORE_ERROR)
                 .long(OPT_IGNORE_ERROR)
+                .action(ArgAction::SetTrue)
                 .help("Ignore build errors and continue"),
         )
+        .subcommand(repo_command(
+            CMD_APT_REPO,
+            "Generate flat apt repository from deb files",
+            "deb",
+            "apt",
+        ))
+        .subcommand(repo_command(
+            CMD_YUM_REPO,
+            "Generate yum repository from rpm files",
+            "rpm",
+            "yum",
+        ))
+        .subcommand(
+            Command::new(CMD_INSPECT)
+                .about("Print metadata, files and scripts of deb, rpm or AppImage file")
+                .arg(
+                    Arg::new(OPT_FILE)
+                        .action(ArgAction::Set)
+                        .required(true)
+                        .value_name("package file")
+                        .value_parser(value_parser!(PathBuf)),
+                )
+                .arg(
+                    Arg::new(OPT_JSON)
+                        .long(OPT_JSON)
+                        .action(ArgAction::SetTrue)
+                        .help("Print in json format"),
+                ),
+        )
         .get_matches();
 
-    if matches.contains_id(OPT_DOWNLOAD) {
+    if matches.get_flag(OPT_DOWNLOAD) {
         return download::download();
     }
 
-    // read config
-    let mut config_file: String = (*matches
-        .get_one::<&str>(OPT_CONFIG)
-        .unwrap_or(&"pkg/pifu.toml"))
-    .to_string();
-    if !Path::new(&config_file).exists() {
-        config_file = "pifu.toml".to_owned();
+    if let Some(sub_matches) = matches.subcommand_matches(CMD_APT_REPO) {
+        let (deb_dir, repo_dir) = repo_dirs(&matches, sub_matches, "apt")?;
+        return deb::generate_apt_repo(&deb_dir, &repo_dir);
     }
-    log::info!("config file: {:?}", config_file);
 
-    let config_content = fs::read_to_string(&config_file).map_err(|err| {
-        Error::from_string(
-            ErrorKind::IoError,
-            format!("Failed to read config at {config_file}, err: {err}"),
-        )
-    })?;
-    let mut conf: Config = toml::from_str(&config_content).map_err(|_err| {
-        Error::from_string(
-            ErrorKind::TomlError,
-            format!("Invalid toml config, {config_file}"),
-        )
-    })?;
+    if let Some(sub_matches) = matches.subcommand_matches(CMD_YUM_REPO) {
+        let (rpm_dir, repo_dir) = repo_dirs(&matches, sub_matches, "yum")?;
+        return rpm::generate_yum_repo(&rpm_dir, &repo_dir);
+    }
 
-    conf.metadata.build_id = expand_file_macro_simple(&conf.metadata.build_id)?;
+    if let Some(sub_matches) = matches.subcommand_matches(CMD_INSPECT) {
+        if let Some(file) = sub_matches.get_one::<PathBuf>(OPT_FILE) {
+            return inspect::inspect(file, sub_matches.get_flag(OPT_JSON));
+        }
+    }
+
+    let conf = read_config(&matches)?;
 
     let mut options = build::BuildOptions {
-        ignore_error: matches.contains_id(OPT_IGNORE_ERROR),
+        ignore_error: matches.get_flag(OPT_IGNORE_ERROR),
         ..Default::default()
     };
 
-    if let Some(os_list) = matches.get_many::<&str>(OPT_OS) {
+    if let Some(os_list) = matches.get_many::<String>(OPT_OS) {
         options.targets.clear();
         for os in os_list {
             if *os == "linux" {
@@ -124,7 +160,7 @@ pub fn read_cmdline() -> Result<(), Error> {
         }
     }
 
-    if let Some(target_list) = matches.get_many::<&str>(OPT_TARGET) {
+    if let Some(target_list) = matches.get_many::<String>(OPT_TARGET) {
         options.targets.clear();
         for target in target_list {
             if let Ok(target) = PlatformTarget::from_str(target) {
@@ -132,13 +168,13 @@ pub fn read_cmdline() -> Result<(), Error> {
             } else {
                 return Err(Error::from_string(
                     ErrorKind::CmdlineError,
-                    format!("Invalid --target {target}, available values are `deb`, `rpm`, `app_image` or `nsis`"),
+                    format!("Invalid --target {target}, available values are `deb`, `deb_source`, `rpm`, `app_image` or `nsis`"),
                 ));
             }
         }
     }
 
-    if let Some(arch_list) = matches.get_many::<&str>(OPT_ARCH) {
+    if let Some(arch_list) = matches.get_many::<String>(OPT_ARCH) {
         options.arches.clear();
         for arch in arch_list {
             if let Ok(arch) = Arch::from_str(arch) {
@@ -147,7 +183,7 @@ pub fn read_cmdline() -> Result<(), Error> {
                 return Err(Error::from_string(
                     ErrorKind::CmdlineError,
                     format!(
-                        "Invalid --arch {arch}, available values are `x86_64`, `x86` or `aarch64`"
+                        "Invalid --arch {arch}, available values are `x86_64`, `x86`, `aarch64`, `mips64`, `armv7`, `riscv64`, `ppc64le`, `s390x` or `loongarch64`"
                     ),
                 ));
             }
@@ -157,3 +193,82 @@ pub fn read_cmdline() -> Result<(), Error> {
     log::debug!("options: {:#?}", options);
     build::build(&conf, &options)
 }
+
+/// Read config file specified in cmdline, or `pkg/pifu.toml`, or `pifu.toml`.
+fn read_config(matches: &ArgMatches) -> Result<Config, Error> {
+    let mut config_file = matches
+        .get_one::<PathBuf>(OPT_CONFIG)
+        .cloned()
+        .unwrap_or_else(|| PathBuf::from("pkg/pifu.toml"));
+    if !config_file.exists() {
+        config_file = PathBuf::from("pifu.toml");
+    }
+    log::info!("config file: {}", config_file.display());
+
+    let config_content = fs::read_to_string(&config_file).map_err(|err| {
+        Error::from_string(
+            ErrorKind::IoError,
+            format!(
+                "Failed to read config at {}, err: {err}",
+                config_file.display()
+            ),
+        )
+    })?;
+    let mut conf: Config = toml::from_str(&config_content).map_err(|err| {
+        Error::from_string(
+            ErrorKind::TomlError,
+            format!("Invalid toml config, {}, err: {err}", config_file.display()),
+        )
+    })?;
+
+    conf.metadata.build_id = expand_file_macro_simple(&conf.metadata.build_id)?;
+    if let Some(linux) = conf.linux.as_mut() {
+        linux.expand_macros()?;
+        linux.validate()?;
+    }
+    Ok(conf)
+}
+
+/// Subcommand to generate package repository of `kind` files.
+fn repo_command(name: &'static str, about: &'static str, kind: &str, repo_name: &str) -> Command {
+    Command::new(name)
+        .about(about)
+        .arg(
+            Arg::new(OPT_DIR)
+                .action(ArgAction::Set)
+                .value_name("package dir")
+                .help(format!(
+                    "Directory of {kind} files, default is `workdir` in config"
+                ))
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(
+            Arg::new(OPT_OUTPUT)
+                .short('o')
+                .long(OPT_OUTPUT)
+                .action(ArgAction::Set)
+                .value_name("repo dir")
+                .help(format!(
+                    "Output directory of {repo_name} repository, default is `{repo_name}` in {kind} dir"
+                ))
+                .value_parser(value_parser!(PathBuf)),
+        )
+}
+
+/// Get package dir and output dir of repository subcommand.
+fn repo_dirs(
+    matches: &ArgMatches,
+    sub_matches: &ArgMatches,
+    repo_name: &str,
+) -> Result<(PathBuf, PathBuf), Error> {
+    let package_dir = if let Some(dir) = sub_matches.get_one::<PathBuf>(OPT_DIR) {
+        dir.clone()
+    } else {
+        PathBuf::from(read_config(matches)?.metadata.workdir)
+    };
+    let repo_dir = sub_matches
+        .get_one::<PathBuf>(OPT_OUTPUT)
+        .cloned()
+        .unwrap_or_else(|| package_dir.join(repo_name));
+    Ok((package_dir, repo_dir))
+}
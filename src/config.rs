@@ -4,15 +4,18 @@
 
 use directories::ProjectDirs;
 use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
 use std::path::PathBuf;
 
 use crate::app_image::AppImageConfig;
-use crate::base::fileset::FileSet;
-use crate::base::{Arch, Metadata, PlatformTarget};
-use crate::deb::DebConfig;
+use crate::base::fileset::{Directory, FileSet, Symlink};
+use crate::base::systemd::SystemdUnit;
+use crate::base::utils::{default_false, default_true};
+use crate::base::{Arch, Metadata, PackageArch, PlatformTarget};
+use crate::deb::{DebConfig, DebRelations};
 use crate::error::{Error, ErrorKind};
 use crate::nsis::NsisConfig;
-use crate::rpm::RpmConfig;
+use crate::rpm::{RpmConfig, RpmRelations};
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct Config {
@@ -31,8 +34,45 @@ pub struct LinuxConfig {
     #[serde(default = "default_linux_targets")]
     pub targets: Vec<PlatformTarget>,
 
+    /// Boolean - whether deb and rpm packages are architecture independent,
+    /// like data files and scripts. These packages are built only once,
+    /// as `Architecture: all` deb and `noarch` rpm.
+    ///
+    /// `AppImage` files are still built for each `arch`.
+    #[serde(default = "default_false")]
+    pub arch_independent: bool,
+
     pub files: Option<Vec<FileSet>>,
 
+    /// Symbolic links in deb and rpm packages, like `usr/lib/libhello.so`.
+    #[serde(default = "Vec::new")]
+    pub symlinks: Vec<Symlink>,
+
+    /// Directories in deb and rpm packages, created even if they are empty.
+    #[serde(default = "Vec::new")]
+    pub directories: Vec<Directory>,
+
+    /// Boolean - whether to strip symbols of ELF files in package.
+    #[serde(default = "default_false")]
+    pub strip: bool,
+
+    /// Boolean - whether to strip ELF files and move their debug info
+    /// to `/usr/lib/debug/.build-id/`, in a companion `<name>-dbgsym` deb
+    /// or `<name>-debuginfo` rpm package. Implies `strip`.
+    ///
+    /// `AppImage` files are stripped only.
+    #[serde(default = "default_false")]
+    pub split_debug: bool,
+
+    /// Systemd units installed to `/usr/lib/systemd/system`,
+    /// enabled and restarted by deb maintainer scripts and rpm scriptlets.
+    #[serde(default = "Vec::new")]
+    pub systemd_units: Vec<SystemdUnit>,
+
+    /// Sub-packages built along with the main package, like `<name>-dev`.
+    #[serde(default = "Vec::new")]
+    pub packages: Vec<SubPackage>,
+
     /// Specific config for `AppImage` format.
     #[serde(default = "AppImageConfig::default")]
     pub app_image: AppImageConfig,
@@ -46,6 +86,174 @@ pub struct LinuxConfig {
     pub rpm: RpmConfig,
 }
 
+impl LinuxConfig {
+    /// Get architectures to build deb and rpm packages for, in `arches`.
+    ///
+    /// Arch-independent packages are built once if any arch is selected.
+    #[must_use]
+    pub fn package_arches(&self, arches: &[Arch]) -> Vec<PackageArch> {
+        if self.arch_independent && !arches.is_empty() {
+            vec![PackageArch::All]
+        } else {
+            arches.iter().copied().map(PackageArch::from).collect()
+        }
+    }
+
+    /// Whether to strip ELF files, explicitly or by `split_debug`.
+    #[must_use]
+    pub const fn need_strip(&self) -> bool {
+        self.strip || self.split_debug
+    }
+
+    /// Expand macros in version fields of deb and rpm config.
+    ///
+    /// # Errors
+    /// Returns error if failed to expand macros.
+    pub fn expand_macros(&mut self) -> Result<(), Error> {
+        self.deb.expand_macros()?;
+        self.rpm.expand_macros()
+    }
+
+    /// Validate deb and rpm config, symbolic links, directories and sub-packages.
+    ///
+    /// # Errors
+    /// Returns error if relations, custom fields, compression, paths or sub-packages are invalid.
+    pub fn validate(&self) -> Result<(), Error> {
+        self.deb.validate()?;
+        self.rpm.validate()?;
+        validate_entries(&self.symlinks, &self.directories)?;
+
+        let mut suffixes = HashSet::new();
+        for package in &self.packages {
+            let suffix = &package.suffix;
+            let valid_suffix = !suffix.is_empty()
+                && suffix
+                    .chars()
+                    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || "+.-".contains(c));
+            if !valid_suffix || RESERVED_SUFFIXES.contains(&suffix.as_str()) {
+                return Err(Error::from_string(
+                    ErrorKind::InvalidConfError,
+                    format!("Invalid sub-package suffix `{suffix}`"),
+                ));
+            }
+            if !suffixes.insert(suffix.as_str()) {
+                return Err(Error::from_string(
+                    ErrorKind::InvalidConfError,
+                    format!("Duplicated sub-package suffix `{suffix}`"),
+                ));
+            }
+            package.deb.validate()?;
+            validate_entries(&package.symlinks, &package.directories)?;
+        }
+
+        for package in &self.packages {
+            for sibling in &package.depends_on {
+                if sibling == &package.suffix || !suffixes.contains(sibling.as_str()) {
+                    return Err(Error::from_string(
+                        ErrorKind::InvalidConfError,
+                        format!(
+                            "Invalid sibling `{sibling}` in `depends_on` of sub-package `{}`",
+                            package.suffix
+                        ),
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn validate_entries(symlinks: &[Symlink], directories: &[Directory]) -> Result<(), Error> {
+    for symlink in symlinks {
+        symlink.validate()?;
+    }
+    for directory in directories {
+        directory.validate()?;
+    }
+    Ok(())
+}
+
+/// Suffixes used by debug packages generated with `split_debug`.
+const RESERVED_SUFFIXES: &[&str] = &["dbgsym", "debuginfo"];
+
+/// Binary package split from the main package, named `<name>-<suffix>`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SubPackage {
+    /// Suffix of package name, like `dev` for `<name>-dev`.
+    pub suffix: String,
+
+    /// Summary of package, `description` of metadata is used if not set.
+    pub short_description: Option<String>,
+
+    /// Description of package, `description` of metadata is used if not set.
+    pub description: Option<String>,
+
+    pub files: Vec<FileSet>,
+
+    /// Symbolic links in this package.
+    #[serde(default = "Vec::new")]
+    pub symlinks: Vec<Symlink>,
+
+    /// Directories in this package, created even if they are empty.
+    #[serde(default = "Vec::new")]
+    pub directories: Vec<Directory>,
+
+    /// Boolean - whether to depend on the exact version of main package.
+    #[serde(default = "default_true")]
+    pub depends_on_main: bool,
+
+    /// Suffixes of sibling sub-packages to depend on, with exact version.
+    #[serde(default = "Vec::new")]
+    pub depends_on: Vec<String>,
+
+    /// Relations of deb package, like `depends` and `conflicts`.
+    #[serde(default = "DebRelations::default")]
+    pub deb: DebRelations,
+
+    /// Dependency tags of rpm package, like `requires` and `obsoletes`.
+    #[serde(default = "RpmRelations::default")]
+    pub rpm: RpmRelations,
+}
+
+impl SubPackage {
+    /// Get full name of package, like `hello-dev`.
+    #[must_use]
+    pub fn name(&self, metadata: &Metadata) -> String {
+        format!("{}-{}", metadata.name, self.suffix)
+    }
+
+    /// Get metadata of this package, with its own name and description.
+    #[must_use]
+    pub fn metadata(&self, metadata: &Metadata) -> Metadata {
+        let mut sub_metadata = metadata.clone();
+        sub_metadata.name = self.name(metadata);
+        if let Some(description) = &self.description {
+            sub_metadata.description.clone_from(description);
+            sub_metadata.short_description = None;
+        }
+        if self.short_description.is_some() {
+            sub_metadata
+                .short_description
+                .clone_from(&self.short_description);
+        }
+        sub_metadata
+    }
+
+    /// Get names of packages this package depends on with exact version,
+    /// including main package and siblings.
+    #[must_use]
+    pub fn exact_depends(&self, metadata: &Metadata) -> Vec<String> {
+        let main = self.depends_on_main.then(|| metadata.name.clone());
+        main.into_iter()
+            .chain(
+                self.depends_on
+                    .iter()
+                    .map(|sibling| format!("{}-{sibling}", metadata.name)),
+            )
+            .collect()
+    }
+}
+
 fn default_arch() -> Vec<Arch> {
     vec![Arch::X86_64]
 }
@@ -2,18 +2,23 @@
 // Use of this source is governed by General Public License that can be found
 // in the LICENSE file.
 
-use std::path::Path;
+use std::fs::{self, File};
+use std::io::{BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
 
-use crate::base::archive;
-use crate::base::compress;
-use crate::base::fileset;
+use crate::base::archive::{self, TarSummary};
+use crate::base::changelog::{self, Release};
+use crate::base::payload::Payload;
+use crate::base::relation::Relations;
+use crate::base::strip::{self, DebugInfo};
+use crate::base::systemd;
 use crate::base::utils;
-use crate::base::Arch;
-use crate::config::{Config, LinuxConfig};
-use crate::deb::control;
+use crate::base::PackageArch;
+use crate::config::{Config, LinuxConfig, SubPackage};
+use crate::deb::{control, shlibs, DebConfig};
 use crate::error::{Error, ErrorKind};
 
-pub fn build_deb(conf: &Config, linux_conf: &LinuxConfig, arch: Arch) -> Result<(), Error> {
+pub fn build_deb(conf: &Config, linux_conf: &LinuxConfig, arch: PackageArch) -> Result<(), Error> {
     let deb_conf = &linux_conf.deb;
 
     let files = if let Some(files) = deb_conf.files.as_ref() {
@@ -29,44 +34,308 @@ pub fn build_deb(conf: &Config, linux_conf: &LinuxConfig, arch: Arch) -> Result<
 
     let workdir = Path::new(&conf.metadata.workdir);
     let deb_dir = workdir.join("deb");
-    let data_dir = deb_dir.join("data");
     let control_dir = deb_dir.join("control");
+    let dbgsym_dir = deb_dir.join("dbgsym");
 
     let _ = utils::rmdir(&deb_dir);
 
-    fileset::copy_filesets(files, &conf.metadata.src_dir, &data_dir)?;
+    // Files are read from their sources while writing data.tar,
+    // only ELF files to be stripped are copied.
+    let mut payload = Payload::from_filesets(files, &conf.metadata.src_dir)?;
+    payload.add_symlinks(&linux_conf.symlinks);
+    payload.add_directories(&linux_conf.directories);
+    systemd::add_units(
+        &linux_conf.systemd_units,
+        &conf.metadata.src_dir,
+        &mut payload,
+    )?;
+    let releases = changelog::read_releases(&conf.metadata)?;
+    changelog::add_debian_changelog(
+        &mut payload,
+        &conf.metadata,
+        &releases,
+        |version| control::full_version(version, deb_conf.epoch, deb_conf.revision.as_deref()),
+        &deb_dir.join("changelog"),
+    )?;
+    changelog::update_payload_metainfo(&mut payload, &releases, &deb_dir.join("metainfo"))?;
 
-    let data_tar_file = deb_dir.join("data.tar");
-    archive::create_tar_chown(&data_dir, &data_tar_file)?;
+    let dbgsym_data_dir = dbgsym_dir.join("data");
+    let mut debug_infos = if linux_conf.need_strip() {
+        let debug_dir = linux_conf.split_debug.then_some(dbgsym_data_dir.as_path());
+        strip::strip_payload(&mut payload, &deb_dir.join("strip"), arch, debug_dir)?
+    } else {
+        Vec::new()
+    };
+    // Packages whose debug info is saved in dbgsym package.
+    let mut dbgsym_depends = Vec::new();
+    if !debug_infos.is_empty() {
+        dbgsym_depends.push(conf.metadata.name.clone());
+    }
 
-    let data_xz_file = deb_dir.join("data.tar.xz");
-    compress::create_xz2(&data_tar_file, &data_xz_file)?;
+    let mut shlib_depends = if deb_conf.auto_depends {
+        shlibs::get_shlib_depends(&payload, arch)?
+    } else {
+        Relations::default()
+    };
+    if !linux_conf.systemd_units.is_empty() {
+        // Provides `deb-systemd-helper` used in maintainer scripts.
+        shlib_depends.push("init-system-helpers (>= 1.52)".parse()?);
+    }
+    let depends = shlibs::merge_depends(deb_conf.relations.depends.as_ref(), &shlib_depends);
 
-    let md5sum_file = control_dir.join("md5sum");
-    control::generate_md5sum(&data_dir, &md5sum_file)?;
+    let (data_file, summary) = write_data_tar(deb_conf, &payload, &deb_dir)?;
+    control::generate_control(
+        &conf.metadata,
+        deb_conf,
+        arch,
+        summary.installed_size,
+        &depends,
+        &control_dir.join("control"),
+    )?;
+    control::generate_md5sums(&summary.md5sums, &control_dir.join("md5sums"))?;
+    control::generate_scripts(conf, arch, &control_dir)?;
+    control::generate_conffiles(deb_conf, &payload, &control_dir.join("conffiles"))?;
 
-    let file_size = utils::get_folder_size(&data_dir)?;
-    let control_file = control_dir.join("control");
-    control::generate_control(conf, arch, file_size, &control_file)?;
+    let deb_file = workdir.join(deb_filename(
+        &conf.metadata.name,
+        &conf.metadata.version,
+        deb_conf,
+        arch,
+    ));
+    create_deb(deb_conf, &control_dir, &data_file, &deb_file)?;
 
-    let control_tar_file = deb_dir.join("control.tar");
-    archive::create_tar_without_rootdir(&control_dir, &control_tar_file)?;
+    for package in &linux_conf.packages {
+        let pkg_dir = deb_dir.join("packages").join(&package.suffix);
+        let package_debug_infos = build_sub_package(
+            conf,
+            linux_conf,
+            package,
+            arch,
+            &releases,
+            &pkg_dir,
+            &dbgsym_data_dir,
+        )?;
+        if !package_debug_infos.is_empty() {
+            dbgsym_depends.push(package.name(&conf.metadata));
+        }
+        debug_infos.extend(package_debug_infos);
+    }
 
-    let control_xz_file = deb_dir.join("control.tar.xz");
-    compress::create_xz2(&control_tar_file, &control_xz_file)?;
+    if dbgsym_data_dir.is_dir() {
+        build_dbgsym_package(
+            conf,
+            deb_conf,
+            arch,
+            &dbgsym_dir,
+            &dbgsym_depends,
+            debug_infos,
+        )?;
+    }
 
-    let deb_binary_file = deb_dir.join("debian-binary");
-    control::generate_deb_binary(&deb_binary_file)?;
+    Ok(())
+}
 
-    let deb_filename = format!(
-        "{}_{}_{}.deb",
-        conf.metadata.name,
-        conf.metadata.version,
-        control::arch_name(arch)
+/// Build `<name>-dbgsym` deb file from debug files in `dbgsym_dir/data`,
+/// which depends on `depends` packages.
+fn build_dbgsym_package(
+    conf: &Config,
+    deb_conf: &DebConfig,
+    arch: PackageArch,
+    dbgsym_dir: &Path,
+    depends: &[String],
+    debug_infos: Vec<DebugInfo>,
+) -> Result<(), Error> {
+    let workdir = Path::new(&conf.metadata.workdir);
+    let payload = Payload::from_dir(&dbgsym_dir.join("data"))?;
+    for (_path, source) in payload.files() {
+        utils::set_entry_mtime(source)?;
+    }
+    let (data_file, summary) = write_data_tar(deb_conf, &payload, dbgsym_dir)?;
+    let control_dir = dbgsym_dir.join("control");
+    let build_ids = debug_infos
+        .into_iter()
+        .filter_map(|info| info.build_id)
+        .collect::<Vec<_>>();
+    control::generate_dbgsym_control(
+        conf,
+        deb_conf,
+        arch,
+        summary.installed_size,
+        depends,
+        &build_ids,
+        &control_dir.join("control"),
+    )?;
+    control::generate_md5sums(&summary.md5sums, &control_dir.join("md5sums"))?;
+    let name = format!("{}-dbgsym", conf.metadata.name);
+    let deb_file = workdir.join(deb_filename(&name, &conf.metadata.version, deb_conf, arch));
+    create_deb(deb_conf, &control_dir, &data_file, &deb_file)
+}
+
+/// Build deb file of sub-package, with temporary files in `pkg_dir`.
+///
+/// Debug info of ELF files is moved to `dbgsym_data_dir`, returns these files.
+/// `releases` are added to metainfo files only, changelog belongs to main package.
+fn build_sub_package(
+    conf: &Config,
+    linux_conf: &LinuxConfig,
+    package: &SubPackage,
+    arch: PackageArch,
+    releases: &[Release],
+    pkg_dir: &Path,
+    dbgsym_data_dir: &Path,
+) -> Result<Vec<DebugInfo>, Error> {
+    log::info!("build_sub_package() suffix: {}", package.suffix);
+    let deb_conf = &linux_conf.deb;
+    let workdir = Path::new(&conf.metadata.workdir);
+    let control_dir = pkg_dir.join("control");
+    let mut payload = Payload::from_filesets(&package.files, &conf.metadata.src_dir)?;
+    payload.add_symlinks(&package.symlinks);
+    payload.add_directories(&package.directories);
+    changelog::update_payload_metainfo(&mut payload, releases, &pkg_dir.join("metainfo"))?;
+    let debug_infos = if linux_conf.need_strip() {
+        let debug_dir = linux_conf.split_debug.then_some(dbgsym_data_dir);
+        strip::strip_payload(&mut payload, &pkg_dir.join("strip"), arch, debug_dir)?
+    } else {
+        Vec::new()
+    };
+
+    let mut extra_depends = if deb_conf.auto_depends {
+        shlibs::get_shlib_depends(&payload, arch)?
+    } else {
+        Relations::default()
+    };
+    let version = control::full_version(
+        &conf.metadata.version,
+        deb_conf.epoch,
+        deb_conf.revision.as_deref(),
     );
-    let deb_file = workdir.join(deb_filename);
-    let xz_files = vec![&deb_binary_file, &control_xz_file, &data_xz_file];
-    archive::create_ar_files(&xz_files, &deb_file)?;
+    for name in package.exact_depends(&conf.metadata) {
+        extra_depends.push(format!("{name} (= {version})").parse()?);
+    }
+    let depends = shlibs::merge_depends(package.deb.depends.as_ref(), &extra_depends);
+
+    // Maintainer scripts and explicit conffiles belong to main package.
+    let sub_deb_conf = DebConfig {
+        relations: package.deb.clone(),
+        conffiles: None,
+        preinst: None,
+        postinst: None,
+        prerm: None,
+        postrm: None,
+        config: None,
+        ..deb_conf.clone()
+    };
+    let metadata = package.metadata(&conf.metadata);
+    let (data_file, summary) = write_data_tar(&sub_deb_conf, &payload, pkg_dir)?;
+    control::generate_control(
+        &metadata,
+        &sub_deb_conf,
+        arch,
+        summary.installed_size,
+        &depends,
+        &control_dir.join("control"),
+    )?;
+    control::generate_md5sums(&summary.md5sums, &control_dir.join("md5sums"))?;
+    control::generate_conffiles(&sub_deb_conf, &payload, &control_dir.join("conffiles"))?;
+
+    let deb_file = workdir.join(deb_filename(
+        &metadata.name,
+        &metadata.version,
+        deb_conf,
+        arch,
+    ));
+    create_deb(&sub_deb_conf, &control_dir, &data_file, &deb_file)?;
+    Ok(debug_infos)
+}
+
+/// Get file name of deb package, like `hello_1.0.0-1_amd64.deb`, without epoch.
+fn deb_filename(name: &str, version: &str, deb_conf: &DebConfig, arch: PackageArch) -> String {
+    let version = control::full_version(version, None, deb_conf.revision.as_deref());
+    format!("{name}_{version}_{}.deb", control::package_arch_name(arch))
+}
+
+/// Write compressed `data.tar` of `payload` into `pkg_dir`.
+///
+/// Files are streamed from their sources through tar and compressor,
+/// md5 hash and installed size are computed in the same pass.
+pub fn write_data_tar(
+    deb_conf: &DebConfig,
+    payload: &Payload,
+    pkg_dir: &Path,
+) -> Result<(PathBuf, TarSummary), Error> {
+    fs::create_dir_all(pkg_dir)?;
+    let data_file = pkg_dir.join(format!("data.tar{}", deb_conf.compression.extension()));
+    log::info!("write_data_tar() {}", data_file.display());
+    let writer = BufWriter::new(File::create(&data_file)?);
+    let encoder = deb_conf
+        .compression
+        .encoder(writer, deb_conf.compression_level)?;
+    let (encoder, summary) = archive::create_payload_tar(payload, encoder)?;
+    encoder.finish()?.flush()?;
+    Ok((data_file, summary))
+}
+
+/// Assemble deb file from `control_dir` and compressed `data_file`.
+///
+/// Control files shall be ready, `control.tar` is created in memory.
+pub fn create_deb(
+    deb_conf: &DebConfig,
+    control_dir: &Path,
+    data_file: &Path,
+    deb_file: &Path,
+) -> Result<(), Error> {
+    log::info!("create_deb() deb file: {}", deb_file.display());
+    let control_payload = Payload::from_dir(control_dir)?;
+    for (_path, source) in control_payload.files() {
+        utils::set_entry_mtime(source)?;
+    }
+    let encoder = deb_conf
+        .compression
+        .encoder(Vec::new(), deb_conf.compression_level)?;
+    let (encoder, _summary) = archive::create_payload_tar(&control_payload, encoder)?;
+    let control_tar = encoder.finish()?;
+
+    let extension = deb_conf.compression.extension();
+    let mut builder = ar::Builder::new(BufWriter::new(File::create(deb_file)?));
+    append_ar_member(
+        &mut builder,
+        "debian-binary",
+        DEB_BINARY.len() as u64,
+        DEB_BINARY,
+    )?;
+    append_ar_member(
+        &mut builder,
+        &format!("control.tar{extension}"),
+        control_tar.len() as u64,
+        control_tar.as_slice(),
+    )?;
+    let data_fd = File::open(data_file)?;
+    let data_len = data_fd.metadata()?.len();
+    append_ar_member(
+        &mut builder,
+        &format!("data.tar{extension}"),
+        data_len,
+        data_fd,
+    )?;
+    builder.into_inner()?.flush()?;
+
+    Ok(())
+}
+
+/// Content of `debian-binary` member, version of deb format.
+const DEB_BINARY: &[u8] = b"2.0\n";
 
+fn append_ar_member<W: Write, R: Read>(
+    builder: &mut ar::Builder<W>,
+    name: &str,
+    size: u64,
+    data: R,
+) -> Result<(), Error> {
+    let mut header = ar::Header::new(name.as_bytes().to_vec(), size);
+    header.set_mtime(utils::entry_mtime());
+    header.set_uid(0);
+    header.set_gid(0);
+    header.set_mode(0o100_644);
+    builder.append(&header, data)?;
     Ok(())
 }
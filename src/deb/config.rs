@@ -3,8 +3,34 @@
 // in the LICENSE file.
 
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 
+use crate::base::compress::CompressFormat;
+use crate::base::expand_file_macro_simple;
 use crate::base::fileset::FileSet;
+use crate::base::relation::Relations;
+use crate::base::script::Script;
+use crate::base::utils::{default_false, default_true};
+use crate::error::{Error, ErrorKind};
+
+/// Relation fields of deb control file.
+#[derive(Debug, Default, Deserialize, Serialize, Clone)]
+pub struct DebRelations {
+    /// Packages which must be configured before this package is unpacked.
+    pub pre_depends: Option<Relations>,
+
+    /// Relations to other packages, like `libc6 (>= 2.36), foo | bar`.
+    ///
+    /// Either a comma separated string or a list of strings.
+    pub depends: Option<Relations>,
+    pub recommends: Option<Relations>,
+    pub suggests: Option<Relations>,
+    pub enhances: Option<Relations>,
+    pub conflicts: Option<Relations>,
+    pub breaks: Option<Relations>,
+    pub replaces: Option<Relations>,
+    pub provides: Option<Relations>,
+}
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct DebConfig {
@@ -12,18 +38,77 @@ pub struct DebConfig {
     pub priority: String,
 
     pub section: Option<String>,
-    pub depends: Option<String>,
-    pub recommends: Option<String>,
-    pub conflicts: Option<String>,
-    pub breaks: Option<String>,
-    pub replaces: Option<String>,
-    pub provides: Option<String>,
+
+    /// Debian revision appended to version, like `2` in `1.0.0-2`.
+    ///
+    /// Not set by default for binary packages, and `1` is used for source package.
+    /// Macros like `${date}` and `${git}` are expanded.
+    pub revision: Option<String>,
+
+    /// Epoch of package, written as `epoch:version-revision` in `Version` field.
+    pub epoch: Option<u32>,
+
+    /// Relations to other packages, like `depends` and `conflicts`.
+    #[serde(flatten)]
+    pub relations: DebRelations,
+
+    /// Custom fields written to control file, whose names start with `X-`.
+    #[serde(default = "BTreeMap::new")]
+    pub custom_fields: BTreeMap<String, String>,
+
+    /// Boolean - whether to detect shared libraries required by ELF files,
+    /// and append packages providing them to `depends`.
+    ///
+    /// Local dpkg database is used to resolve those packages,
+    /// like `dpkg-shlibdeps` does.
+    #[serde(default = "default_false")]
+    pub auto_depends: bool,
+
+    /// Packages required to build source package, besides `debhelper-compat`.
+    pub build_depends: Option<Relations>,
 
     pub files: Option<Vec<FileSet>>,
+
+    /// Compression format of `control.tar` and `data.tar`.
+    ///
+    /// Available values are `none`, `gzip`, `xz` and `zstd`, default is `xz`.
+    /// Note that `zstd` requires dpkg 1.21.18 or later.
+    #[serde(default = "default_compression")]
+    pub compression: CompressFormat,
+
+    /// Compression level, default level of `compression` format is used if not set.
+    pub compression_level: Option<u32>,
+
+    /// Configuration files which shall be preserved by dpkg on upgrade,
+    /// like `/etc/foo/foo.conf`.
+    pub conffiles: Option<Vec<String>>,
+
+    /// Boolean - whether to mark all files installed to `/etc` as conffiles.
+    #[serde(default = "default_true")]
+    pub auto_conffiles: bool,
+
+    /// Script run before the package is unpacked.
+    pub preinst: Option<Script>,
+
+    /// Script run after the package is unpacked and configured.
+    pub postinst: Option<Script>,
+
+    /// Script run before the package is removed.
+    pub prerm: Option<Script>,
+
+    /// Script run after the package is removed or purged.
+    pub postrm: Option<Script>,
+
+    /// Debconf script, to ask questions before the package is configured.
+    pub config: Option<Script>,
 }
 
 fn default_priority() -> String {
-    "utility".to_string()
+    "optional".to_string()
+}
+
+const fn default_compression() -> CompressFormat {
+    CompressFormat::Xz
 }
 
 impl Default for DebConfig {
@@ -31,13 +116,110 @@ impl Default for DebConfig {
         Self {
             priority: default_priority(),
             section: None,
-            depends: None,
-            recommends: None,
-            conflicts: None,
-            breaks: None,
-            replaces: None,
-            provides: None,
+            revision: None,
+            epoch: None,
+            relations: DebRelations::default(),
+            custom_fields: BTreeMap::new(),
+            auto_depends: false,
+            build_depends: None,
             files: None,
+            compression: default_compression(),
+            compression_level: None,
+            conffiles: None,
+            auto_conffiles: true,
+            preinst: None,
+            postinst: None,
+            prerm: None,
+            postrm: None,
+            config: None,
+        }
+    }
+}
+
+impl DebRelations {
+    /// Get relation fields of control file, in order of Debian policy.
+    #[must_use]
+    pub const fn fields(&self) -> [(&'static str, Option<&Relations>); 9] {
+        [
+            ("Pre-Depends", self.pre_depends.as_ref()),
+            ("Depends", self.depends.as_ref()),
+            ("Recommends", self.recommends.as_ref()),
+            ("Suggests", self.suggests.as_ref()),
+            ("Enhances", self.enhances.as_ref()),
+            ("Conflicts", self.conflicts.as_ref()),
+            ("Breaks", self.breaks.as_ref()),
+            ("Replaces", self.replaces.as_ref()),
+            ("Provides", self.provides.as_ref()),
+        ]
+    }
+
+    /// Validate package names and operators of relations.
+    ///
+    /// # Errors
+    /// Returns error if any relation is invalid for its field.
+    pub fn validate(&self) -> Result<(), Error> {
+        for (field, relations) in self.fields() {
+            if let Some(relations) = relations {
+                relations.validate_deb(field)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl DebConfig {
+    /// Expand macros in `revision`.
+    ///
+    /// # Errors
+    /// Returns error if failed to expand macros.
+    pub fn expand_macros(&mut self) -> Result<(), Error> {
+        if let Some(revision) = self.revision.as_mut() {
+            *revision = expand_file_macro_simple(revision)?;
+        }
+        Ok(())
+    }
+
+    /// Validate revision, relations and custom fields.
+    ///
+    /// # Errors
+    /// Returns error if revision, package names or field names are invalid.
+    pub fn validate(&self) -> Result<(), Error> {
+        if let Some(revision) = &self.revision {
+            let valid_revision = !revision.is_empty()
+                && revision
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || "+.~".contains(c));
+            if !valid_revision {
+                return Err(Error::from_string(
+                    ErrorKind::InvalidConfError,
+                    format!("Invalid debian revision `{revision}`"),
+                ));
+            }
+        }
+        self.relations.validate()?;
+        if let Some(build_depends) = &self.build_depends {
+            build_depends.validate_deb("Build-Depends")?;
+        }
+
+        for (name, value) in &self.custom_fields {
+            let valid_name = name.len() > 2
+                && name[..2].eq_ignore_ascii_case("x-")
+                && name
+                    .chars()
+                    .all(|c| c.is_ascii_graphic() && c != ':' && c != '#');
+            if !valid_name {
+                return Err(Error::from_string(
+                    ErrorKind::InvalidConfError,
+                    format!("Invalid custom field name `{name}`, it shall start with `X-`"),
+                ));
+            }
+            if value.trim().is_empty() || value.contains('\n') {
+                return Err(Error::from_string(
+                    ErrorKind::InvalidConfError,
+                    format!("Value of custom field `{name}` shall be a non-empty single line"),
+                ));
+            }
         }
+        Ok(())
     }
 }
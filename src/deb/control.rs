@@ -3,107 +3,310 @@
 // in the LICENSE file.
 
 use std::fs::{self, File};
-use std::io::{self, Write};
-use std::path::Path;
-use walkdir::WalkDir;
+use std::io::Write;
+#[cfg(not(target_os = "windows"))]
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
 
-use crate::base::Arch;
+use crate::base::payload::Payload;
+use crate::base::relation::Relations;
+use crate::base::{script, systemd, Arch, Metadata, PackageArch, PlatformTarget};
 use crate::config::Config;
-use crate::Error;
+use crate::deb::DebConfig;
+use crate::error::{Error, ErrorKind};
 
 pub fn generate_control(
-    conf: &Config,
-    arch: Arch,
-    size: u64,
+    metadata: &Metadata,
+    deb: &DebConfig,
+    arch: PackageArch,
+    installed_size: u64,
+    depends: &Relations,
     dest_file: &Path,
 ) -> Result<(), Error> {
-    log::info!("generate_control() dest: {:?}", dest_file);
+    log::info!("generate_control() dest: {}", dest_file.display());
     let dest_dir = dest_file.parent().unwrap();
     fs::create_dir_all(dest_dir)?;
     let mut fd = File::create(dest_file)?;
 
-    let metadata = &conf.metadata;
     writeln!(&mut fd, "Package: {}", metadata.name)?;
-    writeln!(&mut fd, "Version: {}", metadata.version)?;
-    writeln!(&mut fd, "Architecture: {}", arch_name(arch))?;
+    writeln!(
+        &mut fd,
+        "Version: {}",
+        full_version(&metadata.version, deb.epoch, deb.revision.as_deref())
+    )?;
+    writeln!(&mut fd, "Architecture: {}", package_arch_name(arch))?;
 
-    let linux = conf.linux.as_ref().expect("Linux conf is not set");
-    let deb = &linux.deb;
     if let Some(section) = deb.section.as_ref() {
         writeln!(&mut fd, "Section: {section}")?;
     }
     writeln!(&mut fd, "Priority: {}", deb.priority)?;
-    writeln!(&mut fd, "Standards-Version: 3.9.4")?;
     writeln!(&mut fd, "Maintainer: {}", metadata.author)?;
-    writeln!(&mut fd, "Installed-Size: {size}")?;
+    writeln!(&mut fd, "Installed-Size: {installed_size}")?;
 
-    if let Some(ref depends) = deb.depends {
-        writeln!(&mut fd, "Depends: {depends}")?;
-    }
-    if let Some(ref conflicts) = deb.conflicts {
-        writeln!(&mut fd, "Conflicts: {conflicts}")?;
-    }
-    if let Some(ref breaks) = deb.breaks {
-        writeln!(&mut fd, "Breaks: {breaks}")?;
-    }
-    if let Some(ref replaces) = deb.replaces {
-        writeln!(&mut fd, "Replaces: {replaces}")?;
-    }
-    if let Some(ref provides) = deb.provides {
-        writeln!(&mut fd, "Provides: {provides}")?;
+    for (field, relations) in deb.relations.fields() {
+        // `depends` is merged with automatically detected dependencies.
+        let relations = if field == "Depends" {
+            Some(depends)
+        } else {
+            relations
+        };
+        if let Some(value) = relations.and_then(|relations| relations.to_deb(arch)) {
+            writeln!(&mut fd, "{field}: {value}")?;
+        }
     }
 
     writeln!(&mut fd, "Homepage: {}", metadata.homepage)?;
-    writeln!(&mut fd, "Description: {}", metadata.description)?;
+    for (name, value) in &deb.custom_fields {
+        writeln!(&mut fd, "{name}: {value}")?;
+    }
+    writeln!(&mut fd, "{}", format_description(metadata))?;
+
+    Ok(())
+}
+
+/// Write control file of `<name>-dbgsym` package, which contains
+/// debug info of ELF files in `depends` packages.
+pub fn generate_dbgsym_control(
+    conf: &Config,
+    deb: &DebConfig,
+    arch: PackageArch,
+    installed_size: u64,
+    depends: &[String],
+    build_ids: &[String],
+    dest_file: &Path,
+) -> Result<(), Error> {
+    log::info!("generate_dbgsym_control() dest: {}", dest_file.display());
+    let dest_dir = dest_file.parent().unwrap();
+    fs::create_dir_all(dest_dir)?;
+    let mut fd = File::create(dest_file)?;
+
+    let metadata = &conf.metadata;
+    let version = full_version(&metadata.version, deb.epoch, deb.revision.as_deref());
+    writeln!(&mut fd, "Package: {}-dbgsym", metadata.name)?;
+    writeln!(&mut fd, "Source: {}", metadata.name)?;
+    writeln!(&mut fd, "Version: {version}")?;
+    writeln!(&mut fd, "Auto-Built-Package: debug-symbols")?;
+    writeln!(&mut fd, "Architecture: {}", package_arch_name(arch))?;
+    writeln!(&mut fd, "Section: debug")?;
+    writeln!(&mut fd, "Priority: optional")?;
+    writeln!(&mut fd, "Maintainer: {}", metadata.author)?;
+    writeln!(&mut fd, "Installed-Size: {installed_size}")?;
+    let depends = depends
+        .iter()
+        .map(|name| format!("{name} (= {version})"))
+        .collect::<Vec<_>>();
+    writeln!(&mut fd, "Depends: {}", depends.join(", "))?;
+    writeln!(&mut fd, "Description: debug symbols for {}", metadata.name)?;
+    if !build_ids.is_empty() {
+        writeln!(&mut fd, "Build-Ids: {}", build_ids.join(" "))?;
+    }
 
     Ok(())
 }
 
-fn md5_file(file: &Path) -> Result<String, Error> {
-    log::info!("md5_file() file: {:?}", file);
-    let mut in_file = File::open(file)?;
-    let mut context = md5::Context::new();
-    io::copy(&mut in_file, &mut context)?;
-    let digest = context.compute();
-    let hash = format!("{digest:x}");
+/// Get version of package in `[epoch:]upstream_version[-debian_revision]` format,
+/// like `1:1.0.0-2`.
+#[must_use]
+pub fn full_version(version: &str, epoch: Option<u32>, revision: Option<&str>) -> String {
+    let mut full_version = epoch.map_or_else(String::new, |epoch| format!("{epoch}:"));
+    full_version.push_str(version);
+    if let Some(revision) = revision {
+        full_version.push('-');
+        full_version.push_str(revision);
+    }
+    full_version
+}
 
-    Ok(hash)
+/// Format `Description` field, with extended description in continuation lines.
+pub fn format_description(metadata: &Metadata) -> String {
+    let mut description = format!("Description: {}", metadata.summary());
+    if let Some(long_description) = metadata.long_description() {
+        for line in long_description.lines() {
+            if line.is_empty() {
+                description.push_str("\n .");
+            } else {
+                description.push_str("\n ");
+                description.push_str(line);
+            }
+        }
+    }
+    description
 }
 
-pub fn generate_md5sum(dir: &Path, dest_file: &Path) -> Result<(), Error> {
-    log::info!(
-        "generate_md5sum() dir: {:?}, dest_file: {:?}",
-        dir,
-        dest_file
-    );
+/// Write md5 hash of each file into `dest_file`, in `md5sum` output format.
+pub fn generate_md5sums(md5sums: &[(PathBuf, String)], dest_file: &Path) -> Result<(), Error> {
+    log::info!("generate_md5sums() dest_file: {}", dest_file.display());
     let dest_dir = dest_file.parent().unwrap();
     fs::create_dir_all(dest_dir)?;
     let mut dest_fd = File::create(dest_file)?;
+    for (path, hash) in md5sums {
+        writeln!(dest_fd, "{}  {}", hash, path.display())?;
+    }
+
+    Ok(())
+}
+
+/// Write list of conffiles into `dest_file`.
+///
+/// Files in `/etc` are detected automatically if `auto_conffiles` is enabled.
+/// Nothing is written if there is no conffile.
+pub fn generate_conffiles(
+    deb: &DebConfig,
+    payload: &Payload,
+    dest_file: &Path,
+) -> Result<(), Error> {
+    log::info!("generate_conffiles() dest: {}", dest_file.display());
 
-    for entry in WalkDir::new(dir) {
-        let entry = entry?;
-        let path = entry.path();
-        if path.is_file() {
-            let filename = path.strip_prefix(dir)?;
-            let hash = md5_file(path)?;
-            writeln!(dest_fd, "{} {}", hash, filename.display())?;
+    let mut conffiles = Vec::new();
+    if let Some(files) = deb.conffiles.as_ref() {
+        for file in files {
+            let file = file.trim_start_matches('/');
+            if !payload.is_file(Path::new(file)) {
+                return Err(Error::from_string(
+                    ErrorKind::InvalidConfError,
+                    format!("conffile /{file} is not found in deb package"),
+                ));
+            }
+            conffiles.push(format!("/{file}"));
         }
     }
 
+    if deb.auto_conffiles {
+        for (path, _source) in payload.files() {
+            if path.starts_with("etc") {
+                conffiles.push(format!("/{}", path.display()));
+            }
+        }
+    }
+
+    if conffiles.is_empty() {
+        return Ok(());
+    }
+    conffiles.sort();
+    conffiles.dedup();
+
+    let dest_dir = dest_file.parent().unwrap();
+    fs::create_dir_all(dest_dir)?;
+    let mut fd = File::create(dest_file)?;
+    for file in &conffiles {
+        writeln!(fd, "{file}")?;
+    }
+
     Ok(())
 }
 
-pub fn generate_deb_binary(path: &Path) -> Result<(), Error> {
-    let mut fd = File::create(path)?;
-    writeln!(fd, "2.0")?;
+/// Write maintainer scripts into control directory.
+///
+/// Snippets to manage systemd units are inserted into scripts.
+pub fn generate_scripts(conf: &Config, arch: PackageArch, control_dir: &Path) -> Result<(), Error> {
+    let linux = conf.linux.as_ref().expect("Linux conf is not set");
+    let deb = &linux.deb;
+    let scripts = [
+        ("preinst", &deb.preinst),
+        ("postinst", &deb.postinst),
+        ("prerm", &deb.prerm),
+        ("postrm", &deb.postrm),
+        ("config", &deb.config),
+    ];
+
+    fs::create_dir_all(control_dir)?;
+    for (name, script) in scripts {
+        let content = script
+            .as_ref()
+            .map(|script| script.read(conf, arch, PlatformTarget::Deb))
+            .transpose()?;
+        let snippet = systemd::deb_script_snippet(&linux.systemd_units, name)?;
+        let content = match (content, snippet) {
+            (None, None) => continue,
+            (content, snippet) => script::insert_snippet(content, &snippet.unwrap_or_default()),
+        };
+        log::info!("generate_scripts() {name}");
+        let path = control_dir.join(name);
+        fs::write(&path, content)?;
+
+        #[cfg(not(target_os = "windows"))]
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755))?;
+    }
+
     Ok(())
 }
 
+/// Get name of `arch` in Debian.
 pub const fn arch_name(arch: Arch) -> &'static str {
     match arch {
         Arch::X86 => "i386",
         Arch::X86_64 => "amd64",
         Arch::AArch64 => "arm64",
-        Arch::Mips64 => "mips64",
+        Arch::Mips64 => "mips64el",
+        Arch::Armv7 => "armhf",
+        Arch::Riscv64 => "riscv64",
+        Arch::Ppc64le => "ppc64el",
+        Arch::S390x => "s390x",
+        Arch::LoongArch64 => "loong64",
+    }
+}
+
+/// Get name of package `arch` in Debian, `all` if arch independent.
+pub const fn package_arch_name(arch: PackageArch) -> &'static str {
+    match arch {
+        PackageArch::Target(arch) => arch_name(arch),
+        PackageArch::All => "all",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new_metadata(short_description: Option<&str>, description: &str) -> Metadata {
+        Metadata {
+            name: "hello".to_owned(),
+            short_description: short_description.map(ToOwned::to_owned),
+            description: description.to_owned(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_full_version() {
+        assert_eq!(full_version("1.0.0", None, None), "1.0.0");
+        assert_eq!(full_version("1.0.0", None, Some("2")), "1.0.0-2");
+        assert_eq!(
+            full_version("1.0.0-rc1", Some(1), Some("1")),
+            "1:1.0.0-rc1-1"
+        );
+    }
+
+    #[test]
+    fn test_format_description() {
+        let metadata = new_metadata(None, "Hello app");
+        assert_eq!(format_description(&metadata), "Description: Hello app");
+
+        let metadata = new_metadata(
+            None,
+            "Hello app\nFirst paragraph,\ncontinued.\n\n\nSecond paragraph.\n  verbatim line\n",
+        );
+        assert_eq!(
+            format_description(&metadata),
+            "Description: Hello app\n First paragraph,\n continued.\n .\n Second paragraph.\n   verbatim line"
+        );
+
+        let metadata = new_metadata(Some("Hello app"), "Long text.\n\nMore text.");
+        assert_eq!(
+            format_description(&metadata),
+            "Description: Hello app\n Long text.\n .\n More text."
+        );
+    }
+
+    #[test]
+    fn test_arch_name() {
+        assert_eq!(arch_name(Arch::X86), "i386");
+        assert_eq!(arch_name(Arch::X86_64), "amd64");
+        assert_eq!(arch_name(Arch::AArch64), "arm64");
+        assert_eq!(arch_name(Arch::Mips64), "mips64el");
+        assert_eq!(arch_name(Arch::Armv7), "armhf");
+        assert_eq!(arch_name(Arch::Ppc64le), "ppc64el");
+        assert_eq!(arch_name(Arch::LoongArch64), "loong64");
+        assert_eq!(package_arch_name(PackageArch::All), "all");
     }
 }
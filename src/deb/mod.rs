@@ -2,9 +2,15 @@
 // Use of this source is governed by General Public License that can be found
 // in the LICENSE file.
 
-mod build;
+pub mod build;
 mod config;
 mod control;
+pub mod reader;
+mod repo;
+mod shlibs;
+mod source;
 
 pub use build::build_deb;
-pub use config::DebConfig;
+pub use config::{DebConfig, DebRelations};
+pub use repo::generate_apt_repo;
+pub use source::build_deb_source;
@@ -0,0 +1,125 @@
+// Copyright (c) 2021 Xu Shaohua <shaohua@biofan.org>. All rights reserved.
+// Use of this source is governed by General Public License that can be found
+// in the LICENSE file.
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use crate::base::compress::CompressFormat;
+use crate::error::{Error, ErrorKind};
+
+/// Read files in control archive of deb file, like `control` and `postinst`.
+///
+/// # Errors
+/// Returns error if `deb_file` is not a valid deb package.
+pub fn read_control_files(deb_file: &Path) -> Result<BTreeMap<String, Vec<u8>>, Error> {
+    with_tar_member(deb_file, "control.tar", |archive| {
+        let mut files = BTreeMap::new();
+        for entry in archive.entries()? {
+            let mut entry = entry?;
+            if !entry.header().entry_type().is_file() {
+                continue;
+            }
+            let path = entry.path()?;
+            let name = path.to_string_lossy().trim_start_matches("./").to_owned();
+            let mut content = Vec::new();
+            entry.read_to_end(&mut content)?;
+            files.insert(name, content);
+        }
+        Ok(files)
+    })
+}
+
+/// Read `control` file of deb package.
+///
+/// # Errors
+/// Returns error if `deb_file` is not a valid deb package.
+pub fn read_control(deb_file: &Path) -> Result<String, Error> {
+    let mut files = read_control_files(deb_file)?;
+    let control = files.remove("control").ok_or_else(|| {
+        Error::from_string(
+            ErrorKind::InvalidPackageError,
+            format!("No control file found in {}", deb_file.display()),
+        )
+    })?;
+    Ok(String::from_utf8(control)?)
+}
+
+/// Get value of field `name` in control file.
+#[must_use]
+pub fn control_field<'a>(control: &'a str, name: &str) -> Option<&'a str> {
+    control.lines().find_map(|line| {
+        line.split_once(':')
+            .filter(|(key, _value)| key.eq_ignore_ascii_case(name))
+            .map(|(_key, value)| value.trim())
+    })
+}
+
+/// Split control file into fields, continuation lines are joined with `\n`,
+/// with the leading space removed.
+#[must_use]
+pub fn control_fields(control: &str) -> Vec<(String, String)> {
+    let mut fields: Vec<(String, String)> = Vec::new();
+    for line in control.lines() {
+        if line.starts_with([' ', '\t']) {
+            if let Some((_name, value)) = fields.last_mut() {
+                value.push('\n');
+                value.push_str(line[1..].trim_end());
+            }
+        } else if let Some((name, value)) = line.split_once(':') {
+            fields.push((name.trim().to_owned(), value.trim().to_owned()));
+        }
+    }
+    fields
+}
+
+/// Find ar member of deb file whose name starts with `prefix`,
+/// and pass it to `f` as a tar archive.
+///
+/// # Errors
+/// Returns error if member is not found or `f` returns error.
+pub fn with_tar_member<T, F>(deb_file: &Path, prefix: &str, f: F) -> Result<T, Error>
+where
+    F: for<'a> FnOnce(&mut tar::Archive<Box<dyn Read + 'a>>) -> Result<T, Error>,
+{
+    let fd = File::open(deb_file)?;
+    let mut archive = ar::Archive::new(fd);
+    while let Some(entry) = archive.next_entry() {
+        let entry = entry?;
+        let identifier = String::from_utf8_lossy(entry.header().identifier()).to_string();
+        let identifier = identifier.trim_end_matches('/');
+        if identifier.starts_with(prefix) {
+            let decoder = CompressFormat::from_filename(identifier).decoder(entry)?;
+            let mut tar_archive = tar::Archive::new(decoder);
+            return f(&mut tar_archive);
+        }
+    }
+
+    Err(Error::from_string(
+        ErrorKind::InvalidPackageError,
+        format!("No {prefix} member found in {}", deb_file.display()),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_control_fields() {
+        let control = "Package: hello\nVersion: 1:1.0.0-2\nDescription: Hello app\n Long line.\n .\n Second paragraph.\n";
+        assert_eq!(control_field(control, "version"), Some("1:1.0.0-2"));
+        assert_eq!(control_field(control, "Depends"), None);
+        let fields = control_fields(control);
+        assert_eq!(fields.len(), 3);
+        assert_eq!(
+            fields[2],
+            (
+                "Description".to_owned(),
+                "Hello app\nLong line.\n.\nSecond paragraph.".to_owned()
+            )
+        );
+    }
+}
@@ -0,0 +1,234 @@
+// Copyright (c) 2021 Xu Shaohua <shaohua@biofan.org>. All rights reserved.
+// Use of this source is governed by General Public License that can be found
+// in the LICENSE file.
+
+use std::collections::BTreeSet;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::Path;
+use time::{format_description, OffsetDateTime};
+
+use super::reader;
+use crate::base::compress::CompressFormat;
+use crate::base::hash;
+use crate::error::{Error, ErrorKind};
+
+/// Generate a flat apt repository in `repo_dir` from deb files in `deb_dir`.
+///
+/// Deb files are copied to `repo_dir`, with `Packages`, `Packages.gz`,
+/// `Packages.xz` and `Release` files generated. The repository can be used
+/// with apt source like `deb [trusted=yes] file:/path/to/repo ./`.
+///
+/// # Errors
+/// Returns error if failed to read deb files or failed to write index files.
+pub fn generate_apt_repo(deb_dir: &Path, repo_dir: &Path) -> Result<(), Error> {
+    log::info!(
+        "generate_apt_repo() deb_dir: {}, repo_dir: {}",
+        deb_dir.display(),
+        repo_dir.display()
+    );
+    fs::create_dir_all(repo_dir)?;
+    let same_dir = fs::canonicalize(deb_dir)? == fs::canonicalize(repo_dir)?;
+
+    let pattern = format!("{}/*.deb", deb_dir.display());
+    let mut deb_files = glob::glob(&pattern)?.collect::<Result<Vec<_>, _>>()?;
+    deb_files.sort();
+    if deb_files.is_empty() {
+        return Err(Error::from_string(
+            ErrorKind::FilesNotSet,
+            format!("No deb file found in {}", deb_dir.display()),
+        ));
+    }
+
+    let mut packages = String::new();
+    let mut arches = BTreeSet::new();
+    for deb_file in &deb_files {
+        let Some(filename) = deb_file.file_name() else {
+            continue;
+        };
+        let filename = filename.to_string_lossy();
+        log::info!("generate_apt_repo() add {filename}");
+        let control = reader::read_control(deb_file)?;
+        if let Some(arch) = reader::control_field(&control, "Architecture") {
+            arches.insert(arch.to_owned());
+        }
+
+        let target_file = repo_dir.join(filename.as_ref());
+        if !same_dir {
+            let _ = fs::remove_file(&target_file);
+            if fs::hard_link(deb_file, &target_file).is_err() {
+                fs::copy(deb_file, &target_file)?;
+            }
+        }
+
+        if !packages.is_empty() {
+            packages.push('\n');
+        }
+        packages.push_str(control.trim_end());
+        packages.push('\n');
+        writeln!(packages, "Filename: ./{filename}")?;
+        writeln!(packages, "Size: {}", fs::metadata(deb_file)?.len())?;
+        writeln!(packages, "MD5sum: {}", hash::md5sum(deb_file)?)?;
+        writeln!(packages, "SHA256: {}", hash::sha256sum(deb_file)?)?;
+    }
+
+    let packages_file = repo_dir.join("Packages");
+    fs::write(&packages_file, packages)?;
+    let mut index_files = vec![packages_file.clone()];
+    for format in [CompressFormat::Gzip, CompressFormat::Xz] {
+        index_files.push(format.compress(&packages_file, None)?);
+    }
+
+    generate_release(&index_files, &arches, &repo_dir.join("Release"))
+}
+
+/// Write `Release` file with checksums of index files.
+fn generate_release(
+    index_files: &[impl AsRef<Path>],
+    arches: &BTreeSet<String>,
+    release_file: &Path,
+) -> Result<(), Error> {
+    let date_format = format_description::parse(
+        "[weekday repr:short], [day] [month repr:short] [year] [hour]:[minute]:[second] UTC",
+    )?;
+    let date = OffsetDateTime::now_utc().format(&date_format)?;
+
+    let mut md5_lines = String::new();
+    let mut sha256_lines = String::new();
+    for index_file in index_files {
+        let index_file = index_file.as_ref();
+        let size = fs::metadata(index_file)?.len();
+        let name = index_file
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        writeln!(md5_lines, " {} {size} {name}", hash::md5sum(index_file)?)?;
+        writeln!(
+            sha256_lines,
+            " {} {size} {name}",
+            hash::sha256sum(index_file)?
+        )?;
+    }
+
+    let mut release = String::new();
+    writeln!(release, "Date: {date}")?;
+    if !arches.is_empty() {
+        let arches = arches.iter().map(String::as_str).collect::<Vec<_>>();
+        writeln!(release, "Architectures: {}", arches.join(" "))?;
+    }
+    writeln!(release, "MD5Sum:\n{md5_lines}SHA256:\n{sha256_lines}")?;
+    fs::write(release_file, release.trim_end().to_owned() + "\n")?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use sha2::{Digest, Sha256};
+
+    use super::*;
+    use crate::base::payload::Payload;
+    use crate::base::utils;
+    use crate::deb::build::{create_deb, write_data_tar};
+    use crate::deb::reader::control_fields;
+    use crate::deb::DebConfig;
+
+    /// Build deb file with `control` in `deb_dir`.
+    fn build_test_deb(dir: &Path, deb_dir: &Path, name: &str, control: &str) {
+        let pkg_dir = dir.join(name);
+        let control_dir = pkg_dir.join("control");
+        let data_dir = pkg_dir.join("data/usr/share/hello");
+        fs::create_dir_all(&control_dir).unwrap();
+        fs::create_dir_all(&data_dir).unwrap();
+        fs::write(control_dir.join("control"), control).unwrap();
+        fs::write(data_dir.join("README"), "hello").unwrap();
+
+        let deb_conf = DebConfig::default();
+        let payload = Payload::from_dir(&pkg_dir.join("data")).unwrap();
+        let (data_file, _summary) = write_data_tar(&deb_conf, &payload, &pkg_dir).unwrap();
+        create_deb(&deb_conf, &control_dir, &data_file, &deb_dir.join(name)).unwrap();
+    }
+
+    fn sha256_hex(content: &[u8]) -> String {
+        hex::encode(Sha256::digest(content))
+    }
+
+    #[test]
+    fn test_generate_apt_repo() {
+        let dir = utils::test_dir("apt-repo");
+        let deb_dir = dir.join("debs");
+        let repo_dir = dir.join("apt");
+        fs::create_dir_all(&deb_dir).unwrap();
+        build_test_deb(
+            &dir,
+            &deb_dir,
+            "hello_1.0.0_amd64.deb",
+            "Package: hello\nVersion: 1.0.0\nArchitecture: amd64\nMaintainer: Hello <hello@example.com>\nDescription: Hello app\n Long description.\n",
+        );
+        build_test_deb(
+            &dir,
+            &deb_dir,
+            "hello-data_1.0.0_all.deb",
+            "Package: hello-data\nVersion: 1.0.0\nArchitecture: all\nMaintainer: Hello <hello@example.com>\nDescription: Data of hello\n",
+        );
+        generate_apt_repo(&deb_dir, &repo_dir).unwrap();
+
+        // Packages are sorted by file name, with control fields first.
+        let packages = fs::read_to_string(repo_dir.join("Packages")).unwrap();
+        let stanzas = packages.split("\n\n").collect::<Vec<_>>();
+        assert_eq!(stanzas.len(), 2);
+        let fields = control_fields(stanzas[1]);
+        let names = fields
+            .iter()
+            .map(|(name, _value)| name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            [
+                "Package",
+                "Version",
+                "Architecture",
+                "Maintainer",
+                "Description",
+                "Filename",
+                "Size",
+                "MD5sum",
+                "SHA256"
+            ]
+        );
+        assert_eq!(fields[4].1, "Hello app\nLong description.");
+        let deb_content = fs::read(deb_dir.join("hello_1.0.0_amd64.deb")).unwrap();
+        assert_eq!(fields[5].1, "./hello_1.0.0_amd64.deb");
+        assert_eq!(fields[6].1, deb_content.len().to_string());
+        assert_eq!(fields[8].1, sha256_hex(&deb_content));
+        assert_eq!(
+            control_fields(stanzas[0])[0],
+            ("Package".to_owned(), "hello-data".to_owned())
+        );
+        assert!(repo_dir.join("hello_1.0.0_amd64.deb").is_file());
+
+        // Release lists checksums and sizes of index files.
+        let release = fs::read_to_string(repo_dir.join("Release")).unwrap();
+        let fields = control_fields(&release);
+        let names = fields
+            .iter()
+            .map(|(name, _value)| name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["Date", "Architectures", "MD5Sum", "SHA256"]);
+        assert_eq!(fields[1].1, "all amd64");
+        let sha256_lines = fields[3].1.trim().lines().collect::<Vec<_>>();
+        assert_eq!(sha256_lines.len(), 3);
+        for (line, name) in sha256_lines
+            .iter()
+            .zip(["Packages", "Packages.gz", "Packages.xz"])
+        {
+            let content = fs::read(repo_dir.join(name)).unwrap();
+            assert_eq!(
+                *line,
+                format!("{} {} {name}", sha256_hex(&content), content.len())
+            );
+        }
+
+        let _ = fs::remove_dir_all(dir);
+    }
+}
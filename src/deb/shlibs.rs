@@ -0,0 +1,357 @@
+// Copyright (c) 2021 Xu Shaohua <shaohua@biofan.org>. All rights reserved.
+// Use of this source is governed by General Public License that can be found
+// in the LICENSE file.
+
+//! Resolve shared libraries required by ELF files to deb packages,
+//! in the spirit of `dpkg-shlibdeps`.
+
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::control::package_arch_name;
+use crate::base::payload::Payload;
+use crate::base::relation::Relations;
+use crate::base::{elf, Arch, PackageArch};
+use crate::error::{Error, ErrorKind};
+
+const DPKG_INFO_DIR: &str = "/var/lib/dpkg/info";
+const DPKG_STATUS_FILE: &str = "/var/lib/dpkg/status";
+
+/// Get packages which provide shared libraries required by ELF files in `payload`.
+///
+/// Libraries are resolved to their owning packages with local dpkg database,
+/// and versioned with `shlibs` file of that package, or its installed version.
+///
+/// # Errors
+/// Returns error if failed to parse ELF files, or dpkg database is not found
+/// or failed to read it.
+pub fn get_shlib_depends(payload: &Payload, arch: PackageArch) -> Result<Relations, Error> {
+    let mut needed_libs = BTreeSet::new();
+    let mut own_files = HashSet::new();
+    for (path, source) in payload.files() {
+        if let Some(file_name) = path.file_name() {
+            own_files.insert(file_name.to_string_lossy().to_string());
+        }
+        if elf::is_elf(source) {
+            needed_libs.extend(elf::get_needed_libs(source)?);
+        }
+    }
+    // Skip libraries shipped in this package.
+    needed_libs.retain(|lib| !own_files.contains(lib));
+    log::info!("get_shlib_depends() needed libs: {needed_libs:?}");
+    if needed_libs.is_empty() {
+        return Ok(Relations::default());
+    }
+    let PackageArch::Target(target_arch) = arch else {
+        log::warn!("get_shlib_depends() ELF files found in arch-independent package");
+        return Ok(Relations::default());
+    };
+    if !Path::new(DPKG_INFO_DIR).is_dir() || !Path::new(DPKG_STATUS_FILE).is_file() {
+        return Err(Error::from_string(
+            ErrorKind::DpkgDatabaseError,
+            format!(
+                "dpkg database not found at {DPKG_INFO_DIR} and {DPKG_STATUS_FILE}, \
+                 it is required to resolve shared library dependencies, \
+                 set `auto_depends = false` in deb section to disable it"
+            ),
+        ));
+    }
+
+    let lib_dirs = resolve_libs(&needed_libs, target_arch);
+    let arch = package_arch_name(arch);
+    let lib_owners = find_lib_owners(&lib_dirs, arch)?;
+    let versions = read_installed_versions(arch)?;
+
+    let mut depends = BTreeSet::new();
+    for lib in &needed_libs {
+        let Some(pkg) = lib_owners.get(lib) else {
+            log::warn!("No package found for shared library {lib}");
+            continue;
+        };
+        if let Some(dep) = read_shlibs_depends(pkg, arch, lib)? {
+            depends.insert(dep);
+        } else if let Some(version) = versions.get(pkg) {
+            depends.insert(format!("{pkg} (>= {})", upstream_version(version)));
+        } else {
+            depends.insert(pkg.clone());
+        }
+    }
+
+    depends.into_iter().collect::<Vec<_>>().join(", ").parse()
+}
+
+/// Merge automatically detected dependencies into `depends` field.
+///
+/// Detected relations are appended even if their packages are listed
+/// in `depends`, like dpkg-gencontrol does, so that both version constraints
+/// are required. Only relations identical to listed ones are skipped.
+#[must_use]
+pub fn merge_depends(depends: Option<&Relations>, shlib_depends: &Relations) -> Relations {
+    let mut merged = depends.cloned().unwrap_or_default();
+    for relation in shlib_depends {
+        if !merged.iter().any(|listed| listed == relation) {
+            merged.push(relation.clone());
+        }
+    }
+    merged
+}
+
+/// Split info filename like `libc6:amd64.list` into package name and arch.
+fn split_info_name<'a>(filename: &'a str, ext: &str) -> Option<(&'a str, Option<&'a str>)> {
+    let stem = filename.strip_suffix(ext)?;
+    Some(
+        stem.split_once(':')
+            .map_or((stem, None), |(pkg, arch)| (pkg, Some(arch))),
+    )
+}
+
+/// Get library search directories of `arch`, in order of the dynamic linker.
+fn lib_search_dirs(arch: Arch) -> Vec<PathBuf> {
+    let triplet = multiarch_triplet(arch);
+    vec![
+        PathBuf::from(format!("/lib/{triplet}")),
+        PathBuf::from(format!("/usr/lib/{triplet}")),
+        PathBuf::from("/lib"),
+        PathBuf::from("/usr/lib"),
+        PathBuf::from("/lib64"),
+        PathBuf::from("/usr/lib64"),
+    ]
+}
+
+/// Get multiarch tuple of `arch` in Debian, like `x86_64-linux-gnu`.
+const fn multiarch_triplet(arch: Arch) -> &'static str {
+    match arch {
+        Arch::X86 => "i386-linux-gnu",
+        Arch::X86_64 => "x86_64-linux-gnu",
+        Arch::AArch64 => "aarch64-linux-gnu",
+        Arch::Mips64 => "mips64el-linux-gnuabi64",
+        Arch::Armv7 => "arm-linux-gnueabihf",
+        Arch::Riscv64 => "riscv64-linux-gnu",
+        Arch::Ppc64le => "powerpc64le-linux-gnu",
+        Arch::S390x => "s390x-linux-gnu",
+        Arch::LoongArch64 => "loongarch64-linux-gnu",
+    }
+}
+
+/// Resolve `libs` to directories containing them, with symlinks in directory
+/// resolved, like `/usr/lib/x86_64-linux-gnu` for `libc.so.6`.
+///
+/// Libraries not found in search directories are skipped.
+fn resolve_libs(libs: &BTreeSet<String>, arch: Arch) -> HashMap<String, PathBuf> {
+    let search_dirs: Vec<PathBuf> = lib_search_dirs(arch)
+        .iter()
+        .filter_map(|dir| fs::canonicalize(dir).ok())
+        .collect();
+    let mut lib_dirs = HashMap::new();
+    for lib in libs {
+        if let Some(dir) = search_dirs.iter().find(|dir| dir.join(lib).exists()) {
+            lib_dirs.insert(lib.clone(), dir.clone());
+        }
+    }
+    lib_dirs
+}
+
+/// Find packages which own `lib_dirs`, by reading file lists in dpkg database.
+///
+/// Paths in file lists are matched against directory of the resolved library,
+/// so that a library with the same name in another directory, like a plugin
+/// of some application, is not taken as the owner.
+fn find_lib_owners(
+    lib_dirs: &HashMap<String, PathBuf>,
+    arch: &str,
+) -> Result<HashMap<String, String>, Error> {
+    let mut list_files = Vec::new();
+    for entry in fs::read_dir(DPKG_INFO_DIR)? {
+        let entry = entry?;
+        let filename = entry.file_name().to_string_lossy().to_string();
+        if let Some((pkg, pkg_arch)) = split_info_name(&filename, ".list") {
+            if pkg_arch.is_none_or(|pkg_arch| pkg_arch == arch || pkg_arch == "all") {
+                list_files.push((pkg.to_owned(), entry.path()));
+            }
+        }
+    }
+    list_files.sort();
+
+    // Directories in file lists may be symlinks, like `/lib` to `/usr/lib`.
+    let mut canonical_dirs: HashMap<PathBuf, Option<PathBuf>> = HashMap::new();
+    let mut owners = HashMap::new();
+    for (pkg, list_file) in list_files {
+        let content = fs::read_to_string(&list_file)?;
+        for line in content.lines() {
+            let path = Path::new(line);
+            let (Some(filename), Some(parent)) = (path.file_name(), path.parent()) else {
+                continue;
+            };
+            let filename = filename.to_string_lossy();
+            let Some(lib_dir) = lib_dirs.get(filename.as_ref()) else {
+                continue;
+            };
+            if owners.contains_key(filename.as_ref()) {
+                continue;
+            }
+            let dir = canonical_dirs
+                .entry(parent.to_path_buf())
+                .or_insert_with(|| fs::canonicalize(parent).ok());
+            if dir.as_ref() == Some(lib_dir) {
+                owners.insert(filename.to_string(), pkg.clone());
+            }
+        }
+    }
+    Ok(owners)
+}
+
+/// Read versions of installed packages of `arch` from dpkg status file.
+fn read_installed_versions(arch: &str) -> Result<HashMap<String, String>, Error> {
+    let content = fs::read_to_string(DPKG_STATUS_FILE)?;
+    let mut versions = HashMap::new();
+    for paragraph in content.split("\n\n") {
+        let mut package = None;
+        let mut pkg_arch = None;
+        let mut version = None;
+        let mut installed = false;
+        for line in paragraph.lines() {
+            if let Some(value) = line.strip_prefix("Package: ") {
+                package = Some(value.trim());
+            } else if let Some(value) = line.strip_prefix("Architecture: ") {
+                pkg_arch = Some(value.trim());
+            } else if let Some(value) = line.strip_prefix("Version: ") {
+                version = Some(value.trim());
+            } else if let Some(value) = line.strip_prefix("Status: ") {
+                installed = value.trim().ends_with(" installed");
+            }
+        }
+        if let (Some(package), Some(pkg_arch), Some(version)) = (package, pkg_arch, version) {
+            if installed && (pkg_arch == arch || pkg_arch == "all") {
+                versions.insert(package.to_owned(), version.to_owned());
+            }
+        }
+    }
+    Ok(versions)
+}
+
+/// Read dependency of `soname` from `shlibs` file of `pkg`.
+///
+/// Each line of `shlibs` file is in format `library soversion dependencies`,
+/// like `libc 6 libc6 (>= 2.36)`.
+fn read_shlibs_depends(pkg: &str, arch: &str, soname: &str) -> Result<Option<String>, Error> {
+    let Some((library, soversion)) = split_soname(soname) else {
+        return Ok(None);
+    };
+    let info_dir = Path::new(DPKG_INFO_DIR);
+    let mut shlibs_file = info_dir.join(format!("{pkg}:{arch}.shlibs"));
+    if !shlibs_file.exists() {
+        shlibs_file = info_dir.join(format!("{pkg}.shlibs"));
+        if !shlibs_file.exists() {
+            return Ok(None);
+        }
+    }
+
+    let content = fs::read_to_string(&shlibs_file)?;
+    Ok(parse_shlibs(&content, library, soversion))
+}
+
+/// Get dependency of `library` with `soversion` in content of `shlibs` file.
+fn parse_shlibs(content: &str, library: &str, soversion: &str) -> Option<String> {
+    for line in content.lines() {
+        let mut parts = line.splitn(3, char::is_whitespace);
+        let (Some(name), Some(version), Some(depends)) = (parts.next(), parts.next(), parts.next())
+        else {
+            continue;
+        };
+        // Skip entries for other package types, like `udeb: libc 6 libc6-udeb`.
+        if name.ends_with(':') {
+            continue;
+        }
+        if name == library && version == soversion {
+            return Some(depends.trim().to_owned());
+        }
+    }
+    None
+}
+
+/// Split soname into library name and version,
+/// like `libfoo.so.1` to `(libfoo, 1)`, and `libfoo-1.2.so` to `(libfoo, 1.2)`.
+fn split_soname(soname: &str) -> Option<(&str, &str)> {
+    if let Some((name, version)) = soname.split_once(".so.") {
+        return Some((name, version));
+    }
+    soname.strip_suffix(".so")?.rsplit_once('-')
+}
+
+/// Remove debian revision from package version, like `2.36-9` to `2.36`.
+fn upstream_version(version: &str) -> &str {
+    version
+        .rsplit_once('-')
+        .map_or(version, |(upstream, _revision)| upstream)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_soname() {
+        assert_eq!(split_soname("libc.so.6"), Some(("libc", "6")));
+        assert_eq!(split_soname("libssl.so.1.1"), Some(("libssl", "1.1")));
+        assert_eq!(split_soname("libfoo-1.2.so"), Some(("libfoo", "1.2")));
+        assert_eq!(split_soname("libfoo.so"), None);
+        assert_eq!(split_soname("hello"), None);
+    }
+
+    #[test]
+    fn test_parse_shlibs() {
+        let content = "\
+udeb: libc 6 libc6-udeb (>= 2.36)
+libc 6 libc6 (>= 2.36)
+libm 6 libc6 (>= 2.36)
+libanl 1 libc6 (>= 2.36)
+";
+        assert_eq!(
+            parse_shlibs(content, "libc", "6").as_deref(),
+            Some("libc6 (>= 2.36)")
+        );
+        assert_eq!(
+            parse_shlibs(content, "libanl", "1").as_deref(),
+            Some("libc6 (>= 2.36)")
+        );
+        assert_eq!(parse_shlibs(content, "libc", "7"), None);
+        assert_eq!(parse_shlibs(content, "libdl", "2"), None);
+    }
+
+    #[test]
+    fn test_merge_depends() {
+        // Stricter detected version is kept besides the listed one.
+        let depends: Relations = "libc6 (>= 2.30), libfoo".parse().unwrap();
+        let shlib_depends: Relations = "libc6 (>= 2.36), libssl3 (>= 3.0.0)".parse().unwrap();
+        let merged = merge_depends(Some(&depends), &shlib_depends);
+        assert_eq!(
+            merged.to_string(),
+            "libc6 (>= 2.30), libfoo, libc6 (>= 2.36), libssl3 (>= 3.0.0)"
+        );
+
+        // Identical relations are not repeated.
+        let depends: Relations = "libssl3 (>= 3.0.0)".parse().unwrap();
+        let merged = merge_depends(Some(&depends), &shlib_depends);
+        assert_eq!(
+            merged.to_string(),
+            "libssl3 (>= 3.0.0), libc6 (>= 2.36)"
+        );
+
+        let merged = merge_depends(None, &shlib_depends);
+        assert_eq!(merged.to_string(), "libc6 (>= 2.36), libssl3 (>= 3.0.0)");
+    }
+
+    #[test]
+    fn test_split_info_name() {
+        assert_eq!(
+            split_info_name("libc6:amd64.list", ".list"),
+            Some(("libc6", Some("amd64")))
+        );
+        assert_eq!(
+            split_info_name("tzdata.list", ".list"),
+            Some(("tzdata", None))
+        );
+        assert_eq!(split_info_name("libc6:amd64.md5sums", ".list"), None);
+    }
+}
@@ -0,0 +1,683 @@
+// Copyright (c) 2021 Xu Shaohua <shaohua@biofan.org>. All rights reserved.
+// Use of this source is governed by General Public License that can be found
+// in the LICENSE file.
+
+//! Generate Debian source package in `3.0 (quilt)` format.
+//!
+//! Files in `src_dir` are packed into `.orig.tar.xz`, and a `debian/` directory
+//! is generated into `.debian.tar.xz`. Its `rules` installs files like file sets
+//! of pifu config do, so that binary packages can be rebuilt with `dpkg-buildpackage`.
+
+use std::fmt::Write as _;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+#[cfg(not(target_os = "windows"))]
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+use super::control;
+use crate::base::archive;
+use crate::base::changelog::{self, Release};
+use crate::base::compress::CompressFormat;
+use crate::base::fileset::{Directory, FileSet, Symlink};
+use crate::base::hash;
+use crate::base::systemd::SYSTEMD_UNIT_DIR;
+use crate::base::utils;
+use crate::base::{Metadata, PackageArch};
+use crate::config::{Config, LinuxConfig};
+use crate::deb::{DebConfig, DebRelations};
+use crate::error::{Error, ErrorKind};
+
+/// Debian revision of generated source package, if `revision` is not set.
+const DEBIAN_REVISION: &str = "1";
+
+const DEBHELPER_COMPAT: &str = "debhelper-compat (= 13)";
+
+const STANDARDS_VERSION: &str = "4.6.2";
+
+const SOURCE_FORMAT: &str = "3.0 (quilt)";
+
+/// Folders of version control systems, excluded from `.orig.tar.xz`.
+const VCS_DIRS: &[&str] = &[".git", ".hg", ".svn", ".bzr"];
+
+/// Build source package into `workdir`.
+///
+/// # Errors
+/// Returns error if `files` is not set, or failed to write source package.
+pub fn build_deb_source(conf: &Config, linux_conf: &LinuxConfig) -> Result<(), Error> {
+    let metadata = &conf.metadata;
+    let deb_conf = &linux_conf.deb;
+    let files = if let Some(files) = deb_conf.files.as_ref() {
+        files
+    } else if let Some(files) = linux_conf.files.as_ref() {
+        files
+    } else {
+        return Err(Error::new(
+            ErrorKind::FilesNotSet,
+            "`files` property not set for deb source package",
+        ));
+    };
+
+    let workdir = Path::new(&metadata.workdir);
+    let source_dir = workdir.join("deb_source");
+    let debian_dir = source_dir.join("debian");
+    let _ = utils::rmdir(&source_dir);
+    fs::create_dir_all(debian_dir.join("source"))?;
+
+    let revision = deb_conf.revision.as_deref().unwrap_or(DEBIAN_REVISION);
+    let version = control::full_version(&metadata.version, deb_conf.epoch, Some(revision));
+    // Epoch is not included in file names.
+    let file_version = control::full_version(&metadata.version, None, Some(revision));
+    let mut rules = Rules::default();
+    rules.add_files(&metadata.name, files, &metadata.src_dir)?;
+    rules.add_entries(
+        &metadata.name,
+        &linux_conf.symlinks,
+        &linux_conf.directories,
+    );
+    for unit in &linux_conf.systemd_units {
+        let path = Path::new(SYSTEMD_UNIT_DIR).join(unit.unit_name()?);
+        rules.add_file(&metadata.name, Path::new(&unit.file), &path);
+        rules.chmod(&metadata.name, &path, 0o644, false);
+    }
+    for package in &linux_conf.packages {
+        let name = package.name(metadata);
+        rules.add_files(&name, &package.files, &metadata.src_dir)?;
+        rules.add_entries(&name, &package.symlinks, &package.directories);
+    }
+    // Only listed conffiles are kept, same as deb packages built by pifu.
+    if !deb_conf.auto_conffiles {
+        for (index, name) in package_names(metadata, linux_conf).iter().enumerate() {
+            let conffiles = if index == 0 {
+                listed_conffiles(deb_conf)
+            } else {
+                Vec::new()
+            };
+            rules.set_conffiles(name, &conffiles);
+        }
+    }
+
+    generate_control(metadata, linux_conf, rules.requires_root(), &debian_dir)?;
+    generate_changelog(metadata, deb_conf.epoch, revision, &debian_dir)?;
+    generate_copyright(metadata, &debian_dir)?;
+    rules.write(linux_conf, &debian_dir)?;
+    generate_scripts(conf, deb_conf, &source_dir, &debian_dir)?;
+    fs::write(
+        debian_dir.join("source").join("format"),
+        format!("{SOURCE_FORMAT}\n"),
+    )?;
+
+    let orig_file = workdir.join(format!(
+        "{}_{}.orig.tar.xz",
+        metadata.name, metadata.version
+    ));
+    write_orig_tar(metadata, &orig_file)?;
+    let debian_file = workdir.join(format!("{}_{file_version}.debian.tar.xz", metadata.name));
+    write_tar_xz(&debian_dir, Path::new("debian"), |_path| true, &debian_file)?;
+
+    let dsc_file = workdir.join(format!("{}_{file_version}.dsc", metadata.name));
+    generate_dsc(
+        metadata,
+        linux_conf,
+        &version,
+        &[orig_file, debian_file],
+        &dsc_file,
+    )
+}
+
+/// Get names of binary packages, main package first.
+fn package_names(metadata: &Metadata, linux_conf: &LinuxConfig) -> Vec<String> {
+    let mut names = vec![metadata.name.clone()];
+    names.extend(
+        linux_conf
+            .packages
+            .iter()
+            .map(|package| package.name(metadata)),
+    );
+    names
+}
+
+const fn package_arch(linux_conf: &LinuxConfig) -> &'static str {
+    if linux_conf.arch_independent {
+        "all"
+    } else {
+        "any"
+    }
+}
+
+fn build_depends(deb_conf: &DebConfig) -> String {
+    deb_conf
+        .build_depends
+        .as_ref()
+        .filter(|relations| !relations.is_empty())
+        .map_or_else(
+            || DEBHELPER_COMPAT.to_owned(),
+            |relations| {
+                format!(
+                    "{DEBHELPER_COMPAT}, {}",
+                    relations.to_deb_with_arches(|arch| control::arch_name(arch).to_owned())
+                )
+            },
+        )
+}
+
+/// Write `debian/control`, with source stanza and a stanza for each binary package.
+fn generate_control(
+    metadata: &Metadata,
+    linux_conf: &LinuxConfig,
+    requires_root: bool,
+    debian_dir: &Path,
+) -> Result<(), Error> {
+    log::info!("generate_control() debian dir: {}", debian_dir.display());
+    let deb_conf = &linux_conf.deb;
+    let mut content = String::new();
+    writeln!(content, "Source: {}", metadata.name)?;
+    writeln!(
+        content,
+        "Section: {}",
+        deb_conf.section.as_deref().unwrap_or("misc")
+    )?;
+    writeln!(content, "Priority: {}", deb_conf.priority)?;
+    writeln!(content, "Maintainer: {}", metadata.author)?;
+    writeln!(content, "Build-Depends: {}", build_depends(deb_conf))?;
+    writeln!(content, "Standards-Version: {STANDARDS_VERSION}")?;
+    writeln!(content, "Homepage: {}", metadata.homepage)?;
+    let rules_requires_root = if requires_root {
+        "binary-targets"
+    } else {
+        "no"
+    };
+    writeln!(content, "Rules-Requires-Root: {rules_requires_root}")?;
+
+    let mut extra_depends = vec!["${shlibs:Depends}".to_owned(), "${misc:Depends}".to_owned()];
+    if !linux_conf.systemd_units.is_empty() {
+        // Provides `deb-systemd-helper` used in maintainer scripts.
+        extra_depends.push("init-system-helpers (>= 1.52)".to_owned());
+    }
+    write_binary_stanza(
+        &mut content,
+        metadata,
+        linux_conf,
+        &deb_conf.relations,
+        &extra_depends,
+    )?;
+    for package in &linux_conf.packages {
+        let mut extra_depends = vec!["${shlibs:Depends}".to_owned(), "${misc:Depends}".to_owned()];
+        extra_depends.extend(
+            package
+                .exact_depends(metadata)
+                .into_iter()
+                .map(|name| format!("{name} (= ${{binary:Version}})")),
+        );
+        write_binary_stanza(
+            &mut content,
+            &package.metadata(metadata),
+            linux_conf,
+            &package.deb,
+            &extra_depends,
+        )?;
+    }
+
+    fs::write(debian_dir.join("control"), content)?;
+    Ok(())
+}
+
+fn write_binary_stanza(
+    content: &mut String,
+    metadata: &Metadata,
+    linux_conf: &LinuxConfig,
+    relations: &DebRelations,
+    extra_depends: &[String],
+) -> Result<(), Error> {
+    writeln!(content, "\nPackage: {}", metadata.name)?;
+    writeln!(content, "Architecture: {}", package_arch(linux_conf))?;
+    for (field, relations) in relations.fields() {
+        let mut values = Vec::new();
+        if field == "Depends" {
+            // Substitution variables are filled by `dpkg-gencontrol`.
+            values.extend_from_slice(extra_depends);
+        }
+        if let Some(relations) = relations.filter(|relations| !relations.is_empty()) {
+            values.push(relations.to_deb_with_arches(|arch| control::arch_name(arch).to_owned()));
+        }
+        if !values.is_empty() {
+            writeln!(content, "{field}: {}", values.join(", "))?;
+        }
+    }
+    // Fields prefixed with `XB-` are copied to control file of binary package.
+    for (name, value) in &linux_conf.deb.custom_fields {
+        writeln!(content, "XB-{name}: {value}")?;
+    }
+    writeln!(content, "{}", control::format_description(metadata))?;
+    Ok(())
+}
+
+/// Write `debian/changelog` from releases of package.
+///
+/// An entry of current version is added if it is not the latest release,
+/// as version of source package is read from changelog.
+fn generate_changelog(
+    metadata: &Metadata,
+    epoch: Option<u32>,
+    revision: &str,
+    debian_dir: &Path,
+) -> Result<(), Error> {
+    let mut releases = changelog::read_releases(metadata)?;
+    if releases
+        .first()
+        .is_none_or(|release| release.version != metadata.version)
+    {
+        releases.insert(0, Release::current(metadata));
+    }
+    let content = changelog::to_debian(&releases, &metadata.name, &metadata.author, |version| {
+        control::full_version(version, epoch, Some(revision))
+    })?;
+    fs::write(debian_dir.join("changelog"), content)?;
+    Ok(())
+}
+
+/// Write `debian/copyright` in machine-readable format.
+///
+/// Text of `license_file` is included if it is set.
+fn generate_copyright(metadata: &Metadata, debian_dir: &Path) -> Result<(), Error> {
+    let mut content = String::new();
+    writeln!(
+        content,
+        "Format: https://www.debian.org/doc/packaging-manuals/copyright-format/1.0/"
+    )?;
+    writeln!(content, "Upstream-Name: {}", metadata.name)?;
+    writeln!(content, "Upstream-Contact: {}", metadata.author)?;
+    writeln!(content, "Source: {}", metadata.homepage)?;
+    writeln!(content, "\nFiles: *")?;
+    writeln!(
+        content,
+        "Copyright: {}",
+        metadata.copyright.as_deref().unwrap_or(&metadata.author)
+    )?;
+    writeln!(content, "License: {}", metadata.license)?;
+    if let Some(license_file) = metadata.license_file.as_ref() {
+        let text = fs::read_to_string(license_file).map_err(|err| {
+            Error::from_string(
+                ErrorKind::IoError,
+                format!("Failed to read license file {license_file}, err: {err}"),
+            )
+        })?;
+        for line in text.trim_end().lines() {
+            let line = line.trim_end();
+            if line.is_empty() {
+                writeln!(content, " .")?;
+            } else {
+                writeln!(content, " {line}")?;
+            }
+        }
+    }
+    fs::write(debian_dir.join("copyright"), content)?;
+    Ok(())
+}
+
+/// Write maintainer scripts and conffiles of main package, like `debian/<name>.postinst`.
+fn generate_scripts(
+    conf: &Config,
+    deb_conf: &DebConfig,
+    source_dir: &Path,
+    debian_dir: &Path,
+) -> Result<(), Error> {
+    let name = &conf.metadata.name;
+    let linux_conf = conf.linux.as_ref().expect("Linux conf is not set");
+    // Source package is built for any architecture, so `${arch}` can not be expanded
+    // unless package is architecture independent.
+    if !linux_conf.arch_independent {
+        let scripts = [
+            ("preinst", &deb_conf.preinst),
+            ("postinst", &deb_conf.postinst),
+            ("prerm", &deb_conf.prerm),
+            ("postrm", &deb_conf.postrm),
+            ("config", &deb_conf.config),
+        ];
+        for (script_name, script) in scripts {
+            if let Some(script) = script {
+                if script.content(conf)?.contains("${arch}") {
+                    return Err(Error::from_string(
+                        ErrorKind::InvalidConfError,
+                        format!(
+                            "`${{arch}}` in `{script_name}` script is not supported by deb source package, \
+                             as it is built for any architecture"
+                        ),
+                    ));
+                }
+            }
+        }
+    }
+    let scripts_dir = source_dir.join("scripts");
+    control::generate_scripts(conf, PackageArch::All, &scripts_dir)?;
+    for entry in fs::read_dir(&scripts_dir)? {
+        let entry = entry?;
+        let script_name = entry.file_name();
+        let dest_file = debian_dir.join(format!("{name}.{}", script_name.to_string_lossy()));
+        fs::rename(entry.path(), dest_file)?;
+    }
+
+    // Files in `/etc` are marked as conffiles by `dh_installdeb`,
+    // and conffiles are rewritten in `rules` if `auto_conffiles` is disabled.
+    if !deb_conf.auto_conffiles {
+        return Ok(());
+    }
+    let conffiles = listed_conffiles(deb_conf)
+        .into_iter()
+        .filter(|file| !file.starts_with("/etc/"))
+        .collect::<Vec<_>>();
+    if !conffiles.is_empty() {
+        let mut content = conffiles.join("\n");
+        content.push('\n');
+        fs::write(debian_dir.join(format!("{name}.conffiles")), content)?;
+    }
+    Ok(())
+}
+
+/// Get absolute paths of conffiles listed in config.
+fn listed_conffiles(deb_conf: &DebConfig) -> Vec<String> {
+    deb_conf
+        .conffiles
+        .iter()
+        .flatten()
+        .map(|file| format!("/{}", file.trim_start_matches('/')))
+        .collect()
+}
+
+/// Write `.orig.tar.xz` of `src_dir`, excluding `workdir` and version control folders.
+fn write_orig_tar(metadata: &Metadata, orig_file: &Path) -> Result<(), Error> {
+    let src_dir = fs::canonicalize(&metadata.src_dir)?;
+    let workdir = fs::canonicalize(&metadata.workdir)?;
+    let excluded_workdir = workdir.strip_prefix(&src_dir).ok().map(Path::to_path_buf);
+    let prefix = PathBuf::from(format!("{}-{}", metadata.name, metadata.version));
+    write_tar_xz(
+        &src_dir,
+        &prefix,
+        |path| {
+            let is_vcs_dir = path
+                .file_name()
+                .is_some_and(|name| VCS_DIRS.iter().any(|vcs| name == *vcs));
+            // `debian/` is replaced by `.debian.tar.xz`.
+            !is_vcs_dir
+                && path != Path::new("debian")
+                && excluded_workdir
+                    .as_ref()
+                    .is_none_or(|workdir| !path.starts_with(workdir))
+        },
+        orig_file,
+    )
+}
+
+fn write_tar_xz<F>(dir: &Path, prefix: &Path, filter: F, tar_file: &Path) -> Result<(), Error>
+where
+    F: FnMut(&Path) -> bool,
+{
+    log::info!("write_tar_xz() {}", tar_file.display());
+    let writer = BufWriter::new(File::create(tar_file)?);
+    let encoder = CompressFormat::Xz.encoder(writer, None)?;
+    let encoder = archive::create_tar_with_prefix(dir, prefix, filter, encoder)?;
+    encoder.finish()?.flush()?;
+    Ok(())
+}
+
+/// Write `.dsc` file, with checksums of `files`.
+fn generate_dsc(
+    metadata: &Metadata,
+    linux_conf: &LinuxConfig,
+    version: &str,
+    files: &[PathBuf],
+    dsc_file: &Path,
+) -> Result<(), Error> {
+    log::info!("generate_dsc() {}", dsc_file.display());
+    let deb_conf = &linux_conf.deb;
+    let names = package_names(metadata, linux_conf);
+    let arch = package_arch(linux_conf);
+    let section = deb_conf.section.as_deref().unwrap_or("misc");
+
+    let mut content = String::new();
+    writeln!(content, "Format: {SOURCE_FORMAT}")?;
+    writeln!(content, "Source: {}", metadata.name)?;
+    writeln!(content, "Binary: {}", names.join(", "))?;
+    writeln!(content, "Architecture: {arch}")?;
+    writeln!(content, "Version: {version}")?;
+    writeln!(content, "Maintainer: {}", metadata.author)?;
+    writeln!(content, "Homepage: {}", metadata.homepage)?;
+    writeln!(content, "Standards-Version: {STANDARDS_VERSION}")?;
+    writeln!(content, "Build-Depends: {}", build_depends(deb_conf))?;
+    writeln!(content, "Package-List:")?;
+    for name in &names {
+        writeln!(
+            content,
+            " {name} deb {section} {} arch={arch}",
+            deb_conf.priority
+        )?;
+    }
+
+    let mut sha256_lines = String::new();
+    let mut md5_lines = String::new();
+    for file in files {
+        let filename = file
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let size = fs::metadata(file)?.len();
+        writeln!(
+            sha256_lines,
+            " {} {size} {filename}",
+            hash::sha256sum(file)?
+        )?;
+        writeln!(md5_lines, " {} {size} {filename}", hash::md5sum(file)?)?;
+    }
+    write!(content, "Checksums-Sha256:\n{sha256_lines}")?;
+    write!(content, "Files:\n{md5_lines}")?;
+
+    fs::write(dsc_file, content)?;
+    Ok(())
+}
+
+/// Recipes of `debian/rules`.
+#[derive(Debug, Default)]
+struct Rules {
+    /// Commands to copy files into `debian/<package>`.
+    install: Vec<String>,
+
+    /// Commands to set mode and owner, run after `dh_fixperms`.
+    fixperms: Vec<String>,
+
+    /// Commands to rewrite conffiles, run after `dh_installdeb`.
+    installdeb: Vec<String>,
+
+    /// Whether `chown` is used.
+    has_owner: bool,
+}
+
+impl Rules {
+    /// Whether `rules` shall be run as root, to set owner of files.
+    const fn requires_root(&self) -> bool {
+        self.has_owner
+    }
+
+    /// Add commands to install files matched by `files` into `package`.
+    fn add_files(&mut self, package: &str, files: &[FileSet], src: &str) -> Result<(), Error> {
+        for file in files {
+            let src_pattern = format!("{src}/{}", &file.from);
+            let entries = glob::glob(&src_pattern).map_err(|err| {
+                Error::from_string(
+                    ErrorKind::GlobPatternError,
+                    format!("Failed to crate glob pattern for {src_pattern}, error: {err}"),
+                )
+            })?;
+            let mut entry_not_match = true;
+            for entry in entries {
+                entry_not_match = false;
+                let entry = entry?;
+                let source = entry.strip_prefix(src).unwrap_or(&entry);
+                let dest_path = Path::new(&file.to);
+                let is_dir = fs::symlink_metadata(&entry)?.is_dir();
+                // Folder is copied into `to` folder, like `FileSet::copy_to()`.
+                let dest_path = if is_dir {
+                    dest_path.join(source.file_name().unwrap_or_default())
+                } else {
+                    dest_path.to_path_buf()
+                };
+                self.add_file(package, source, &dest_path);
+                if let Some(mode) = file.mode {
+                    self.chmod(package, &dest_path, mode, is_dir);
+                }
+                if file.owner.is_some() || file.group.is_some() {
+                    if let Some(attr) = file.attr() {
+                        self.chown(package, &dest_path, &attr.owner, &attr.group);
+                    }
+                }
+            }
+            if entry_not_match {
+                return Err(Error::from_string(
+                    ErrorKind::GlobError,
+                    format!("No file is matched with pattern `{src_pattern}`"),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Add command to copy `source` to `dest_path` of `package`.
+    fn add_file(&mut self, package: &str, source: &Path, dest_path: &Path) {
+        let dest = package_path(package, dest_path);
+        if let Some(parent) = Path::new(&dest).parent() {
+            self.install_dir(&parent.to_string_lossy());
+        }
+        self.install.push(format!(
+            "cp -a {} {}",
+            quote(&source.to_string_lossy()),
+            quote(&dest)
+        ));
+    }
+
+    fn add_entries(&mut self, package: &str, symlinks: &[Symlink], directories: &[Directory]) {
+        for symlink in symlinks {
+            let dest = package_path(package, &symlink.package_path());
+            if let Some(parent) = Path::new(&dest).parent() {
+                self.install_dir(&parent.to_string_lossy());
+            }
+            self.install.push(format!(
+                "ln -sfn {} {}",
+                quote(&symlink.target),
+                quote(&dest)
+            ));
+        }
+        for directory in directories {
+            let path = directory.package_path();
+            self.install_dir(&package_path(package, &path));
+            if let Some(mode) = directory.mode {
+                self.chmod(package, &path, mode, false);
+            }
+            if directory.owner.is_some() || directory.group.is_some() {
+                let attr = directory.attr();
+                self.chown(package, &path, &attr.owner, &attr.group);
+            }
+        }
+    }
+
+    fn install_dir(&mut self, dir: &str) {
+        let command = format!("install -d {}", quote(dir));
+        if !self.install.contains(&command) {
+            self.install.push(command);
+        }
+    }
+
+    /// Set mode of `path`, or files in `path` if `recursive` is true.
+    fn chmod(&mut self, package: &str, path: &Path, mode: u32, recursive: bool) {
+        let dest = quote(&package_path(package, path));
+        if recursive {
+            self.fixperms
+                .push(format!("find {dest} -type f -exec chmod {mode:o} {{}} +"));
+        } else {
+            self.fixperms.push(format!("chmod {mode:o} {dest}"));
+        }
+    }
+
+    /// Replace conffiles of `package`, which are marked by `dh_installdeb`
+    /// for all files in `/etc`, with `conffiles`.
+    fn set_conffiles(&mut self, package: &str, conffiles: &[String]) {
+        let dest = quote(&package_path(package, Path::new("DEBIAN/conffiles")));
+        if conffiles.is_empty() {
+            self.installdeb.push(format!("rm -f {dest}"));
+        } else {
+            let files = conffiles
+                .iter()
+                .map(|file| quote(file))
+                .collect::<Vec<_>>()
+                .join(" ");
+            self.installdeb
+                .push(format!("printf '%s\\n' {files} > {dest}"));
+        }
+    }
+
+    fn chown(&mut self, package: &str, path: &Path, owner: &str, group: &str) {
+        self.has_owner = true;
+        self.fixperms.push(format!(
+            "chown -R {}:{} {}",
+            quote(owner),
+            quote(group),
+            quote(&package_path(package, path))
+        ));
+    }
+
+    /// Write `debian/rules` with mode `0755`.
+    fn write(&self, linux_conf: &LinuxConfig, debian_dir: &Path) -> Result<(), Error> {
+        let mut content = String::new();
+        writeln!(content, "#!/usr/bin/make -f\n")?;
+        writeln!(content, "%:\n\tdh $@\n")?;
+        writeln!(
+            content,
+            "# Files are prebuilt, nothing to configure, build or test."
+        )?;
+        writeln!(
+            content,
+            "override_dh_auto_configure override_dh_auto_build override_dh_auto_test override_dh_auto_clean:\n"
+        )?;
+        writeln!(content, "override_dh_auto_install:")?;
+        for command in &self.install {
+            writeln!(content, "\t{command}")?;
+        }
+        if !self.fixperms.is_empty() {
+            writeln!(content, "\noverride_dh_fixperms:\n\tdh_fixperms")?;
+            for command in &self.fixperms {
+                writeln!(content, "\t{command}")?;
+            }
+        }
+        if !self.installdeb.is_empty() {
+            writeln!(content, "\noverride_dh_installdeb:\n\tdh_installdeb")?;
+            for command in &self.installdeb {
+                writeln!(content, "\t{command}")?;
+            }
+        }
+        if !linux_conf.need_strip() {
+            writeln!(content, "\noverride_dh_strip override_dh_dwz:")?;
+        }
+        if !linux_conf.systemd_units.is_empty() {
+            writeln!(content, "\n# Units are managed by maintainer scripts.")?;
+            writeln!(content, "override_dh_installsystemd:")?;
+        }
+
+        let rules_file = debian_dir.join("rules");
+        fs::write(&rules_file, content)?;
+        #[cfg(not(target_os = "windows"))]
+        fs::set_permissions(&rules_file, fs::Permissions::from_mode(0o755))?;
+        Ok(())
+    }
+}
+
+/// Get path of `path` in staging folder of `package`, like `debian/hello/usr/bin/hello`.
+fn package_path(package: &str, path: &Path) -> String {
+    Path::new("debian")
+        .join(package)
+        .join(path)
+        .to_string_lossy()
+        .to_string()
+}
+
+/// Quote `s` as a single shell word in makefile recipe.
+fn quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', r"'\''").replace('$', "$$"))
+}
@@ -2,6 +2,7 @@
 // Use of this source is governed by General Public License that can be found
 // in the LICENSE file.
 
+use std::fmt;
 use std::io;
 use std::path;
 use std::string;
@@ -15,6 +16,15 @@ pub enum ErrorKind {
 
     Lz2EncodeError,
 
+    /// Invalid ELF file.
+    ElfError,
+
+    /// Failed to run `strip` or `objcopy` command.
+    StripError,
+
+    /// Local dpkg database is missing or invalid.
+    DpkgDatabaseError,
+
     WalkDirError,
 
     StripPrefixError,
@@ -23,6 +33,9 @@ pub enum ErrorKind {
 
     InvalidConfError,
 
+    /// Invalid package file, like deb or rpm.
+    InvalidPackageError,
+
     AppImageCompilerError,
 
     NsisCompilerError,
@@ -57,6 +70,8 @@ pub enum ErrorKind {
     CmdlineError,
 
     TimeError,
+
+    FormatError,
 }
 
 #[derive(Debug, Clone)]
@@ -90,6 +105,18 @@ impl Error {
     }
 }
 
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}: {}", self.kind, self.message)
+    }
+}
+
+impl From<std::fmt::Error> for Error {
+    fn from(err: std::fmt::Error) -> Self {
+        Self::from_string(ErrorKind::FormatError, format!("{err}"))
+    }
+}
+
 impl From<glob::GlobError> for Error {
     fn from(err: glob::GlobError) -> Self {
         Self::from_string(ErrorKind::GlobError, format!("{err}"))
@@ -102,6 +129,12 @@ impl From<glob::PatternError> for Error {
     }
 }
 
+impl From<goblin::error::Error> for Error {
+    fn from(err: goblin::error::Error) -> Self {
+        Self::from_string(ErrorKind::ElfError, format!("{err}"))
+    }
+}
+
 impl From<regex::Error> for Error {
     fn from(err: regex::Error) -> Self {
         Self::from_string(ErrorKind::RegexError, format!("{err}"))
@@ -0,0 +1,207 @@
+// Copyright (c) 2021 Xu Shaohua <shaohua@biofan.org>. All rights reserved.
+// Use of this source is governed by General Public License that can be found
+// in the LICENSE file.
+
+use goblin::elf::header::header64::SIZEOF_EHDR;
+use goblin::elf::header::machine_to_str;
+use goblin::elf::Elf;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+
+use super::{format_timestamp, Field, PackageFormat, PackageInfo};
+use crate::error::{Error, ErrorKind};
+
+const SQUASHFS_MAGIC: &[u8; 4] = b"hsqs";
+const SQUASHFS_SUPERBLOCK_SIZE: usize = 96;
+
+/// Read runtime and squashfs info of type 2 `AppImage` file.
+///
+/// The squashfs image is appended right after the ELF runtime,
+/// whose end is the end of section header table.
+///
+/// # Errors
+/// Returns error if `path` is not a valid `AppImage` file.
+pub fn read_app_image(path: &Path) -> Result<PackageInfo, Error> {
+    let mut fd = File::open(path)?;
+    let mut ehdr = [0_u8; SIZEOF_EHDR];
+    fd.read_exact(&mut ehdr)?;
+    let header = Elf::parse_header(&ehdr)?;
+    let appimage_type = ehdr[10];
+    if appimage_type != 2 {
+        return Err(Error::from_string(
+            ErrorKind::InvalidPackageError,
+            format!("Unsupported AppImage type {appimage_type}, only type 2 is supported"),
+        ));
+    }
+
+    let offset = header.e_shoff + u64::from(header.e_shentsize) * u64::from(header.e_shnum);
+    let mut fields = vec![
+        Field::new("AppImage Type", appimage_type.to_string()),
+        Field::new("Arch", machine_to_str(header.e_machine)),
+        Field::new("Squashfs Offset", offset.to_string()),
+    ];
+
+    // Read sections of runtime, like update info and signature.
+    fd.seek(SeekFrom::Start(0))?;
+    let mut runtime = Vec::new();
+    (&mut fd).take(offset).read_to_end(&mut runtime)?;
+    let elf = Elf::parse(&runtime)?;
+    for section in &elf.section_headers {
+        let name = elf.shdr_strtab.get_at(section.sh_name).unwrap_or_default();
+        let Some(content) = section.file_range().and_then(|range| runtime.get(range)) else {
+            continue;
+        };
+        let content = String::from_utf8_lossy(content);
+        let content = content.trim_end_matches('\0').trim();
+        match name {
+            ".upd_info" if !content.is_empty() => {
+                fields.push(Field::new("Update Info", content));
+            }
+            ".sha256_sig" => {
+                let signed = if content.is_empty() { "no" } else { "yes" };
+                fields.push(Field::new("Signed", signed));
+            }
+            _ => {}
+        }
+    }
+
+    let mut superblock = [0_u8; SQUASHFS_SUPERBLOCK_SIZE];
+    fd.seek(SeekFrom::Start(offset))?;
+    fd.read_exact(&mut superblock)?;
+    if &superblock[..4] != SQUASHFS_MAGIC {
+        return Err(Error::new(
+            ErrorKind::InvalidPackageError,
+            "No squashfs image found in AppImage",
+        ));
+    }
+    let le_u16 = |pos: usize| u16::from_le_bytes([superblock[pos], superblock[pos + 1]]);
+    let le_u32 = |pos: usize| {
+        u32::from_le_bytes([
+            superblock[pos],
+            superblock[pos + 1],
+            superblock[pos + 2],
+            superblock[pos + 3],
+        ])
+    };
+    let mut bytes_used = [0_u8; 8];
+    bytes_used.copy_from_slice(&superblock[40..48]);
+    let compression = match le_u16(20) {
+        1 => "gzip",
+        2 => "lzma",
+        3 => "lzo",
+        4 => "xz",
+        5 => "lz4",
+        6 => "zstd",
+        _ => "unknown",
+    };
+    fields.extend([
+        Field::new("Squashfs Version", format!("{}.{}", le_u16(28), le_u16(30))),
+        Field::new("Squashfs Compression", compression),
+        Field::new("Squashfs Block Size", le_u32(12).to_string()),
+        Field::new("Squashfs Inodes", le_u32(4).to_string()),
+        Field::new("Squashfs Size", u64::from_le_bytes(bytes_used).to_string()),
+        Field::new("Squashfs Date", format_timestamp(u64::from(le_u32(8)))?),
+    ]);
+
+    Ok(PackageInfo {
+        format: PackageFormat::AppImage,
+        fields,
+        files: Vec::new(),
+        scripts: Vec::new(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::base::utils;
+
+    const UPDATE_INFO: &str = "zsync|https://example.com/hello-x86_64.AppImage.zsync";
+
+    fn section_header(name: u32, sh_type: u32, offset: usize, size: usize) -> Vec<u8> {
+        let mut shdr = vec![0_u8; 64];
+        shdr[..4].copy_from_slice(&name.to_le_bytes());
+        shdr[4..8].copy_from_slice(&sh_type.to_le_bytes());
+        shdr[24..32].copy_from_slice(&(offset as u64).to_le_bytes());
+        shdr[32..40].copy_from_slice(&(size as u64).to_le_bytes());
+        shdr
+    }
+
+    /// Minimal `AppImage`: ELF64 runtime with `.shstrtab` and `.upd_info` sections,
+    /// followed by a squashfs superblock.
+    fn app_image_bytes(appimage_type: u8) -> Vec<u8> {
+        let strtab = b"\0.shstrtab\0.upd_info\0";
+        let strtab_offset = SIZEOF_EHDR;
+        let upd_info_offset = strtab_offset + strtab.len();
+        let shoff = upd_info_offset + UPDATE_INFO.len();
+
+        let mut image = vec![0_u8; SIZEOF_EHDR];
+        image[..4].copy_from_slice(b"\x7fELF");
+        image[4] = 2; // ELFCLASS64
+        image[5] = 1; // Little endian
+        image[6] = 1; // EV_CURRENT
+        image[8..11].copy_from_slice(&[b'A', b'I', appimage_type]);
+        image[16..18].copy_from_slice(&2_u16.to_le_bytes()); // ET_EXEC
+        image[18..20].copy_from_slice(&62_u16.to_le_bytes()); // EM_X86_64
+        image[20..24].copy_from_slice(&1_u32.to_le_bytes());
+        image[40..48].copy_from_slice(&(shoff as u64).to_le_bytes());
+        image[52..54].copy_from_slice(&(SIZEOF_EHDR as u16).to_le_bytes());
+        image[54..56].copy_from_slice(&56_u16.to_le_bytes());
+        image[58..60].copy_from_slice(&64_u16.to_le_bytes());
+        image[60..62].copy_from_slice(&3_u16.to_le_bytes());
+        image[62..64].copy_from_slice(&1_u16.to_le_bytes());
+
+        image.extend_from_slice(strtab);
+        image.extend_from_slice(UPDATE_INFO.as_bytes());
+        image.extend(section_header(0, 0, 0, 0));
+        image.extend(section_header(1, 3, strtab_offset, strtab.len()));
+        image.extend(section_header(11, 7, upd_info_offset, UPDATE_INFO.len()));
+
+        let mut superblock = [0_u8; SQUASHFS_SUPERBLOCK_SIZE];
+        superblock[..4].copy_from_slice(SQUASHFS_MAGIC);
+        superblock[4..8].copy_from_slice(&12_u32.to_le_bytes());
+        superblock[8..12].copy_from_slice(&86400_u32.to_le_bytes());
+        superblock[12..16].copy_from_slice(&131_072_u32.to_le_bytes());
+        superblock[20..22].copy_from_slice(&4_u16.to_le_bytes());
+        superblock[28..30].copy_from_slice(&4_u16.to_le_bytes());
+        superblock[40..48].copy_from_slice(&4096_u64.to_le_bytes());
+        image.extend_from_slice(&superblock);
+        image
+    }
+
+    #[test]
+    fn test_read_app_image() {
+        let dir = utils::test_dir("inspect-app-image");
+        let path = dir.join("hello-x86_64.AppImage");
+        fs::write(&path, app_image_bytes(2)).unwrap();
+
+        let info = read_app_image(&path).unwrap();
+        assert_eq!(info.format, PackageFormat::AppImage);
+        let fields = info
+            .fields
+            .iter()
+            .map(|field| (field.name.as_str(), field.value.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            fields,
+            [
+                ("AppImage Type", "2"),
+                ("Arch", "X86_64"),
+                ("Squashfs Offset", "330"),
+                ("Update Info", UPDATE_INFO),
+                ("Squashfs Version", "4.0"),
+                ("Squashfs Compression", "xz"),
+                ("Squashfs Block Size", "131072"),
+                ("Squashfs Inodes", "12"),
+                ("Squashfs Size", "4096"),
+                ("Squashfs Date", "Fri, 02 Jan 1970 00:00:00 UTC"),
+            ]
+        );
+
+        fs::write(&path, app_image_bytes(1)).unwrap();
+        assert!(read_app_image(&path).is_err());
+    }
+}
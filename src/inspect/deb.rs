@@ -0,0 +1,167 @@
+// Copyright (c) 2021 Xu Shaohua <shaohua@biofan.org>. All rights reserved.
+// Use of this source is governed by General Public License that can be found
+// in the LICENSE file.
+
+use std::path::Path;
+use tar::EntryType;
+
+use super::{Field, FileEntry, PackageFormat, PackageInfo};
+use crate::deb::reader;
+use crate::error::Error;
+
+const SCRIPT_NAMES: &[&str] = &["preinst", "postinst", "prerm", "postrm", "config"];
+
+/// Read control fields, data files and maintainer scripts of deb file.
+///
+/// # Errors
+/// Returns error if `path` is not a valid deb package.
+pub fn read_deb(path: &Path) -> Result<PackageInfo, Error> {
+    let control_files = reader::read_control_files(path)?;
+    let control = control_files
+        .get("control")
+        .map(|content| String::from_utf8_lossy(content))
+        .unwrap_or_default();
+    let mut fields: Vec<Field> = reader::control_fields(&control)
+        .into_iter()
+        .map(|(name, value)| Field::new(name, value))
+        .collect();
+    if let Some(conffiles) = control_files.get("conffiles") {
+        fields.push(Field::new(
+            "Conffiles",
+            String::from_utf8_lossy(conffiles).trim_end(),
+        ));
+    }
+
+    let scripts = SCRIPT_NAMES
+        .iter()
+        .filter_map(|name| {
+            control_files
+                .get(*name)
+                .map(|content| Field::new(*name, String::from_utf8_lossy(content)))
+        })
+        .collect();
+
+    let files = reader::with_tar_member(path, "data.tar", |archive| {
+        let mut files = Vec::new();
+        for entry in archive.entries()? {
+            let entry = entry?;
+            let header = entry.header();
+            let file_type = match header.entry_type() {
+                EntryType::Directory => 0o040_000,
+                EntryType::Symlink => 0o120_000,
+                EntryType::Char => 0o020_000,
+                EntryType::Block => 0o060_000,
+                EntryType::Fifo => 0o010_000,
+                _ => 0o100_000,
+            };
+            let link_target = entry
+                .link_name()?
+                .map(|target| target.to_string_lossy().to_string());
+            files.push(FileEntry {
+                path: entry.path()?.to_string_lossy().to_string(),
+                mode: file_type | (header.mode()? & 0o7777),
+                owner: header.username().ok().flatten().unwrap_or("?").to_owned(),
+                group: header.groupname().ok().flatten().unwrap_or("?").to_owned(),
+                size: header.size()?,
+                link_target,
+            });
+        }
+        Ok(files)
+    })?;
+
+    Ok(PackageInfo {
+        format: PackageFormat::Deb,
+        fields,
+        files,
+        scripts,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::base::fileset::FileAttr;
+    use crate::base::payload::Payload;
+    use crate::base::utils;
+    use crate::deb::build::{create_deb, write_data_tar};
+    use crate::deb::DebConfig;
+
+    const CONTROL: &str = "Package: hello\nVersion: 1.0.0\nArchitecture: amd64\n\
+                           Maintainer: Hello <hello@example.com>\nDescription: Hello app\n";
+    const POSTINST: &str = "#!/bin/sh\nset -e\necho configured\n";
+
+    #[test]
+    fn test_read_deb() {
+        let dir = utils::test_dir("inspect-deb");
+        let control_dir = dir.join("control");
+        fs::create_dir_all(&control_dir).unwrap();
+        fs::write(control_dir.join("control"), CONTROL).unwrap();
+        fs::write(control_dir.join("postinst"), POSTINST).unwrap();
+        fs::write(control_dir.join("conffiles"), "/etc/hello.conf\n").unwrap();
+        let source = dir.join("hello");
+        fs::write(&source, "hello").unwrap();
+
+        let mut payload = Payload::default();
+        payload.add_file(
+            PathBuf::from("usr/bin/hello"),
+            source,
+            FileAttr {
+                mode: Some(0o4755),
+                owner: "root".to_owned(),
+                group: "hello".to_owned(),
+            },
+        );
+        payload.add_symlink(
+            PathBuf::from("usr/bin/hi"),
+            PathBuf::from("hello"),
+            FileAttr::default(),
+        );
+        let deb_conf = DebConfig::default();
+        let (data_file, _summary) = write_data_tar(&deb_conf, &payload, &dir).unwrap();
+        let deb_file = dir.join("hello_1.0.0_amd64.deb");
+        create_deb(&deb_conf, &control_dir, &data_file, &deb_file).unwrap();
+
+        let info = read_deb(&deb_file).unwrap();
+        assert_eq!(info.format, PackageFormat::Deb);
+        let fields = info
+            .fields
+            .iter()
+            .map(|field| (field.name.as_str(), field.value.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            fields,
+            [
+                ("Package", "hello"),
+                ("Version", "1.0.0"),
+                ("Architecture", "amd64"),
+                ("Maintainer", "Hello <hello@example.com>"),
+                ("Description", "Hello app"),
+                ("Conffiles", "/etc/hello.conf"),
+            ]
+        );
+        assert_eq!(info.scripts.len(), 1);
+        assert_eq!(info.scripts[0].name, "postinst");
+        assert_eq!(info.scripts[0].value, POSTINST);
+
+        let file = |path: &str| {
+            info.files
+                .iter()
+                .find(|file| file.path.trim_start_matches('.') == path)
+                .unwrap()
+        };
+        let hello = file("/usr/bin/hello");
+        assert_eq!(hello.mode, 0o104_755);
+        assert_eq!(hello.owner, "root");
+        assert_eq!(hello.group, "hello");
+        assert_eq!(hello.size, 5);
+        assert_eq!(hello.link_target, None);
+        let link = file("/usr/bin/hi");
+        assert_eq!(link.mode, 0o120_777);
+        assert_eq!(link.size, 0);
+        assert_eq!(link.link_target.as_deref(), Some("hello"));
+        assert_eq!(file("/usr/bin/").mode, 0o040_755);
+    }
+}
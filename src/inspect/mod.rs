@@ -0,0 +1,224 @@
+// Copyright (c) 2021 Xu Shaohua <shaohua@biofan.org>. All rights reserved.
+// Use of this source is governed by General Public License that can be found
+// in the LICENSE file.
+
+//! Read back metadata, file list and scripts of packages,
+//! without dpkg or rpm tools installed.
+
+mod app_image;
+mod deb;
+pub mod rpm;
+
+use serde::Serialize;
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use time::{format_description, OffsetDateTime};
+
+use crate::error::{Error, ErrorKind};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PackageFormat {
+    Deb,
+    Rpm,
+    AppImage,
+}
+
+/// Named value, like a control field or a maintainer script.
+#[derive(Debug, Clone, Serialize)]
+pub struct Field {
+    pub name: String,
+    pub value: String,
+}
+
+impl Field {
+    pub fn new<N: Into<String>, V: Into<String>>(name: N, value: V) -> Self {
+        Self {
+            name: name.into(),
+            value: value.into(),
+        }
+    }
+}
+
+/// File entry in package payload.
+#[derive(Debug, Clone, Serialize)]
+pub struct FileEntry {
+    pub path: String,
+
+    /// File type and permission bits, like `st_mode`.
+    pub mode: u32,
+
+    pub owner: String,
+
+    pub group: String,
+
+    pub size: u64,
+
+    /// Target of symbolic link.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub link_target: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PackageInfo {
+    pub format: PackageFormat,
+
+    /// Metadata fields, in order of the package.
+    pub fields: Vec<Field>,
+
+    pub files: Vec<FileEntry>,
+
+    pub scripts: Vec<Field>,
+}
+
+/// Print metadata, file list and scripts of package file.
+///
+/// # Errors
+/// Returns error if `path` is not a deb, rpm or `AppImage` file.
+pub fn inspect(path: &Path, json: bool) -> Result<(), Error> {
+    log::info!("inspect() path: {}", path.display());
+    let info = match detect_format(path)? {
+        PackageFormat::Deb => deb::read_deb(path)?,
+        PackageFormat::Rpm => rpm::read_rpm(path)?,
+        PackageFormat::AppImage => app_image::read_app_image(path)?,
+    };
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&info)?);
+    } else {
+        print!("{}", format_info(&info)?);
+    }
+    Ok(())
+}
+
+fn detect_format(path: &Path) -> Result<PackageFormat, Error> {
+    let mut magic = [0_u8; 12];
+    File::open(path)?.read_exact(&mut magic).map_err(|err| {
+        Error::from_string(
+            ErrorKind::InvalidPackageError,
+            format!("Failed to read {}, err: {err}", path.display()),
+        )
+    })?;
+    if magic.starts_with(b"!<arch>\n") {
+        Ok(PackageFormat::Deb)
+    } else if magic.starts_with(&crate::rpm::header::LEAD_MAGIC) {
+        Ok(PackageFormat::Rpm)
+    } else if magic.starts_with(b"\x7fELF") && &magic[8..10] == b"AI" {
+        Ok(PackageFormat::AppImage)
+    } else {
+        Err(Error::from_string(
+            ErrorKind::InvalidPackageError,
+            format!("Unknown package format: {}", path.display()),
+        ))
+    }
+}
+
+/// Format package info in human readable text.
+fn format_info(info: &PackageInfo) -> Result<String, Error> {
+    let mut out = String::new();
+    let format = match info.format {
+        PackageFormat::Deb => "deb",
+        PackageFormat::Rpm => "rpm",
+        PackageFormat::AppImage => "AppImage",
+    };
+    writeln!(out, "Format: {format}")?;
+    for field in &info.fields {
+        let mut lines = field.value.lines();
+        writeln!(out, "{}: {}", field.name, lines.next().unwrap_or_default())?;
+        for line in lines {
+            writeln!(out, "  {line}")?;
+        }
+    }
+
+    if !info.files.is_empty() {
+        writeln!(out, "\nFiles:")?;
+        let width = info
+            .files
+            .iter()
+            .map(|file| file.owner.len() + file.group.len() + 1)
+            .max()
+            .unwrap_or_default();
+        for file in &info.files {
+            let owner = format!("{}/{}", file.owner, file.group);
+            write!(
+                out,
+                "{} {owner:<width$} {:>10} {}",
+                mode_string(file.mode),
+                file.size,
+                file.path
+            )?;
+            if let Some(target) = &file.link_target {
+                write!(out, " -> {target}")?;
+            }
+            out.push('\n');
+        }
+    }
+
+    for script in &info.scripts {
+        writeln!(out, "\n{}:", script.name)?;
+        out.push_str(&script.value);
+        if !script.value.ends_with('\n') {
+            out.push('\n');
+        }
+    }
+    Ok(out)
+}
+
+/// Convert file mode to string, like `drwxr-xr-x` in output of `ls -l`.
+fn mode_string(mode: u32) -> String {
+    let file_type = match mode & 0o170_000 {
+        0o040_000 => 'd',
+        0o120_000 => 'l',
+        0o020_000 => 'c',
+        0o060_000 => 'b',
+        0o010_000 => 'p',
+        0o140_000 => 's',
+        _ => '-',
+    };
+    let mut out = String::from(file_type);
+    for (shift, special, special_char) in [(6, 0o4000, 's'), (3, 0o2000, 's'), (0, 0o1000, 't')] {
+        let bits = (mode >> shift) & 0o7;
+        out.push(if bits & 0o4 == 0 { '-' } else { 'r' });
+        out.push(if bits & 0o2 == 0 { '-' } else { 'w' });
+        let exec = bits & 0o1 != 0;
+        out.push(match (mode & special != 0, exec) {
+            (true, true) => special_char,
+            (true, false) => special_char.to_ascii_uppercase(),
+            (false, true) => 'x',
+            (false, false) => '-',
+        });
+    }
+    out
+}
+
+/// Format unix timestamp as date time in UTC.
+fn format_timestamp(timestamp: u64) -> Result<String, Error> {
+    let timestamp = i64::try_from(timestamp).unwrap_or_default();
+    let date = OffsetDateTime::from_unix_timestamp(timestamp).map_err(|err| {
+        Error::from_string(
+            ErrorKind::TimeError,
+            format!("Invalid timestamp, err: {err}"),
+        )
+    })?;
+    let date_format = format_description::parse(
+        "[weekday repr:short], [day] [month repr:short] [year] [hour]:[minute]:[second] UTC",
+    )?;
+    Ok(date.format(&date_format)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::mode_string;
+
+    #[test]
+    fn test_mode_string() {
+        assert_eq!(mode_string(0o040_755), "drwxr-xr-x");
+        assert_eq!(mode_string(0o100_644), "-rw-r--r--");
+        assert_eq!(mode_string(0o120_777), "lrwxrwxrwx");
+        assert_eq!(mode_string(0o104_755), "-rwsr-xr-x");
+        assert_eq!(mode_string(0o102_644), "-rw-r-Sr--");
+        assert_eq!(mode_string(0o041_777), "drwxrwxrwt");
+    }
+}
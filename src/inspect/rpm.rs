@@ -0,0 +1,137 @@
+// Copyright (c) 2021 Xu Shaohua <shaohua@biofan.org>. All rights reserved.
+// Use of this source is governed by General Public License that can be found
+// in the LICENSE file.
+
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+use super::{format_timestamp, Field, FileEntry, PackageFormat, PackageInfo};
+use crate::error::Error;
+use crate::rpm::header::{
+    read_headers, Header, RPMTAG_ARCH, RPMTAG_BUILDHOST, RPMTAG_BUILDTIME, RPMTAG_DESCRIPTION,
+    RPMTAG_EPOCH, RPMTAG_FILEGROUPNAME, RPMTAG_FILELINKTOS, RPMTAG_FILEMODES, RPMTAG_FILESIZES,
+    RPMTAG_FILEUSERNAME, RPMTAG_GROUP, RPMTAG_LICENSE, RPMTAG_LONGFILESIZES, RPMTAG_NAME,
+    RPMTAG_OS, RPMTAG_PACKAGER, RPMTAG_PAYLOADCOMPRESSOR, RPMTAG_PAYLOADFORMAT, RPMTAG_POSTIN,
+    RPMTAG_POSTINPROG, RPMTAG_POSTTRANS, RPMTAG_POSTTRANSPROG, RPMTAG_POSTUN, RPMTAG_POSTUNPROG,
+    RPMTAG_PREIN, RPMTAG_PREINPROG, RPMTAG_PRETRANS, RPMTAG_PRETRANSPROG, RPMTAG_PREUN,
+    RPMTAG_PREUNPROG, RPMTAG_PROVIDENAME, RPMTAG_RELEASE, RPMTAG_REQUIRENAME, RPMTAG_SIZE,
+    RPMTAG_SOURCERPM, RPMTAG_SUMMARY, RPMTAG_URL, RPMTAG_VENDOR, RPMTAG_VERSION,
+};
+
+/// Read header tags, file list and scriptlets of rpm file.
+///
+/// # Errors
+/// Returns error if `path` is not a valid rpm package.
+pub fn read_rpm(path: &Path) -> Result<PackageInfo, Error> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let (_signature, header) = read_headers(&mut reader)?;
+
+    let mut fields = Vec::new();
+    let string_tags = [
+        ("Name", RPMTAG_NAME),
+        ("Version", RPMTAG_VERSION),
+        ("Release", RPMTAG_RELEASE),
+        ("Arch", RPMTAG_ARCH),
+        ("OS", RPMTAG_OS),
+        ("Summary", RPMTAG_SUMMARY),
+        ("Description", RPMTAG_DESCRIPTION),
+        ("License", RPMTAG_LICENSE),
+        ("URL", RPMTAG_URL),
+        ("Vendor", RPMTAG_VENDOR),
+        ("Group", RPMTAG_GROUP),
+        ("Packager", RPMTAG_PACKAGER),
+        ("Build Host", RPMTAG_BUILDHOST),
+        ("Source RPM", RPMTAG_SOURCERPM),
+    ];
+    for (name, tag) in string_tags {
+        if let Some(value) = header.get_string(tag) {
+            fields.push(Field::new(name, value));
+        }
+    }
+    if let Some(epoch) = header.get_int(RPMTAG_EPOCH) {
+        fields.insert(1, Field::new("Epoch", epoch.to_string()));
+    }
+    if let Some(build_time) = header.get_int(RPMTAG_BUILDTIME) {
+        fields.push(Field::new("Build Date", format_timestamp(build_time)?));
+    }
+    if let Some(size) = header.get_int(RPMTAG_SIZE) {
+        fields.push(Field::new("Size", size.to_string()));
+    }
+    for (name, tag) in [
+        ("Requires", RPMTAG_REQUIRENAME),
+        ("Provides", RPMTAG_PROVIDENAME),
+    ] {
+        if let Some(values) = header.get_strings(tag) {
+            fields.push(Field::new(name, values.join(", ")));
+        }
+    }
+    if let Some(format) = header.get_string(RPMTAG_PAYLOADFORMAT) {
+        let compressor = header
+            .get_string(RPMTAG_PAYLOADCOMPRESSOR)
+            .unwrap_or("gzip");
+        fields.push(Field::new("Payload", format!("{format}, {compressor}")));
+    }
+
+    Ok(PackageInfo {
+        format: PackageFormat::Rpm,
+        fields,
+        files: read_files(&header),
+        scripts: read_scripts(&header),
+    })
+}
+
+fn read_files(header: &Header) -> Vec<FileEntry> {
+    let paths = header.file_paths();
+    let modes = header.get_ints(RPMTAG_FILEMODES).unwrap_or_default();
+    let sizes = header
+        .get_ints(RPMTAG_LONGFILESIZES)
+        .or_else(|| header.get_ints(RPMTAG_FILESIZES))
+        .unwrap_or_default();
+    let owners = header.get_strings(RPMTAG_FILEUSERNAME).unwrap_or_default();
+    let groups = header.get_strings(RPMTAG_FILEGROUPNAME).unwrap_or_default();
+    let link_targets = header.get_strings(RPMTAG_FILELINKTOS).unwrap_or_default();
+
+    paths
+        .into_iter()
+        .enumerate()
+        .map(|(index, path)| FileEntry {
+            path,
+            mode: modes
+                .get(index)
+                .and_then(|&mode| u32::try_from(mode).ok())
+                .unwrap_or_default(),
+            owner: owners.get(index).cloned().unwrap_or_default(),
+            group: groups.get(index).cloned().unwrap_or_default(),
+            size: sizes.get(index).copied().unwrap_or_default(),
+            link_target: link_targets
+                .get(index)
+                .filter(|target| !target.is_empty())
+                .cloned(),
+        })
+        .collect()
+}
+
+fn read_scripts(header: &Header) -> Vec<Field> {
+    let script_tags = [
+        ("pretrans", RPMTAG_PRETRANS, RPMTAG_PRETRANSPROG),
+        ("pre", RPMTAG_PREIN, RPMTAG_PREINPROG),
+        ("post", RPMTAG_POSTIN, RPMTAG_POSTINPROG),
+        ("preun", RPMTAG_PREUN, RPMTAG_PREUNPROG),
+        ("postun", RPMTAG_POSTUN, RPMTAG_POSTUNPROG),
+        ("posttrans", RPMTAG_POSTTRANS, RPMTAG_POSTTRANSPROG),
+    ];
+    let mut scripts = Vec::new();
+    for (name, tag, prog_tag) in script_tags {
+        let content = header.get_string(tag).unwrap_or_default();
+        let prog = header
+            .get_string(prog_tag)
+            .filter(|prog| *prog != "/bin/sh");
+        if content.is_empty() && prog.is_none() {
+            continue;
+        }
+        let value = prog.map_or_else(|| content.to_owned(), |prog| format!("#!{prog}\n{content}"));
+        scripts.push(Field::new(name, value));
+    }
+    scripts
+}
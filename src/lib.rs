@@ -54,6 +54,7 @@ mod config;
 mod deb;
 mod download;
 mod error;
+mod inspect;
 mod nsis;
 mod rpm;
 
@@ -53,8 +53,12 @@ fn define_header(
         writeln!(nsis_fd, "Unicode False")?;
     }
 
-    let artifact_name =
-        expand_file_macro(&nsis_conf.artifact_name, conf, arch, PlatformTarget::Nsis)?;
+    let artifact_name = expand_file_macro(
+        &nsis_conf.artifact_name,
+        conf,
+        arch.into(),
+        PlatformTarget::Nsis,
+    )?;
     writeln!(nsis_fd, r#"OutFile "{artifact_name}""#)?;
     writeln!(
         nsis_fd,
@@ -126,19 +130,12 @@ fn define_pages(
         writeln!(nsis_fd, "SilentInstall silent")?;
     } else {
         if nsis_conf.per_machine {
-            if arch == Arch::X86_64 {
-                writeln!(
-                    nsis_fd,
-                    r#"InstallDir "$PROGRAMFILES64\{}""#,
-                    &conf.metadata.name
-                )?;
-            } else {
-                writeln!(
-                    nsis_fd,
-                    r#"InstallDir $PROGRAMFILES\{}"#,
-                    &conf.metadata.name
-                )?;
-            }
+            writeln!(
+                nsis_fd,
+                r#"InstallDir "{}\{}""#,
+                program_files_dir(arch),
+                &conf.metadata.name
+            )?;
             writeln!(nsis_fd, "RequestExecutionlevel Admin")?;
         } else {
             writeln!(
@@ -212,7 +209,7 @@ fn define_languages(conf: &Config, nsis_fd: &mut File) -> Result<(), Error> {
     writeln!(
         nsis_fd,
         r#"VIAddVersionKey /LANG=${{LANG_ENGLISH}} "FileDescription" "{}""#,
-        &conf.metadata.description
+        conf.metadata.summary().replace('"', r#"$\""#)
     )?;
     if let Some(ref company) = conf.metadata.company {
         writeln!(
@@ -418,3 +415,17 @@ where
         ))
     }
 }
+
+/// Get install folder of `arch` for all users.
+const fn program_files_dir(arch: Arch) -> &'static str {
+    match arch {
+        Arch::X86 | Arch::Armv7 => "$PROGRAMFILES",
+        Arch::X86_64
+        | Arch::AArch64
+        | Arch::Mips64
+        | Arch::Riscv64
+        | Arch::Ppc64le
+        | Arch::S390x
+        | Arch::LoongArch64 => "$PROGRAMFILES64",
+    }
+}
@@ -2,21 +2,32 @@
 // Use of this source is governed by General Public License that can be found
 // in the LICENSE file.
 
+use std::collections::{BTreeMap, BTreeSet};
 use std::fs::{self, File};
-use std::io::{self, Write};
-use std::path::Path;
+use std::io::{self, BufReader, Write};
+use std::path::{Path, PathBuf};
 use std::process::Command;
+use walkdir::WalkDir;
 
-use super::config::RpmConfig;
 use crate::base::archive;
+use crate::base::changelog::{self, Release};
 use crate::base::compress;
-use crate::base::fileset::copy_filesets;
+use crate::base::fileset::{
+    copy_filesets, install_directories, install_symlinks, Directory, FileAttrs, Symlink,
+};
+use crate::base::payload::{EntryKind, PayloadEntry};
+use crate::base::strip::{self, DebugInfo};
+use crate::base::systemd;
 use crate::base::utils;
-use crate::base::Arch;
+use crate::base::{expand_file_macro, Arch, Metadata, PackageArch, PlatformTarget};
 use crate::config::{Config, LinuxConfig};
 use crate::error::{Error, ErrorKind};
+use crate::rpm::header::{self, RPMTAG_NAME, RPMTAG_RELEASE};
+use crate::rpm::scriptlet::{self, Scriptlets, DEFAULT_INTERPRETER};
+use crate::rpm::writer::{self, FileDirective, RpmDependency, RpmPackage};
+use crate::rpm::{RpmBackend, RpmConfig, RpmRelations};
 
-pub fn build_rpm(conf: &Config, linux_conf: &LinuxConfig, _arch: Arch) -> Result<(), Error> {
+pub fn build_rpm(conf: &Config, linux_conf: &LinuxConfig, arch: PackageArch) -> Result<(), Error> {
     let rpm_conf = &linux_conf.rpm;
 
     let workdir = Path::new(&conf.metadata.workdir);
@@ -24,24 +35,12 @@ pub fn build_rpm(conf: &Config, linux_conf: &LinuxConfig, _arch: Arch) -> Result
     let _ = utils::rmdir(&rpm_dir);
 
     fs::create_dir_all(&rpm_dir)?;
-    let spec_file = rpm_dir.join(format!("{}.spec", &conf.metadata.name));
-    let mut spec_fd = File::create(&spec_file).map_err(|err| {
-        Error::from_string(
-            ErrorKind::IoError,
-            format!(
-                "Failed to create rpm spec file {:?}, error: {:?}",
-                &spec_file, err
-            ),
-        )
-    })?;
     let source_dir = rpm_dir.join(format!(
         "{}-{}",
         &conf.metadata.name, &conf.metadata.version
     ));
     fs::create_dir_all(&source_dir)?;
 
-    generate_spec_file(conf, rpm_conf, &mut spec_fd)?;
-
     // Copy files.
     let files = if let Some(files) = rpm_conf.files.as_ref() {
         files
@@ -53,13 +52,342 @@ pub fn build_rpm(conf: &Config, linux_conf: &LinuxConfig, _arch: Arch) -> Result
             "`files` property not set for rpm format",
         ));
     };
-    copy_filesets(files, &conf.metadata.src_dir, &source_dir)?;
+    let (_paths, mut attrs) = copy_filesets(files, &conf.metadata.src_dir, &source_dir)?;
+    install_symlinks(&linux_conf.symlinks, &source_dir)?;
+    attrs.extend(install_directories(&linux_conf.directories, &source_dir)?);
+    systemd::install_units(
+        &linux_conf.systemd_units,
+        &conf.metadata.src_dir,
+        &source_dir,
+    )?;
+    install_license_file(&conf.metadata, &source_dir)?;
+    let releases = changelog::read_releases(&conf.metadata)?;
+    changelog::update_metainfo_files(&source_dir, &releases)?;
+
+    // File lists of sub-packages are taken from copied paths,
+    // and these files are excluded from main package.
+    let mut package_files = Vec::new();
+    for package in &linux_conf.packages {
+        let (paths, package_attrs) =
+            copy_filesets(&package.files, &conf.metadata.src_dir, &source_dir)?;
+        attrs.extend(package_attrs);
+        install_symlinks(&package.symlinks, &source_dir)?;
+        attrs.extend(install_directories(&package.directories, &source_dir)?);
+
+        // Copied directories are not listed, same as `list_files()`.
+        let mut files = Vec::new();
+        for path in paths {
+            if !fs::symlink_metadata(source_dir.join(&path))?.is_dir() {
+                files.push(path);
+            }
+        }
+        files.extend(package.symlinks.iter().map(Symlink::package_path));
+        files.sort();
+        files.dedup();
+        files.extend(package.directories.iter().map(Directory::package_path));
+        package_files.push(files);
+    }
+
+    // Debug files are saved to `/usr/lib/debug` in the same tree,
+    // and packaged into `debuginfo` sub-package.
+    let debug_infos = if linux_conf.need_strip() {
+        let debug_dir = linux_conf.split_debug.then_some(source_dir.as_path());
+        strip::strip_elf_files(&source_dir, arch, debug_dir)?
+    } else {
+        Vec::new()
+    };
+
+    let mut staged_files = collect_staged_files(linux_conf, &source_dir, package_files, attrs)?;
+    staged_files.debuginfo_depends =
+        debuginfo_depends(conf, linux_conf, &staged_files, &debug_infos);
+
+    match rpm_conf.backend {
+        RpmBackend::Native => write_rpm_files(
+            conf,
+            linux_conf,
+            arch,
+            &source_dir,
+            &staged_files,
+            &releases,
+        ),
+        RpmBackend::Rpmbuild => run_rpmbuild(
+            conf,
+            linux_conf,
+            arch,
+            &rpm_dir,
+            &source_dir,
+            &staged_files,
+            &releases,
+        ),
+    }
+}
+
+/// Get names of packages which contain ELF files of `debug_infos`,
+/// main package first.
+fn debuginfo_depends(
+    conf: &Config,
+    linux_conf: &LinuxConfig,
+    staged_files: &StagedFiles,
+    debug_infos: &[DebugInfo],
+) -> Vec<String> {
+    let has_debug_info =
+        |files: &[PathBuf]| debug_infos.iter().any(|info| files.contains(&info.path));
+    let main = has_debug_info(&staged_files.main).then(|| conf.metadata.name.clone());
+    let packages = linux_conf
+        .packages
+        .iter()
+        .zip(&staged_files.packages)
+        .filter(|(_package, files)| has_debug_info(files))
+        .map(|(package, _files)| package.name(&conf.metadata));
+    main.into_iter().chain(packages).collect()
+}
+
+/// Collect file lists of main package, sub-packages and debuginfo package
+/// from staged tree, with directories owned by each package.
+fn collect_staged_files(
+    linux_conf: &LinuxConfig,
+    source_dir: &Path,
+    package_files: Vec<Vec<PathBuf>>,
+    attrs: FileAttrs,
+) -> Result<StagedFiles, Error> {
+    let all_files = list_files(source_dir)?;
+    let main_files = all_files
+        .iter()
+        .filter(|path| {
+            !path.starts_with(strip::DEBUG_DIR)
+                && !package_files.iter().flatten().any(|file| file == *path)
+        })
+        .cloned()
+        .chain(linux_conf.directories.iter().map(Directory::package_path))
+        .collect();
+    let debug_files = all_files
+        .into_iter()
+        .filter(|path| path.starts_with(strip::DEBUG_DIR))
+        .collect();
+    let mut directories: BTreeSet<PathBuf> = linux_conf
+        .directories
+        .iter()
+        .chain(
+            linux_conf
+                .packages
+                .iter()
+                .flat_map(|package| &package.directories),
+        )
+        .map(Directory::package_path)
+        .collect();
+
+    // Parent directories created by each package are owned by it.
+    let mut staged_files = StagedFiles {
+        main: main_files,
+        packages: package_files,
+        debuginfo: debug_files,
+        debuginfo_depends: Vec::new(),
+        directories: BTreeSet::new(),
+        directives: BTreeMap::new(),
+        attrs,
+    };
+    for files in staged_files.file_lists_mut() {
+        let owned = owned_directories(files);
+        directories.extend(owned.iter().cloned());
+        files.extend(owned);
+    }
+    staged_files.directives = staged_files
+        .file_lists()
+        .flatten()
+        .filter(|path| !directories.contains(*path))
+        .filter_map(|path| file_directive(path).map(|directive| (path.clone(), directive)))
+        .collect();
+    staged_files.directories = directories;
+    Ok(staged_files)
+}
+
+/// Write rpm files of main package, sub-packages and debuginfo package to `workdir`,
+/// with native rpm writer.
+fn write_rpm_files(
+    conf: &Config,
+    linux_conf: &LinuxConfig,
+    arch: PackageArch,
+    source_dir: &Path,
+    staged_files: &StagedFiles,
+    releases: &[Release],
+) -> Result<(), Error> {
+    log::info!("write_rpm_files()");
+    let rpm_conf = &linux_conf.rpm;
+    let metadata = &conf.metadata;
+    let release = rpm_conf.full_release();
+    let full_version = rpm_conf.full_version(&metadata.version);
+    let new_package = |metadata: Metadata| RpmPackage {
+        metadata,
+        release: release.clone(),
+        epoch: rpm_conf.epoch,
+        vendor: rpm_conf.vendor.clone(),
+        group: rpm_group(rpm_conf).to_owned(),
+        arch,
+        source_rpm: format!(
+            "{}-{}-{release}.src.rpm",
+            conf.metadata.name, conf.metadata.version
+        ),
+        dependencies: Vec::new(),
+        scriptlets: Scriptlets::default(),
+        files: BTreeMap::new(),
+        directives: BTreeMap::new(),
+        changelog: releases.to_vec(),
+    };
+    let dependencies = |relations: &RpmRelations| -> Vec<(&'static str, RpmDependency)> {
+        relations
+            .tags()
+            .into_iter()
+            .filter_map(|(tag, relations)| relations.map(|relations| (tag, relations)))
+            .flat_map(|(tag, relations)| {
+                RpmDependency::from_relations(relations, arch)
+                    .into_iter()
+                    .map(move |dependency| (tag, dependency))
+            })
+            .collect()
+    };
+
+    let mut main = new_package(metadata.clone());
+    main.dependencies = dependencies(&rpm_conf.relations);
+    main.scriptlets = scriptlet::read_scriptlets(conf, linux_conf, arch, false)?;
+    main.files = package_entries(source_dir, &staged_files.main, staged_files)?;
+    main.directives = package_directives(&staged_files.main, staged_files);
+    let mut packages = vec![main];
+
+    for (package, files) in linux_conf.packages.iter().zip(&staged_files.packages) {
+        let mut sub_package = new_package(package.metadata(metadata));
+        sub_package.dependencies = package
+            .exact_depends(metadata)
+            .iter()
+            .map(|name| ("Requires", RpmDependency::exact(name, &full_version)))
+            .chain(dependencies(&package.rpm))
+            .collect();
+        sub_package.files = package_entries(source_dir, files, staged_files)?;
+        sub_package.directives = package_directives(files, staged_files);
+        packages.push(sub_package);
+    }
+
+    if !staged_files.debuginfo.is_empty() {
+        let mut debug_metadata = metadata.clone();
+        debug_metadata.name = format!("{}-debuginfo", metadata.name);
+        debug_metadata.short_description =
+            Some(format!("Debug information for package {}", metadata.name));
+        debug_metadata.description = format!(
+            "This package provides debug information for package {}.",
+            metadata.name
+        );
+        let mut debuginfo = new_package(debug_metadata);
+        debuginfo.dependencies = staged_files
+            .debuginfo_depends
+            .iter()
+            .map(|name| ("Requires", RpmDependency::exact(name, &full_version)))
+            .collect();
+        debuginfo.files = package_entries(source_dir, &staged_files.debuginfo, staged_files)?;
+        packages.push(debuginfo);
+    }
+
+    let workdir = Path::new(&metadata.workdir);
+    for package in &packages {
+        let rpm_file = workdir.join(artifact_name(
+            conf,
+            rpm_conf,
+            arch,
+            &package.metadata.name,
+            &release,
+        )?);
+        writer::write_rpm(
+            package,
+            rpm_conf.compression,
+            rpm_conf.compression_level,
+            &rpm_file,
+        )?;
+    }
+    Ok(())
+}
+
+/// Get payload entries of `files` in staged tree, keyed by absolute path.
+///
+/// Directories are included recursively like in `%files`, unless they are
+/// declared with `%dir`.
+fn package_entries(
+    source_dir: &Path,
+    files: &[PathBuf],
+    staged_files: &StagedFiles,
+) -> Result<BTreeMap<String, PayloadEntry>, Error> {
+    let mut entries = BTreeMap::new();
+    for file in files {
+        let max_depth = if staged_files.directories.contains(file) {
+            0
+        } else {
+            usize::MAX
+        };
+        let walker = WalkDir::new(source_dir.join(file))
+            .follow_root_links(false)
+            .max_depth(max_depth);
+        for entry in walker {
+            let entry = entry?;
+            let path = entry.path().strip_prefix(source_dir)?;
+            let file_type = entry.file_type();
+            let kind = if file_type.is_dir() {
+                EntryKind::Directory
+            } else if file_type.is_symlink() {
+                EntryKind::Symlink(fs::read_link(entry.path())?)
+            } else {
+                EntryKind::File(entry.path().to_path_buf())
+            };
+            let attr = staged_files.attrs.get(path).cloned().unwrap_or_default();
+            entries.insert(format!("/{}", path.display()), PayloadEntry { kind, attr });
+        }
+    }
+    Ok(entries)
+}
+
+/// Get directives of `files`, keyed by absolute path like in `package_entries()`.
+fn package_directives(
+    files: &[PathBuf],
+    staged_files: &StagedFiles,
+) -> BTreeMap<String, FileDirective> {
+    files
+        .iter()
+        .filter_map(|file| {
+            staged_files
+                .directives
+                .get(file)
+                .map(|directive| (format!("/{}", file.display()), *directive))
+        })
+        .collect()
+}
+
+/// Generate spec file and run `rpmbuild` in `rpm_dir`.
+fn run_rpmbuild(
+    conf: &Config,
+    linux_conf: &LinuxConfig,
+    arch: PackageArch,
+    rpm_dir: &Path,
+    source_dir: &Path,
+    staged_files: &StagedFiles,
+    releases: &[Release],
+) -> Result<(), Error> {
+    let spec_file = rpm_dir.join(format!("{}.spec", &conf.metadata.name));
+    let mut spec_fd = File::create(&spec_file).map_err(|err| {
+        Error::from_string(
+            ErrorKind::IoError,
+            format!(
+                "Failed to create rpm spec file {}, error: {err:?}",
+                spec_file.display()
+            ),
+        )
+    })?;
+    generate_spec_file(conf, linux_conf, arch, staged_files, releases, &mut spec_fd)?;
 
     // Create binary tarbal.
     let source_tar_file = rpm_dir.join(format!("{}.tar", &conf.metadata.name));
-    archive::create_tar(&source_dir, &source_tar_file)?;
+    archive::create_tar(source_dir, &source_tar_file)?;
     let source_xz_file = rpm_dir.join(format!("{}.tar.xz", &conf.metadata.name));
-    compress::create_xz2(&source_tar_file, &source_xz_file)?;
+    compress::create_xz2(
+        &source_tar_file,
+        &source_xz_file,
+        compress::DEFAULT_XZ_LEVEL,
+    )?;
 
     // Create rpmbuild folder.
     let rpm_source_dir = rpm_dir.join("SOURCES");
@@ -67,72 +395,426 @@ pub fn build_rpm(conf: &Config, linux_conf: &LinuxConfig, _arch: Arch) -> Result
     let new_source_xz_file = rpm_source_dir.join(format!("{}.tar.xz", &conf.metadata.name));
     fs::rename(&source_xz_file, new_source_xz_file)?;
 
-    generate_rpm_file(&spec_file, &rpm_dir)?;
+    generate_rpm_file(&spec_file, rpm_dir, arch)?;
+    move_rpm_files(conf, &linux_conf.rpm, arch, rpm_dir)
+}
+
+/// Get file name of rpm package from `artifact_name` template of `rpm_conf`.
+// Macros like `${name}` are not format arguments.
+#[allow(clippy::literal_string_with_formatting_args)]
+fn artifact_name(
+    conf: &Config,
+    rpm_conf: &RpmConfig,
+    arch: PackageArch,
+    name: &str,
+    release: &str,
+) -> Result<String, Error> {
+    let template = rpm_conf
+        .artifact_name
+        .replace("${name}", name)
+        .replace("${release}", release)
+        .replace("${arch}", package_arch_name(arch));
+    expand_file_macro(&template, conf, arch, PlatformTarget::Rpm)
+}
+
+/// Get group of package, `Unspecified` if not set.
+fn rpm_group(rpm_conf: &RpmConfig) -> &str {
+    rpm_conf.group.as_deref().unwrap_or("Unspecified")
+}
+
+/// Get name of `arch` in rpm, like `%{_arch}` macro.
+pub const fn arch_name(arch: Arch) -> &'static str {
+    match arch {
+        Arch::X86 => "i686",
+        Arch::X86_64 => "x86_64",
+        Arch::AArch64 => "aarch64",
+        Arch::Mips64 => "mips64el",
+        Arch::Armv7 => "armv7hl",
+        Arch::Riscv64 => "riscv64",
+        Arch::Ppc64le => "ppc64le",
+        Arch::S390x => "s390x",
+        Arch::LoongArch64 => "loongarch64",
+    }
+}
+
+/// Get name of package `arch` in rpm, `noarch` if arch independent.
+pub const fn package_arch_name(arch: PackageArch) -> &'static str {
+    match arch {
+        PackageArch::Target(arch) => arch_name(arch),
+        PackageArch::All => "noarch",
+    }
+}
+
+/// Directories owned by `filesystem` package or other common packages,
+/// which are never owned by generated packages.
+const STANDARD_DIRS: &[&str] = &[
+    "etc",
+    "etc/xdg",
+    "etc/xdg/autostart",
+    "etc/systemd",
+    "etc/systemd/system",
+    "opt",
+    "usr",
+    "usr/bin",
+    "usr/include",
+    "usr/lib",
+    "usr/lib/debug",
+    "usr/lib/debug/.build-id",
+    "usr/lib/systemd",
+    "usr/lib/systemd/system",
+    "usr/lib/systemd/user",
+    "usr/lib/sysusers.d",
+    "usr/lib/tmpfiles.d",
+    "usr/lib64",
+    "usr/libexec",
+    "usr/sbin",
+    "usr/share",
+    "usr/share/applications",
+    "usr/share/doc",
+    "usr/share/icons",
+    "usr/share/licenses",
+    "usr/share/metainfo",
+    "usr/share/mime",
+    "usr/share/mime/packages",
+    "usr/share/pixmaps",
+    "var",
+    "var/cache",
+    "var/lib",
+    "var/log",
+];
+
+/// Folders owned by `filesystem` or icon theme packages, with their sub-directories.
+const STANDARD_TREES: &[&str] = &[
+    "usr/share/icons/hicolor",
+    "usr/share/locale",
+    "usr/share/man",
+];
+
+/// Folder of documents, marked with `%doc`.
+const DOC_DIR: &str = "usr/share/doc";
+
+/// Folder of license files, marked with `%license`.
+const LICENSE_DIR: &str = "usr/share/licenses";
+
+/// Folder of config files, marked with `%config(noreplace)`.
+const CONFIG_DIR: &str = "etc";
+
+/// Files staged in rpm build tree, listed in `%files` sections.
+struct StagedFiles {
+    /// Files of main package, excluding debug files.
+    main: Vec<PathBuf>,
+
+    /// Files of each sub-package, in order of `packages`.
+    packages: Vec<Vec<PathBuf>>,
+
+    /// Files of debuginfo package, empty if debug info is not split.
+    debuginfo: Vec<PathBuf>,
 
-    let rpm_dir = rpm_dir.to_str().unwrap();
-    move_rpm_files(rpm_dir)
+    /// Packages whose debug info is in debuginfo package.
+    debuginfo_depends: Vec<String>,
+
+    /// Declared directories and directories owned by packages, listed with `%dir`.
+    directories: BTreeSet<PathBuf>,
+
+    /// Config files, documents and licenses.
+    directives: BTreeMap<PathBuf, FileDirective>,
+
+    /// Attributes set explicitly in file sets.
+    attrs: FileAttrs,
+}
+
+impl StagedFiles {
+    fn file_lists(&self) -> impl Iterator<Item = &Vec<PathBuf>> {
+        std::iter::once(&self.main)
+            .chain(&self.packages)
+            .chain(std::iter::once(&self.debuginfo))
+    }
+
+    fn file_lists_mut(&mut self) -> impl Iterator<Item = &mut Vec<PathBuf>> {
+        std::iter::once(&mut self.main)
+            .chain(&mut self.packages)
+            .chain(std::iter::once(&mut self.debuginfo))
+    }
+}
+
+/// Get parent directories of `files` which shall be owned by package,
+/// excluding standard directories and directories in `files`.
+fn owned_directories(files: &[PathBuf]) -> BTreeSet<PathBuf> {
+    files
+        .iter()
+        .flat_map(|file| file.ancestors().skip(1))
+        .filter(|dir| {
+            !dir.as_os_str().is_empty()
+                && !files.iter().any(|file| file == dir)
+                && !STANDARD_DIRS
+                    .iter()
+                    .any(|standard| *dir == Path::new(standard))
+                && !STANDARD_TREES.iter().any(|tree| dir.starts_with(tree))
+        })
+        .map(Path::to_path_buf)
+        .collect()
+}
+
+/// Get directive of file by its path, directories are not checked.
+fn file_directive(path: &Path) -> Option<FileDirective> {
+    if path.starts_with(CONFIG_DIR) {
+        Some(FileDirective::Config)
+    } else if path.starts_with(DOC_DIR) {
+        Some(FileDirective::Doc)
+    } else if path.starts_with(LICENSE_DIR) {
+        Some(FileDirective::License)
+    } else {
+        None
+    }
+}
+
+/// Install `license_file` of package to `/usr/share/licenses/<name>/`,
+/// unless it is installed by file sets already.
+fn install_license_file(metadata: &Metadata, source_dir: &Path) -> Result<(), Error> {
+    let Some(license_file) = metadata.license_file.as_ref() else {
+        return Ok(());
+    };
+    let file_name = Path::new(license_file).file_name().ok_or_else(|| {
+        Error::from_string(
+            ErrorKind::InvalidConfError,
+            format!("Invalid license file: {license_file}"),
+        )
+    })?;
+    let license_dir = source_dir.join(LICENSE_DIR).join(&metadata.name);
+    let dest_path = license_dir.join(file_name);
+    if dest_path.exists() {
+        return Ok(());
+    }
+    fs::create_dir_all(&license_dir)?;
+    fs::copy(license_file, &dest_path).map_err(|err| {
+        Error::from_string(
+            ErrorKind::IoError,
+            format!("Failed to copy license file {license_file}, err: {err}"),
+        )
+    })?;
+    Ok(())
 }
 
 fn generate_spec_file(
     conf: &Config,
-    rpm_conf: &RpmConfig,
+    linux_conf: &LinuxConfig,
+    arch: PackageArch,
+    staged_files: &StagedFiles,
+    releases: &[Release],
     spec_fd: &mut File,
 ) -> Result<(), Error> {
     log::info!("generate_spec_file()");
+    let rpm_conf = &linux_conf.rpm;
+
+    // Binaries are prebuilt, debuginfo package is generated by pifu with `split_debug`.
+    writeln!(spec_fd, "%global debug_package %{{nil}}\n")?;
 
     // Generate spec file.
     writeln!(spec_fd, "Name: {}", &conf.metadata.name)?;
+    if let Some(epoch) = rpm_conf.epoch {
+        writeln!(spec_fd, "Epoch: {epoch}")?;
+    }
     writeln!(spec_fd, "Version: {}", &conf.metadata.version)?;
-    writeln!(spec_fd, "Release: 1%{{?dist}}")?;
-    // TODO(Shaohua): Replace with short_description.
-    writeln!(spec_fd, "Summary: {}", &conf.metadata.description)?;
+    let dist = rpm_conf.dist.as_deref().unwrap_or("%{?dist}");
+    writeln!(spec_fd, "Release: {}{dist}", rpm_conf.release)?;
+    writeln!(spec_fd, "Summary: {}", conf.metadata.summary())?;
     writeln!(spec_fd, "License: {}", &conf.metadata.license)?;
     writeln!(spec_fd, "URL: {}", &conf.metadata.homepage)?;
     writeln!(spec_fd, "Packager: {}", &conf.metadata.author)?;
+    if let Some(vendor) = &rpm_conf.vendor {
+        writeln!(spec_fd, "Vendor: {vendor}")?;
+    }
+    writeln!(spec_fd, "Group: {}", rpm_group(rpm_conf))?;
 
     let source_tar_filename = format!("{}.tar.xz", &conf.metadata.name);
     writeln!(spec_fd, "Source0: {}", &source_tar_filename)?;
+    if arch == PackageArch::All {
+        writeln!(spec_fd, "BuildArch: {}", package_arch_name(arch))?;
+    }
 
-    if let Some(required_pkgs) = rpm_conf.required_pkgs.as_ref() {
-        for pkg in required_pkgs {
-            writeln!(spec_fd, "Required: {pkg}")?;
+    write_relations(spec_fd, &rpm_conf.relations, arch)?;
+    writeln!(
+        spec_fd,
+        "\n%description\n{}\n",
+        spec_description(&conf.metadata)
+    )?;
+
+    for package in &linux_conf.packages {
+        let metadata = package.metadata(&conf.metadata);
+        writeln!(spec_fd, "%package -n {}", metadata.name)?;
+        writeln!(spec_fd, "Summary: {}", metadata.summary())?;
+        for name in package.exact_depends(&conf.metadata) {
+            writeln!(
+                spec_fd,
+                "Requires: {name} = %{{?epoch:%{{epoch}}:}}%{{version}}-%{{release}}"
+            )?;
         }
+        write_relations(spec_fd, &package.rpm, arch)?;
+        writeln!(
+            spec_fd,
+            "\n%description -n {}\n{}\n",
+            metadata.name,
+            spec_description(&metadata)
+        )?;
     }
 
-    writeln!(spec_fd, "\n%description\n{}", &conf.metadata.description)?;
+    if !staged_files.debuginfo.is_empty() {
+        writeln!(
+            spec_fd,
+            r"%package debuginfo
+Summary: Debug information for package %{{name}}
+AutoReqProv: 0"
+        )?;
+        for name in &staged_files.debuginfo_depends {
+            writeln!(
+                spec_fd,
+                "Requires: {name} = %{{?epoch:%{{epoch}}:}}%{{version}}-%{{release}}"
+            )?;
+        }
+        writeln!(
+            spec_fd,
+            r"
+%description debuginfo
+This package provides debug information for package %{{name}}.
+"
+        )?;
+    }
     writeln!(
         spec_fd,
-        r#"%prep  
+        r"%prep  
 %setup -q           
 
 %build 
 
 %install
 cp -rfa * %{{buildroot}}
+"
+    )?;
+    let scriptlets = scriptlet::read_scriptlets(conf, linux_conf, arch, true)?;
+    write_scriptlets(spec_fd, &scriptlets)?;
+    writeln!(spec_fd, "%clean\n\n%files\n%defattr(-,root,root,-)")?;
+    write_files(spec_fd, &staged_files.main, staged_files)?;
+    if !staged_files.debuginfo.is_empty() {
+        writeln!(spec_fd, "\n%files debuginfo\n%defattr(-,root,root,-)")?;
+        write_files(spec_fd, &staged_files.debuginfo, staged_files)?;
+    }
+    for (package, files) in linux_conf.packages.iter().zip(&staged_files.packages) {
+        writeln!(spec_fd, "\n%files -n {}", package.name(&conf.metadata))?;
+        writeln!(spec_fd, "%defattr(-,root,root,-)")?;
+        write_files(spec_fd, files, staged_files)?;
+    }
 
-%pre
-
-%post
+    if !releases.is_empty() {
+        let entries = changelog::to_rpm_spec(releases, &conf.metadata.author, |version| {
+            rpm_conf.full_version(version)
+        })?;
+        write!(spec_fd, "\n%changelog\n{entries}")?;
+    }
+    Ok(())
+}
 
-%preun
+/// Write scriptlet sections like `%post`, and file trigger sections like `%filetriggerin`.
+///
+/// Interpreter is set with `-p` option if it is not `/bin/sh`.
+fn write_scriptlets(spec_fd: &mut File, scriptlets: &Scriptlets) -> Result<(), Error> {
+    for (name, scriptlet) in &scriptlets.scriptlets {
+        write!(spec_fd, "%{name}")?;
+        if scriptlet.interpreter != DEFAULT_INTERPRETER {
+            write!(spec_fd, " -p {}", scriptlet.interpreter)?;
+        }
+        writeln!(spec_fd, "\n{}", scriptlet.content)?;
+    }
+    for trigger in &scriptlets.file_triggers {
+        write!(spec_fd, "%{}", trigger.kind.name())?;
+        if trigger.scriptlet.interpreter != DEFAULT_INTERPRETER {
+            write!(spec_fd, " -p {}", trigger.scriptlet.interpreter)?;
+        }
+        writeln!(
+            spec_fd,
+            " -P {} -- {}\n{}",
+            trigger.priority,
+            trigger.paths.join(" "),
+            trigger.scriptlet.content
+        )?;
+    }
+    Ok(())
+}
 
-%postun
+/// Write dependency tags applied to `arch`.
+fn write_relations(
+    spec_fd: &mut File,
+    relations: &RpmRelations,
+    arch: PackageArch,
+) -> Result<(), Error> {
+    for (tag, relations) in relations.tags() {
+        for value in relations
+            .iter()
+            .flat_map(|relations| relations.to_rpm(arch))
+        {
+            writeln!(spec_fd, "{tag}: {value}")?;
+        }
+    }
+    Ok(())
+}
 
-%clean
+/// Get description of package, with `%` escaped to prevent macro expansion.
+fn spec_description(metadata: &Metadata) -> String {
+    metadata
+        .long_description()
+        .unwrap_or_else(|| metadata.summary())
+        .replace('%', "%%")
+}
 
-%files
-/*
-"#
-    )?;
+/// Get paths of files and symbolic links in `dir`, relative to `dir`.
+fn list_files(dir: &Path) -> Result<Vec<PathBuf>, Error> {
+    let mut files = Vec::new();
+    for entry in WalkDir::new(dir).sort_by_file_name() {
+        let entry = entry?;
+        if !entry.file_type().is_dir() {
+            files.push(entry.path().strip_prefix(dir)?.to_path_buf());
+        }
+    }
+    Ok(files)
+}
 
+/// Write quoted paths of `files` as entries of `%files`, with `%attr` if set.
+///
+/// Directories are marked with `%dir`, so that only the directory itself
+/// is owned by package. Config files, documents and licenses are marked
+/// with their directives.
+fn write_files(
+    spec_fd: &mut File,
+    files: &[PathBuf],
+    staged_files: &StagedFiles,
+) -> Result<(), Error> {
+    for file in files {
+        let path = file.display().to_string().replace('%', "%%");
+        if staged_files.directories.contains(file) {
+            write!(spec_fd, "%dir ")?;
+        } else if let Some(directive) = staged_files.directives.get(file) {
+            write!(spec_fd, "{} ", directive.spec_directive())?;
+        }
+        if let Some(attr) = staged_files.attrs.get(file) {
+            let mode = attr
+                .mode
+                .map_or_else(|| "-".to_owned(), |mode| format!("{mode:o}"));
+            writeln!(
+                spec_fd,
+                "%attr({mode},{},{}) \"/{path}\"",
+                attr.owner, attr.group
+            )?;
+        } else {
+            writeln!(spec_fd, "\"/{path}\"")?;
+        }
+    }
     Ok(())
 }
 
-fn generate_rpm_file(spec_file: &Path, rpm_dir: &Path) -> Result<(), Error> {
+fn generate_rpm_file(spec_file: &Path, rpm_dir: &Path, arch: PackageArch) -> Result<(), Error> {
     log::info!(
-        "generate_rpm_file() spec: {:?}, rpm_dir: {:?}",
-        spec_file,
-        rpm_dir
+        "generate_rpm_file() spec: {}, rpm_dir: {}, arch: {arch}",
+        spec_file.display(),
+        rpm_dir.display()
     );
     let def = format!("_topdir {}", fs::canonicalize(rpm_dir)?.display());
 
@@ -141,6 +823,9 @@ fn generate_rpm_file(spec_file: &Path, rpm_dir: &Path) -> Result<(), Error> {
     let output = cmd
         .arg("-D")
         .arg(&def)
+        // Files are prebuilt, so packages can be built for any arch.
+        .arg("--target")
+        .arg(package_arch_name(arch))
         .arg("-bb")
         .arg(spec_file)
         .output()
@@ -161,12 +846,63 @@ fn generate_rpm_file(spec_file: &Path, rpm_dir: &Path) -> Result<(), Error> {
         io::stderr().write_all(&output.stderr)?;
         Err(Error::from_string(
             ErrorKind::RpmCompilerError,
-            format!("rpm file: {spec_file:?}",),
+            format!("rpm file: {}", spec_file.display()),
         ))
     }
 }
 
-fn move_rpm_files(rpm_dir: &str) -> Result<(), Error> {
-    let rpm_files = format!("{rpm_dir}/RPMS/*/*.rpm");
-    utils::mv(&rpm_files, rpm_dir)
+/// Move rpm files in `RPMS/<arch>` folder of `rpm_dir` to `workdir`,
+/// renamed with `artifact_name` template.
+fn move_rpm_files(
+    conf: &Config,
+    rpm_conf: &RpmConfig,
+    arch: PackageArch,
+    rpm_dir: &Path,
+) -> Result<(), Error> {
+    let arch_dir = rpm_dir.join("RPMS").join(package_arch_name(arch));
+    let workdir = Path::new(&conf.metadata.workdir);
+    // Escape folder name, which may contain glob characters.
+    let pattern = format!(
+        "{}/*.rpm",
+        glob::Pattern::escape(&arch_dir.to_string_lossy())
+    );
+    let rpm_files = utils::mv(&pattern, workdir)?;
+    if rpm_files.is_empty() {
+        return Err(Error::from_string(
+            ErrorKind::RpmCompilerError,
+            format!("No rpm file found in {}", arch_dir.display()),
+        ));
+    }
+
+    let mut artifact_files = Vec::with_capacity(rpm_files.len());
+    for rpm_file in &rpm_files {
+        // Release may contain distribution tag of build host.
+        let mut reader = BufReader::new(File::open(rpm_file)?);
+        let (_signature, header) = header::read_headers(&mut reader)?;
+        let name = header.get_string(RPMTAG_NAME).unwrap_or_default();
+        let release = header.get_string(RPMTAG_RELEASE).unwrap_or_default();
+        let artifact_file = workdir.join(artifact_name(conf, rpm_conf, arch, name, release)?);
+        if artifact_files.contains(&artifact_file) {
+            return Err(Error::from_string(
+                ErrorKind::RpmCompilerError,
+                format!(
+                    "Rpm files are renamed to the same file {}, please check artifact_name",
+                    artifact_file.display()
+                ),
+            ));
+        }
+        artifact_files.push(artifact_file);
+    }
+
+    for (rpm_file, artifact_file) in rpm_files.iter().zip(&artifact_files) {
+        log::info!(
+            "move_rpm_files() {} -> {}",
+            rpm_file.display(),
+            artifact_file.display()
+        );
+        if artifact_file != rpm_file {
+            fs::rename(rpm_file, artifact_file)?;
+        }
+    }
+    Ok(())
 }
@@ -4,13 +4,352 @@
 
 use serde::{Deserialize, Serialize};
 
+use crate::base::compress::CompressFormat;
+use crate::base::expand_file_macro_simple;
 use crate::base::fileset::FileSet;
+use crate::base::relation::Relations;
+use crate::base::script::Script;
+use crate::error::{Error, ErrorKind};
 
-#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct RpmConfig {
     /// File list.
     pub files: Option<Vec<FileSet>>,
 
-    /// Specify additional required packages.
-    pub required_pkgs: Option<Vec<String>>,
+    /// Dependency tags, like `requires` and `obsoletes`.
+    #[serde(flatten)]
+    pub relations: RpmRelations,
+
+    /// Release of package, like `2` in `hello-1.0.0-2`, default is `1`.
+    ///
+    /// Bump it to rebuild package of the same version.
+    /// Macros like `${date}` and `${git}` are expanded.
+    #[serde(default = "default_release")]
+    pub release: String,
+
+    /// Epoch of package, written as `epoch:version-release` in dependencies.
+    pub epoch: Option<u32>,
+
+    /// Distribution tag appended to release, like `.el9`.
+    ///
+    /// With `rpmbuild` backend, `%{?dist}` of build host is used if not set.
+    pub dist: Option<String>,
+
+    pub vendor: Option<String>,
+
+    /// Group of package, default is `Unspecified`.
+    pub group: Option<String>,
+
+    /// File name template of rpm files.
+    ///
+    /// Default is `${name}-${version}-${release}.${arch}.${ext}`, in which `${name}`
+    /// is name of each package, `${release}` is release with distribution tag,
+    /// and `${arch}` is architecture name in rpm, like `aarch64` and `noarch`.
+    #[serde(default = "default_artifact_name")]
+    pub artifact_name: String,
+
+    /// Tool to generate rpm files, default is `native`.
+    #[serde(default)]
+    pub backend: RpmBackend,
+
+    /// Compression format of payload.
+    ///
+    /// Available values are `gzip`, `xz` and `zstd`, default is `xz`.
+    /// Note that `zstd` requires rpm 4.14 or later.
+    #[serde(default = "default_compression")]
+    pub compression: CompressFormat,
+
+    /// Compression level, default level of `compression` format is used if not set.
+    pub compression_level: Option<u32>,
+
+    /// Scriptlet run before the package is installed.
+    pub pre: Option<RpmScript>,
+
+    /// Scriptlet run after the package is installed.
+    pub post: Option<RpmScript>,
+
+    /// Scriptlet run before the package is removed.
+    pub preun: Option<RpmScript>,
+
+    /// Scriptlet run after the package is removed.
+    pub postun: Option<RpmScript>,
+
+    /// Scriptlet run before the transaction, before any package is installed.
+    pub pretrans: Option<RpmScript>,
+
+    /// Scriptlet run after the transaction, after all packages are installed.
+    pub posttrans: Option<RpmScript>,
+
+    /// Scriptlets triggered by files installed or removed by other packages.
+    #[serde(default = "Vec::new")]
+    pub file_triggers: Vec<FileTrigger>,
+}
+
+/// Scriptlet of rpm package, with its interpreter.
+///
+/// ```toml
+/// post = { inline = "systemctl reload ${name}" }
+/// posttrans = { file = "pkg/posttrans.lua", interpreter = "<lua>" }
+/// ```
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct RpmScript {
+    #[serde(flatten)]
+    pub script: Script,
+
+    /// Path to interpreter, or `<lua>` for embedded lua interpreter of rpm.
+    ///
+    /// Program in shebang line of script is used if not set, or `/bin/sh`.
+    pub interpreter: Option<String>,
+}
+
+/// Type of file trigger, like `%filetriggerin`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FileTriggerKind {
+    /// Run once for each package installing matched files.
+    FileTriggerIn,
+
+    /// Run once for each package removing matched files, before removal.
+    FileTriggerUn,
+
+    /// Run once for each package removing matched files, after removal.
+    FileTriggerPostUn,
+
+    /// Run once after the transaction installing matched files.
+    TransFileTriggerIn,
+
+    /// Run once before the transaction removing matched files.
+    TransFileTriggerUn,
+
+    /// Run once after the transaction removing matched files.
+    TransFileTriggerPostUn,
+}
+
+/// Scriptlet run when files under `paths` are installed or removed.
+///
+/// ```toml
+/// [[linux.rpm.file_triggers]]
+/// kind = "transfiletriggerin"
+/// paths = ["/usr/lib/hello/plugins"]
+/// inline = "hello --rebuild-plugin-cache"
+/// ```
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct FileTrigger {
+    pub kind: FileTriggerKind,
+
+    /// Path prefixes to watch, like `/usr/lib64`.
+    pub paths: Vec<String>,
+
+    /// Triggers with higher priority run first, default is `1000000`.
+    #[serde(default = "default_trigger_priority")]
+    pub priority: u32,
+
+    #[serde(flatten)]
+    pub script: RpmScript,
+}
+
+const fn default_trigger_priority() -> u32 {
+    1_000_000
+}
+
+impl FileTriggerKind {
+    /// Name of trigger in spec file, like `filetriggerin`.
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::FileTriggerIn => "filetriggerin",
+            Self::FileTriggerUn => "filetriggerun",
+            Self::FileTriggerPostUn => "filetriggerpostun",
+            Self::TransFileTriggerIn => "transfiletriggerin",
+            Self::TransFileTriggerUn => "transfiletriggerun",
+            Self::TransFileTriggerPostUn => "transfiletriggerpostun",
+        }
+    }
+
+    /// Whether trigger runs once in a transaction.
+    #[must_use]
+    pub const fn is_transaction(self) -> bool {
+        matches!(
+            self,
+            Self::TransFileTriggerIn | Self::TransFileTriggerUn | Self::TransFileTriggerPostUn
+        )
+    }
+}
+
+/// Tool to generate rpm files.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum RpmBackend {
+    /// Write rpm files with pifu itself, works on any host.
+    #[default]
+    #[serde(alias = "native")]
+    Native,
+
+    /// Run `rpmbuild` with a generated spec file.
+    #[serde(alias = "rpmbuild")]
+    Rpmbuild,
+}
+
+const fn default_compression() -> CompressFormat {
+    CompressFormat::Xz
+}
+
+fn default_release() -> String {
+    "1".to_owned()
+}
+
+fn default_artifact_name() -> String {
+    "${name}-${version}-${release}.${arch}.${ext}".to_owned()
+}
+
+impl Default for RpmConfig {
+    fn default() -> Self {
+        Self {
+            files: None,
+            relations: RpmRelations::default(),
+            release: default_release(),
+            epoch: None,
+            dist: None,
+            vendor: None,
+            group: None,
+            artifact_name: default_artifact_name(),
+            backend: RpmBackend::default(),
+            compression: default_compression(),
+            compression_level: None,
+            pre: None,
+            post: None,
+            preun: None,
+            postun: None,
+            pretrans: None,
+            posttrans: None,
+            file_triggers: Vec::new(),
+        }
+    }
+}
+
+impl RpmConfig {
+    /// Get scriptlets with their names in spec file.
+    #[must_use]
+    pub const fn scriptlets(&self) -> [(&'static str, Option<&RpmScript>); 6] {
+        [
+            ("pretrans", self.pretrans.as_ref()),
+            ("pre", self.pre.as_ref()),
+            ("post", self.post.as_ref()),
+            ("preun", self.preun.as_ref()),
+            ("postun", self.postun.as_ref()),
+            ("posttrans", self.posttrans.as_ref()),
+        ]
+    }
+
+    /// Get release with distribution tag, like `1.el9`.
+    #[must_use]
+    pub fn full_release(&self) -> String {
+        format!(
+            "{}{}",
+            self.release,
+            self.dist.as_deref().unwrap_or_default()
+        )
+    }
+
+    /// Get version with epoch and release, like `1:1.0.0-1.el9`.
+    #[must_use]
+    pub fn full_version(&self, version: &str) -> String {
+        let release = self.full_release();
+        self.epoch.map_or_else(
+            || format!("{version}-{release}"),
+            |epoch| format!("{epoch}:{version}-{release}"),
+        )
+    }
+
+    /// Expand macros in `release`, `dist`, `vendor` and `group`.
+    ///
+    /// # Errors
+    /// Returns error if failed to expand macros.
+    pub fn expand_macros(&mut self) -> Result<(), Error> {
+        self.release = expand_file_macro_simple(&self.release)?;
+        for value in [&mut self.dist, &mut self.vendor, &mut self.group]
+            .into_iter()
+            .flatten()
+        {
+            *value = expand_file_macro_simple(value)?;
+        }
+        Ok(())
+    }
+
+    /// # Errors
+    /// Returns error if payload compression is not supported by rpm,
+    /// release is invalid, or paths of file triggers are invalid.
+    pub fn validate(&self) -> Result<(), Error> {
+        let release = self.full_release();
+        let valid_release = !self.release.is_empty()
+            && release
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "._+~^".contains(c));
+        if !valid_release {
+            return Err(Error::from_string(
+                ErrorKind::InvalidConfError,
+                format!("Invalid rpm release `{release}`"),
+            ));
+        }
+        if !self.artifact_name.contains("${name}") {
+            // Main package, sub-packages and debuginfo package share the same template.
+            return Err(Error::from_string(
+                ErrorKind::InvalidConfError,
+                format!(
+                    "Rpm artifact_name `{}` shall contain `${{name}}`",
+                    self.artifact_name
+                ),
+            ));
+        }
+        if self.compression == CompressFormat::None {
+            return Err(Error::new(
+                ErrorKind::InvalidConfError,
+                "Rpm payload compression shall be one of `gzip`, `xz` and `zstd`",
+            ));
+        }
+        for trigger in &self.file_triggers {
+            if trigger.paths.is_empty() || trigger.paths.iter().any(|path| !path.starts_with('/')) {
+                return Err(Error::from_string(
+                    ErrorKind::InvalidConfError,
+                    format!(
+                        "Paths of `{}` trigger shall be absolute paths, got: `{}`",
+                        trigger.kind.name(),
+                        trigger.paths.join(" ")
+                    ),
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Dependency tags of rpm spec file.
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+pub struct RpmRelations {
+    /// Required packages, like `glibc >= 2.36`.
+    ///
+    /// Either a comma separated string or a list of strings.
+    /// Alternatives like `foo | bar` are written as rich dependency `(foo or bar)`.
+    #[serde(alias = "required_pkgs")]
+    pub requires: Option<Relations>,
+
+    pub recommends: Option<Relations>,
+    pub suggests: Option<Relations>,
+    pub obsoletes: Option<Relations>,
+    pub conflicts: Option<Relations>,
+    pub provides: Option<Relations>,
+}
+
+impl RpmRelations {
+    /// Get dependency tags of spec file.
+    #[must_use]
+    pub const fn tags(&self) -> [(&'static str, Option<&Relations>); 6] {
+        [
+            ("Requires", self.requires.as_ref()),
+            ("Recommends", self.recommends.as_ref()),
+            ("Suggests", self.suggests.as_ref()),
+            ("Obsoletes", self.obsoletes.as_ref()),
+            ("Conflicts", self.conflicts.as_ref()),
+            ("Provides", self.provides.as_ref()),
+        ]
+    }
 }
@@ -0,0 +1,176 @@
+// Copyright (c) 2021 Xu Shaohua <shaohua@biofan.org>. All rights reserved.
+// Use of this source is governed by General Public License that can be found
+// in the LICENSE file.
+
+//! Writer of cpio archive in `newc` format, used as payload of rpm.
+
+use std::fmt::Write as _;
+use std::io::{self, Read, Write};
+
+use crate::error::{Error, ErrorKind};
+
+/// Magic number of `newc` format, without checksum.
+const NEWC_MAGIC: &str = "070701";
+
+/// Name of the last entry in archive.
+const TRAILER_NAME: &str = "TRAILER!!!";
+
+/// Size of entry header, excluding file name.
+const HEADER_SIZE: usize = 110;
+
+/// Fields of an entry, numbers are limited to 32 bits by `newc` format.
+#[derive(Debug, Clone, Copy)]
+pub struct CpioEntry<'a> {
+    pub name: &'a str,
+    pub inode: u32,
+
+    /// File type and permission bits, like `0o100644`.
+    pub mode: u32,
+    pub nlink: u32,
+    pub mtime: u32,
+    pub size: u32,
+}
+
+pub struct CpioWriter<W: Write> {
+    inner: W,
+
+    /// Bytes written, used to align entries to 4 bytes.
+    written: usize,
+}
+
+impl<W: Write> CpioWriter<W> {
+    pub const fn new(inner: W) -> Self {
+        Self { inner, written: 0 }
+    }
+
+    /// Append an entry, with exactly `entry.size` bytes of content read from `reader`.
+    ///
+    /// # Errors
+    /// Returns error if failed to write data, or size of content does not match.
+    pub fn append<R: Read>(&mut self, entry: &CpioEntry, reader: &mut R) -> Result<(), Error> {
+        self.write_header(entry)?;
+        let copied = io::copy(&mut reader.take(u64::from(entry.size)), &mut self.inner)?;
+        if copied != u64::from(entry.size) {
+            return Err(Error::from_string(
+                ErrorKind::IoError,
+                format!(
+                    "Size of {} changed while writing cpio archive, expected: {}, got: {copied}",
+                    entry.name, entry.size
+                ),
+            ));
+        }
+        self.written += entry.size as usize;
+        self.write_padding()
+    }
+
+    /// Write trailer entry, and returns inner writer and size of archive.
+    ///
+    /// # Errors
+    /// Returns error if failed to write data.
+    pub fn finish(mut self) -> Result<(W, usize), Error> {
+        let trailer = CpioEntry {
+            name: TRAILER_NAME,
+            inode: 0,
+            mode: 0,
+            nlink: 1,
+            mtime: 0,
+            size: 0,
+        };
+        self.write_header(&trailer)?;
+        Ok((self.inner, self.written))
+    }
+
+    fn write_header(&mut self, entry: &CpioEntry) -> Result<(), Error> {
+        let name_size = entry.name.len() + 1;
+        let name_size_field = u32::try_from(name_size).map_err(|_err| {
+            Error::from_string(
+                ErrorKind::InvalidPackageError,
+                format!("File name is too long: {}", entry.name),
+            )
+        })?;
+        let fields = [
+            entry.inode,
+            entry.mode,
+            0, // uid, owner is set in rpm header.
+            0, // gid
+            entry.nlink,
+            entry.mtime,
+            entry.size,
+            0, // dev major
+            0, // dev minor
+            0, // rdev major
+            0, // rdev minor
+            name_size_field,
+            0, // checksum
+        ];
+        let mut header = String::with_capacity(HEADER_SIZE);
+        header.push_str(NEWC_MAGIC);
+        for field in fields {
+            write!(header, "{field:08x}")?;
+        }
+        self.inner.write_all(header.as_bytes())?;
+        self.inner.write_all(entry.name.as_bytes())?;
+        self.inner.write_all(&[0])?;
+        self.written += HEADER_SIZE + name_size;
+        self.write_padding()
+    }
+
+    /// Align to 4 bytes with zeros.
+    fn write_padding(&mut self) -> Result<(), Error> {
+        let padding = self.written.next_multiple_of(4) - self.written;
+        self.inner.write_all(&[0; 3][..padding])?;
+        self.written += padding;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(name: &str, size: u32) -> CpioEntry<'_> {
+        CpioEntry {
+            name,
+            inode: 1,
+            mode: 0o100_644,
+            nlink: 1,
+            mtime: 0,
+            size,
+        }
+    }
+
+    #[test]
+    fn test_padding() {
+        let mut writer = CpioWriter::new(Vec::new());
+        writer.append(&entry("./a", 5), &mut &b"hello"[..]).unwrap();
+        let (buf, size) = writer.finish().unwrap();
+        assert_eq!(size, buf.len());
+        assert_eq!(size % 4, 0);
+
+        assert_eq!(&buf[..6], NEWC_MAGIC.as_bytes());
+        // Name size field includes the trailing nul.
+        assert_eq!(&buf[94..102], b"00000004");
+        // Header and name take 114 bytes, padded to 116.
+        assert_eq!(&buf[110..116], b"./a\0\0\0");
+        assert_eq!(&buf[116..121], b"hello");
+        assert_eq!(&buf[121..124], &[0, 0, 0]);
+    }
+
+    #[test]
+    fn test_trailer() {
+        let (buf, size) = CpioWriter::new(Vec::new()).finish().unwrap();
+        // 110 bytes of header and 11 bytes of name, padded to 124.
+        assert_eq!(size, 124);
+        assert_eq!(&buf[..6], NEWC_MAGIC.as_bytes());
+        assert_eq!(&buf[110..121], b"TRAILER!!!\0");
+        assert_eq!(&buf[121..], &[0, 0, 0]);
+    }
+
+    #[test]
+    fn test_size_mismatch() {
+        let mut writer = CpioWriter::new(Vec::new());
+        assert!(writer
+            .append(&entry("./a", 10), &mut &b"hello"[..])
+            .is_err());
+    }
+}
@@ -0,0 +1,539 @@
+// Copyright (c) 2021 Xu Shaohua <shaohua@biofan.org>. All rights reserved.
+// Use of this source is governed by General Public License that can be found
+// in the LICENSE file.
+
+//! Rpm header structure, used by both signature header and main header.
+//!
+//! Headers are read by `pifu inspect`, and written by native rpm writer.
+//!
+//! See [rpm file format](https://rpm-software-management.github.io/rpm/manual/format.html).
+
+use std::collections::BTreeMap;
+use std::io::Read;
+use std::mem;
+use std::ops::Range;
+
+use crate::error::{Error, ErrorKind};
+
+/// Magic number of rpm lead.
+pub const LEAD_MAGIC: [u8; 4] = [0xed, 0xab, 0xee, 0xdb];
+
+/// Size of rpm lead, in bytes.
+pub const LEAD_SIZE: usize = 96;
+
+/// Magic number and version of header structure.
+pub const HEADER_MAGIC: [u8; 4] = [0x8e, 0xad, 0xe8, 0x01];
+
+/// Size of an index entry, also size of region trailer.
+const REGION_ENTRY_SIZE: usize = 16;
+
+/// Type id of binary value, used by region entries.
+const BIN_TYPE: u32 = 7;
+
+/// Region tag of signature header.
+pub const RPMTAG_HEADERSIGNATURES: u32 = 62;
+/// Region tag of main header.
+pub const RPMTAG_HEADERIMMUTABLE: u32 = 63;
+pub const RPMTAG_HEADERI18NTABLE: u32 = 100;
+
+pub const RPMSIGTAG_SHA256: u32 = 273;
+pub const RPMSIGTAG_SIZE: u32 = 1000;
+pub const RPMSIGTAG_MD5: u32 = 1004;
+pub const RPMSIGTAG_PAYLOADSIZE: u32 = 1007;
+
+pub const RPMTAG_NAME: u32 = 1000;
+pub const RPMTAG_VERSION: u32 = 1001;
+pub const RPMTAG_RELEASE: u32 = 1002;
+pub const RPMTAG_EPOCH: u32 = 1003;
+pub const RPMTAG_SUMMARY: u32 = 1004;
+pub const RPMTAG_DESCRIPTION: u32 = 1005;
+pub const RPMTAG_BUILDTIME: u32 = 1006;
+pub const RPMTAG_BUILDHOST: u32 = 1007;
+pub const RPMTAG_SIZE: u32 = 1009;
+pub const RPMTAG_VENDOR: u32 = 1011;
+pub const RPMTAG_LICENSE: u32 = 1014;
+pub const RPMTAG_PACKAGER: u32 = 1015;
+pub const RPMTAG_GROUP: u32 = 1016;
+pub const RPMTAG_URL: u32 = 1020;
+pub const RPMTAG_OS: u32 = 1021;
+pub const RPMTAG_ARCH: u32 = 1022;
+pub const RPMTAG_PREIN: u32 = 1023;
+pub const RPMTAG_POSTIN: u32 = 1024;
+pub const RPMTAG_PREUN: u32 = 1025;
+pub const RPMTAG_POSTUN: u32 = 1026;
+pub const RPMTAG_OLDFILENAMES: u32 = 1027;
+pub const RPMTAG_FILESIZES: u32 = 1028;
+pub const RPMTAG_FILEMODES: u32 = 1030;
+pub const RPMTAG_FILERDEVS: u32 = 1033;
+pub const RPMTAG_FILEMTIMES: u32 = 1034;
+pub const RPMTAG_FILEDIGESTS: u32 = 1035;
+pub const RPMTAG_FILELINKTOS: u32 = 1036;
+pub const RPMTAG_FILEFLAGS: u32 = 1037;
+pub const RPMTAG_FILEUSERNAME: u32 = 1039;
+pub const RPMTAG_FILEGROUPNAME: u32 = 1040;
+pub const RPMTAG_SOURCERPM: u32 = 1044;
+pub const RPMTAG_FILEVERIFYFLAGS: u32 = 1045;
+pub const RPMTAG_PROVIDENAME: u32 = 1047;
+pub const RPMTAG_REQUIREFLAGS: u32 = 1048;
+pub const RPMTAG_REQUIRENAME: u32 = 1049;
+pub const RPMTAG_REQUIREVERSION: u32 = 1050;
+pub const RPMTAG_CONFLICTFLAGS: u32 = 1053;
+pub const RPMTAG_CONFLICTNAME: u32 = 1054;
+pub const RPMTAG_CONFLICTVERSION: u32 = 1055;
+pub const RPMTAG_CHANGELOGTIME: u32 = 1080;
+pub const RPMTAG_CHANGELOGNAME: u32 = 1081;
+pub const RPMTAG_CHANGELOGTEXT: u32 = 1082;
+pub const RPMTAG_PREINPROG: u32 = 1085;
+pub const RPMTAG_POSTINPROG: u32 = 1086;
+pub const RPMTAG_PREUNPROG: u32 = 1087;
+pub const RPMTAG_POSTUNPROG: u32 = 1088;
+pub const RPMTAG_OBSOLETENAME: u32 = 1090;
+pub const RPMTAG_FILEDEVICES: u32 = 1095;
+pub const RPMTAG_FILEINODES: u32 = 1096;
+pub const RPMTAG_FILELANGS: u32 = 1097;
+pub const RPMTAG_PROVIDEFLAGS: u32 = 1112;
+pub const RPMTAG_PROVIDEVERSION: u32 = 1113;
+pub const RPMTAG_OBSOLETEFLAGS: u32 = 1114;
+pub const RPMTAG_OBSOLETEVERSION: u32 = 1115;
+pub const RPMTAG_DIRINDEXES: u32 = 1116;
+pub const RPMTAG_BASENAMES: u32 = 1117;
+pub const RPMTAG_DIRNAMES: u32 = 1118;
+pub const RPMTAG_PAYLOADFORMAT: u32 = 1124;
+pub const RPMTAG_PAYLOADCOMPRESSOR: u32 = 1125;
+pub const RPMTAG_PAYLOADFLAGS: u32 = 1126;
+pub const RPMTAG_PRETRANS: u32 = 1151;
+pub const RPMTAG_POSTTRANS: u32 = 1152;
+pub const RPMTAG_PRETRANSPROG: u32 = 1153;
+pub const RPMTAG_POSTTRANSPROG: u32 = 1154;
+pub const RPMTAG_LONGFILESIZES: u32 = 5008;
+pub const RPMTAG_FILEDIGESTALGO: u32 = 5011;
+pub const RPMTAG_RECOMMENDNAME: u32 = 5046;
+pub const RPMTAG_RECOMMENDVERSION: u32 = 5047;
+pub const RPMTAG_RECOMMENDFLAGS: u32 = 5048;
+pub const RPMTAG_SUGGESTNAME: u32 = 5049;
+pub const RPMTAG_SUGGESTVERSION: u32 = 5050;
+pub const RPMTAG_SUGGESTFLAGS: u32 = 5051;
+pub const RPMTAG_ENCODING: u32 = 5062;
+pub const RPMTAG_FILETRIGGERSCRIPTS: u32 = 5066;
+pub const RPMTAG_FILETRIGGERSCRIPTPROG: u32 = 5067;
+pub const RPMTAG_FILETRIGGERSCRIPTFLAGS: u32 = 5068;
+pub const RPMTAG_FILETRIGGERNAME: u32 = 5069;
+pub const RPMTAG_FILETRIGGERINDEX: u32 = 5070;
+pub const RPMTAG_FILETRIGGERVERSION: u32 = 5071;
+pub const RPMTAG_FILETRIGGERFLAGS: u32 = 5072;
+pub const RPMTAG_TRANSFILETRIGGERSCRIPTS: u32 = 5073;
+pub const RPMTAG_TRANSFILETRIGGERSCRIPTPROG: u32 = 5074;
+pub const RPMTAG_TRANSFILETRIGGERSCRIPTFLAGS: u32 = 5075;
+pub const RPMTAG_TRANSFILETRIGGERNAME: u32 = 5076;
+pub const RPMTAG_TRANSFILETRIGGERINDEX: u32 = 5077;
+pub const RPMTAG_TRANSFILETRIGGERVERSION: u32 = 5078;
+pub const RPMTAG_TRANSFILETRIGGERFLAGS: u32 = 5079;
+pub const RPMTAG_FILETRIGGERPRIORITIES: u32 = 5081;
+pub const RPMTAG_TRANSFILETRIGGERPRIORITIES: u32 = 5082;
+pub const RPMTAG_PAYLOADDIGEST: u32 = 5092;
+pub const RPMTAG_PAYLOADDIGESTALGO: u32 = 5093;
+
+/// Value of header entry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TagValue {
+    Null,
+    Char(Vec<u8>),
+    Int8(Vec<u8>),
+    Int16(Vec<u16>),
+    Int32(Vec<u32>),
+    Int64(Vec<u64>),
+    String(String),
+    Bin(Vec<u8>),
+    StringArray(Vec<String>),
+    I18nString(Vec<String>),
+}
+
+impl TagValue {
+    /// Type id of value in header index.
+    const fn type_id(&self) -> u32 {
+        match self {
+            Self::Null => 0,
+            Self::Char(_) => 1,
+            Self::Int8(_) => 2,
+            Self::Int16(_) => 3,
+            Self::Int32(_) => 4,
+            Self::Int64(_) => 5,
+            Self::String(_) => 6,
+            Self::Bin(_) => 7,
+            Self::StringArray(_) => 8,
+            Self::I18nString(_) => 9,
+        }
+    }
+
+    /// Number of items, or size in bytes of binary value.
+    fn count(&self) -> usize {
+        match self {
+            Self::Null | Self::String(_) => 1,
+            Self::Char(values) | Self::Int8(values) | Self::Bin(values) => values.len(),
+            Self::Int16(values) => values.len(),
+            Self::Int32(values) => values.len(),
+            Self::Int64(values) => values.len(),
+            Self::StringArray(values) | Self::I18nString(values) => values.len(),
+        }
+    }
+
+    /// Integer values are aligned to their size in data store.
+    const fn alignment(&self) -> usize {
+        match self {
+            Self::Int16(_) => mem::size_of::<u16>(),
+            Self::Int32(_) => mem::size_of::<u32>(),
+            Self::Int64(_) => mem::size_of::<u64>(),
+            _ => 1,
+        }
+    }
+
+    /// Append value to data store, in big endian.
+    fn write_to(&self, data: &mut Vec<u8>) {
+        match self {
+            Self::Null => {}
+            Self::String(value) => {
+                data.extend_from_slice(value.as_bytes());
+                data.push(0);
+            }
+            Self::StringArray(values) | Self::I18nString(values) => {
+                for value in values {
+                    data.extend_from_slice(value.as_bytes());
+                    data.push(0);
+                }
+            }
+            Self::Char(values) | Self::Int8(values) | Self::Bin(values) => {
+                data.extend_from_slice(values);
+            }
+            Self::Int16(values) => {
+                for value in values {
+                    data.extend_from_slice(&value.to_be_bytes());
+                }
+            }
+            Self::Int32(values) => {
+                for value in values {
+                    data.extend_from_slice(&value.to_be_bytes());
+                }
+            }
+            Self::Int64(values) => {
+                for value in values {
+                    data.extend_from_slice(&value.to_be_bytes());
+                }
+            }
+        }
+    }
+}
+
+/// Header structure, a list of tagged values.
+#[derive(Debug, Clone, Default)]
+pub struct Header {
+    entries: BTreeMap<u32, TagValue>,
+}
+
+impl Header {
+    /// Set value of `tag`, empty arrays are ignored as they are invalid in rpm.
+    pub fn insert(&mut self, tag: u32, value: TagValue) {
+        if value.count() > 0 {
+            self.entries.insert(tag, value);
+        }
+    }
+
+    /// Serialize header, with all entries in an immutable region of `region_tag`.
+    ///
+    /// Region entry is the first one in index, pointing to a trailer entry
+    /// at end of data store, whose offset is the negative size of index.
+    ///
+    /// # Errors
+    /// Returns error if header is too large.
+    pub fn to_bytes(&self, region_tag: u32) -> Result<Vec<u8>, Error> {
+        let mut index = Vec::new();
+        let mut data = Vec::new();
+        for (&tag, value) in &self.entries {
+            let alignment = value.alignment();
+            data.resize(data.len().next_multiple_of(alignment), 0);
+            write_index_entry(&mut index, tag, value.type_id(), data.len(), value.count())?;
+            value.write_to(&mut data);
+        }
+
+        // Region entry and its trailer.
+        let index_count = self.entries.len() + 1;
+        let mut region = Vec::with_capacity(REGION_ENTRY_SIZE);
+        write_index_entry(
+            &mut region,
+            region_tag,
+            BIN_TYPE,
+            data.len(),
+            REGION_ENTRY_SIZE,
+        )?;
+        let trailer_offset = -i32::try_from(index_count * REGION_ENTRY_SIZE).map_err(too_large)?;
+        data.extend_from_slice(&region_tag.to_be_bytes());
+        data.extend_from_slice(&BIN_TYPE.to_be_bytes());
+        data.extend_from_slice(&trailer_offset.to_be_bytes());
+        data.extend_from_slice(&be_size(REGION_ENTRY_SIZE)?);
+
+        let mut bytes = Vec::with_capacity(16 + region.len() + index.len() + data.len());
+        bytes.extend_from_slice(&HEADER_MAGIC);
+        bytes.extend_from_slice(&[0; 4]);
+        bytes.extend_from_slice(&be_size(index_count)?);
+        bytes.extend_from_slice(&be_size(data.len())?);
+        bytes.extend_from_slice(&region);
+        bytes.extend_from_slice(&index);
+        bytes.extend_from_slice(&data);
+        Ok(bytes)
+    }
+
+    /// Get string value of `tag`, or the first one of string array.
+    #[must_use]
+    pub fn get_string(&self, tag: u32) -> Option<&str> {
+        match self.entries.get(&tag)? {
+            TagValue::String(value) => Some(value),
+            TagValue::StringArray(values) | TagValue::I18nString(values) => {
+                values.first().map(String::as_str)
+            }
+            _ => None,
+        }
+    }
+
+    /// Get string array value of `tag`.
+    #[must_use]
+    pub fn get_strings(&self, tag: u32) -> Option<&[String]> {
+        match self.entries.get(&tag)? {
+            TagValue::StringArray(values) | TagValue::I18nString(values) => Some(values),
+            _ => None,
+        }
+    }
+
+    /// Get integer array value of `tag`, of any integer type.
+    #[must_use]
+    pub fn get_ints(&self, tag: u32) -> Option<Vec<u64>> {
+        match self.entries.get(&tag)? {
+            TagValue::Int8(values) | TagValue::Char(values) => {
+                Some(values.iter().map(|&v| u64::from(v)).collect())
+            }
+            TagValue::Int16(values) => Some(values.iter().map(|&v| u64::from(v)).collect()),
+            TagValue::Int32(values) => Some(values.iter().map(|&v| u64::from(v)).collect()),
+            TagValue::Int64(values) => Some(values.clone()),
+            _ => None,
+        }
+    }
+
+    /// Get the first integer value of `tag`.
+    #[must_use]
+    pub fn get_int(&self, tag: u32) -> Option<u64> {
+        self.get_ints(tag)?.first().copied()
+    }
+
+    /// Get paths of files in package, from `BASENAMES` and `DIRNAMES` tags,
+    /// or `OLDFILENAMES` tag of legacy packages.
+    #[must_use]
+    pub fn file_paths(&self) -> Vec<String> {
+        if let (Some(basenames), Some(dirnames), Some(dir_indexes)) = (
+            self.get_strings(RPMTAG_BASENAMES),
+            self.get_strings(RPMTAG_DIRNAMES),
+            self.get_ints(RPMTAG_DIRINDEXES),
+        ) {
+            basenames
+                .iter()
+                .zip(dir_indexes)
+                .map(|(basename, index)| {
+                    let dirname = usize::try_from(index)
+                        .ok()
+                        .and_then(|index| dirnames.get(index))
+                        .map_or("", String::as_str);
+                    format!("{dirname}{basename}")
+                })
+                .collect()
+        } else {
+            self.get_strings(RPMTAG_OLDFILENAMES)
+                .map(<[String]>::to_vec)
+                .unwrap_or_default()
+        }
+    }
+}
+
+/// Read lead, signature header and main header of rpm package.
+///
+/// Reader is left at start of payload.
+///
+/// # Errors
+/// Returns error if `reader` is not a valid rpm package.
+pub fn read_headers<R: Read>(reader: &mut R) -> Result<(Header, Header), Error> {
+    read_headers_with_range(reader).map(|(signature, header, _range)| (signature, header))
+}
+
+/// Read headers of rpm package, also returns byte range of main header in file.
+///
+/// The range is written as `<rpm:header-range>` in yum repository metadata.
+///
+/// # Errors
+/// Returns error if `reader` is not a valid rpm package.
+pub fn read_headers_with_range<R: Read>(
+    reader: &mut R,
+) -> Result<(Header, Header, Range<usize>), Error> {
+    let mut lead = [0_u8; LEAD_SIZE];
+    reader.read_exact(&mut lead)?;
+    if lead[..4] != LEAD_MAGIC {
+        return Err(Error::new(
+            ErrorKind::InvalidPackageError,
+            "Invalid magic number of rpm lead",
+        ));
+    }
+
+    let (signature, size) = read_header(reader)?;
+    // Signature header is aligned to 8 bytes.
+    let padding = (8 - size % 8) % 8;
+    let mut padding_bytes = vec![0_u8; padding];
+    reader.read_exact(&mut padding_bytes)?;
+
+    let (header, header_size) = read_header(reader)?;
+    let start = LEAD_SIZE + size + padding;
+    Ok((signature, header, start..start + header_size))
+}
+
+/// Read header structure from `reader`, returns header and its size in bytes.
+fn read_header<R: Read>(reader: &mut R) -> Result<(Header, usize), Error> {
+    let mut intro = [0_u8; 16];
+    reader.read_exact(&mut intro)?;
+    if intro[..4] != HEADER_MAGIC {
+        return Err(Error::new(
+            ErrorKind::InvalidPackageError,
+            "Invalid magic number of rpm header",
+        ));
+    }
+    let index_count = be_u32(&intro[8..]) as usize;
+    let data_size = be_u32(&intro[12..]) as usize;
+
+    let mut index = vec![0_u8; index_count * 16];
+    reader.read_exact(&mut index)?;
+    let mut data = vec![0_u8; data_size];
+    reader.read_exact(&mut data)?;
+
+    let mut entries = BTreeMap::new();
+    for entry in index.chunks_exact(16) {
+        let tag = be_u32(entry);
+        let type_id = be_u32(&entry[4..]);
+        let offset = be_u32(&entry[8..]) as usize;
+        let count = be_u32(&entry[12..]) as usize;
+        let value = parse_value(type_id, &data, offset, count).ok_or_else(|| {
+            Error::from_string(
+                ErrorKind::InvalidPackageError,
+                format!("Invalid rpm header entry, tag: {tag}, type: {type_id}"),
+            )
+        })?;
+        entries.insert(tag, value);
+    }
+
+    let size = intro.len() + index.len() + data.len();
+    Ok((Header { entries }, size))
+}
+
+fn too_large<E>(_err: E) -> Error {
+    Error::new(ErrorKind::InvalidPackageError, "Rpm header is too large")
+}
+
+/// Convert `size` to big endian `u32`.
+fn be_size(size: usize) -> Result<[u8; 4], Error> {
+    u32::try_from(size).map(u32::to_be_bytes).map_err(too_large)
+}
+
+fn write_index_entry(
+    index: &mut Vec<u8>,
+    tag: u32,
+    type_id: u32,
+    offset: usize,
+    count: usize,
+) -> Result<(), Error> {
+    index.extend_from_slice(&tag.to_be_bytes());
+    index.extend_from_slice(&type_id.to_be_bytes());
+    index.extend_from_slice(&be_size(offset)?);
+    index.extend_from_slice(&be_size(count)?);
+    Ok(())
+}
+
+fn be_u32(bytes: &[u8]) -> u32 {
+    u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+/// Read nul-terminated strings at `offset`.
+fn parse_strings(data: &[u8], offset: usize, count: usize) -> Option<Vec<String>> {
+    let mut values = Vec::with_capacity(count);
+    let mut pos = offset;
+    for _i in 0..count {
+        let len = data.get(pos..)?.iter().position(|&b| b == 0)?;
+        values.push(String::from_utf8_lossy(&data[pos..pos + len]).to_string());
+        pos += len + 1;
+    }
+    Some(values)
+}
+
+fn parse_value(type_id: u32, data: &[u8], offset: usize, count: usize) -> Option<TagValue> {
+    let slice = |size: usize| data.get(offset..offset.checked_add(size.checked_mul(count)?)?);
+    let value = match type_id {
+        0 => TagValue::Null,
+        1 => TagValue::Char(slice(1)?.to_vec()),
+        2 => TagValue::Int8(slice(1)?.to_vec()),
+        3 => TagValue::Int16(
+            slice(2)?
+                .chunks_exact(2)
+                .map(|b| u16::from_be_bytes([b[0], b[1]]))
+                .collect(),
+        ),
+        4 => TagValue::Int32(slice(4)?.chunks_exact(4).map(be_u32).collect()),
+        5 => TagValue::Int64(
+            slice(8)?
+                .chunks_exact(8)
+                .map(|b| u64::from_be_bytes([b[0], b[1], b[2], b[3], b[4], b[5], b[6], b[7]]))
+                .collect(),
+        ),
+        6 => TagValue::String(parse_strings(data, offset, 1)?.pop()?),
+        7 => TagValue::Bin(slice(1)?.to_vec()),
+        8 => TagValue::StringArray(parse_strings(data, offset, count)?),
+        9 => TagValue::I18nString(parse_strings(data, offset, count)?),
+        _ => return None,
+    };
+    Some(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_header() {
+        let mut header = Header::default();
+        header.insert(RPMTAG_NAME, TagValue::String("hello".to_owned()));
+        header.insert(
+            RPMTAG_SUMMARY,
+            TagValue::I18nString(vec!["Hello world".to_owned()]),
+        );
+        header.insert(RPMTAG_FILEMODES, TagValue::Int16(vec![0o100_755, 0o40_755]));
+        header.insert(RPMTAG_FILESIZES, TagValue::Int32(vec![42, 0]));
+        header.insert(RPMTAG_LONGFILESIZES, TagValue::Int64(vec![42, 0]));
+        header.insert(RPMTAG_BASENAMES, TagValue::StringArray(Vec::new()));
+
+        let bytes = header.to_bytes(RPMTAG_HEADERIMMUTABLE).unwrap();
+        let (read_header, size) = read_header(&mut bytes.as_slice()).unwrap();
+        assert_eq!(size, bytes.len());
+        assert_eq!(read_header.get_string(RPMTAG_NAME), Some("hello"));
+        assert_eq!(read_header.get_string(RPMTAG_SUMMARY), Some("Hello world"));
+        assert_eq!(
+            read_header.get_ints(RPMTAG_FILEMODES),
+            Some(vec![0o100_755, 0o40_755])
+        );
+        assert_eq!(read_header.get_ints(RPMTAG_FILESIZES), Some(vec![42, 0]));
+        assert_eq!(
+            read_header.get_ints(RPMTAG_LONGFILESIZES),
+            Some(vec![42, 0])
+        );
+        assert!(read_header.get_strings(RPMTAG_BASENAMES).is_none());
+
+        // Region entry is the first one, pointing to trailer at end of data.
+        assert_eq!(be_u32(&bytes[16..]), RPMTAG_HEADERIMMUTABLE);
+        let index_count = be_u32(&bytes[8..]) as usize;
+        let trailer = &bytes[bytes.len() - 16..];
+        assert_eq!(be_u32(trailer), RPMTAG_HEADERIMMUTABLE);
+        assert_eq!(
+            be_u32(&trailer[8..]).wrapping_neg() as usize,
+            index_count * 16
+        );
+    }
+}
@@ -4,6 +4,12 @@
 
 mod build;
 mod config;
+mod cpio;
+pub mod header;
+mod repo;
+mod scriptlet;
+mod writer;
 
 pub use build::build_rpm;
-pub use config::RpmConfig;
+pub use config::{RpmBackend, RpmConfig, RpmRelations};
+pub use repo::generate_yum_repo;
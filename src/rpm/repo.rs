@@ -0,0 +1,709 @@
+// Copyright (c) 2021 Xu Shaohua <shaohua@biofan.org>. All rights reserved.
+// Use of this source is governed by General Public License that can be found
+// in the LICENSE file.
+
+//! Yum repository metadata, in the same format as `createrepo_c`.
+//!
+//! See [repository metadata](https://github.com/rpm-software-management/createrepo_c/wiki).
+
+use std::collections::BTreeSet;
+use std::fmt::Write as _;
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+use time::OffsetDateTime;
+
+use crate::base::changelog::escape_xml;
+use crate::base::compress::CompressFormat;
+use crate::base::hash;
+use crate::error::{Error, ErrorKind};
+use crate::rpm::header::{
+    read_headers_with_range, Header, RPMSIGTAG_PAYLOADSIZE, RPMTAG_ARCH, RPMTAG_BUILDHOST,
+    RPMTAG_BUILDTIME, RPMTAG_CHANGELOGNAME, RPMTAG_CHANGELOGTEXT, RPMTAG_CHANGELOGTIME,
+    RPMTAG_CONFLICTFLAGS, RPMTAG_CONFLICTNAME, RPMTAG_CONFLICTVERSION, RPMTAG_DESCRIPTION,
+    RPMTAG_EPOCH, RPMTAG_FILEFLAGS, RPMTAG_FILEMODES, RPMTAG_GROUP, RPMTAG_LICENSE, RPMTAG_NAME,
+    RPMTAG_OBSOLETEFLAGS, RPMTAG_OBSOLETENAME, RPMTAG_OBSOLETEVERSION, RPMTAG_PACKAGER,
+    RPMTAG_PROVIDEFLAGS, RPMTAG_PROVIDENAME, RPMTAG_PROVIDEVERSION, RPMTAG_RECOMMENDFLAGS,
+    RPMTAG_RECOMMENDNAME, RPMTAG_RECOMMENDVERSION, RPMTAG_RELEASE, RPMTAG_REQUIREFLAGS,
+    RPMTAG_REQUIRENAME, RPMTAG_REQUIREVERSION, RPMTAG_SIZE, RPMTAG_SOURCERPM, RPMTAG_SUGGESTFLAGS,
+    RPMTAG_SUGGESTNAME, RPMTAG_SUGGESTVERSION, RPMTAG_SUMMARY, RPMTAG_URL, RPMTAG_VENDOR,
+    RPMTAG_VERSION,
+};
+
+const NS_COMMON: &str = "http://linux.duke.edu/metadata/common";
+const NS_FILELISTS: &str = "http://linux.duke.edu/metadata/filelists";
+const NS_OTHER: &str = "http://linux.duke.edu/metadata/other";
+const NS_REPO: &str = "http://linux.duke.edu/metadata/repo";
+const NS_RPM: &str = "http://linux.duke.edu/metadata/rpm";
+
+/// Comparison flags of dependency.
+const RPMSENSE_LESS: u64 = 1 << 1;
+const RPMSENSE_GREATER: u64 = 1 << 2;
+const RPMSENSE_EQUAL: u64 = 1 << 3;
+
+/// Dependency is required before install, written as `pre="1"`.
+const RPMSENSE_PREREQ: u64 = (1 << 5) | (1 << 6) | (1 << 7) | (1 << 9) | (1 << 10);
+
+/// File is not included in payload.
+const RPMFILE_GHOST: u64 = 1 << 6;
+
+/// Mask and value of directory in file mode.
+const S_IFMT: u64 = 0o170_000;
+const S_IFDIR: u64 = 0o040_000;
+
+/// Dependency tags written in `<format>` of primary metadata, with element names.
+const DEPENDENCY_TAGS: [(&str, u32, u32, u32); 6] = [
+    (
+        "provides",
+        RPMTAG_PROVIDENAME,
+        RPMTAG_PROVIDEFLAGS,
+        RPMTAG_PROVIDEVERSION,
+    ),
+    (
+        "requires",
+        RPMTAG_REQUIRENAME,
+        RPMTAG_REQUIREFLAGS,
+        RPMTAG_REQUIREVERSION,
+    ),
+    (
+        "conflicts",
+        RPMTAG_CONFLICTNAME,
+        RPMTAG_CONFLICTFLAGS,
+        RPMTAG_CONFLICTVERSION,
+    ),
+    (
+        "obsoletes",
+        RPMTAG_OBSOLETENAME,
+        RPMTAG_OBSOLETEFLAGS,
+        RPMTAG_OBSOLETEVERSION,
+    ),
+    (
+        "suggests",
+        RPMTAG_SUGGESTNAME,
+        RPMTAG_SUGGESTFLAGS,
+        RPMTAG_SUGGESTVERSION,
+    ),
+    (
+        "recommends",
+        RPMTAG_RECOMMENDNAME,
+        RPMTAG_RECOMMENDFLAGS,
+        RPMTAG_RECOMMENDVERSION,
+    ),
+];
+
+/// Rpm file in repository, with its headers.
+struct RepoPackage {
+    header: Header,
+
+    /// Path relative to root of repository.
+    location: String,
+
+    /// Sha256 checksum of rpm file, used as package id.
+    checksum: String,
+
+    file_size: u64,
+    file_time: u64,
+    archive_size: u64,
+    header_range: Range<usize>,
+}
+
+/// Generate a yum repository in `repo_dir` from rpm files in `rpm_dir`.
+///
+/// Rpm files are copied to `repo_dir`, with `repodata/repomd.xml`,
+/// `primary.xml.gz`, `filelists.xml.gz` and `other.xml.gz` generated
+/// from headers of rpm files. The repository can be used with dnf by
+/// `baseurl=file:///path/to/repo` and `gpgcheck=0`.
+///
+/// # Errors
+/// Returns error if failed to read rpm files or failed to write metadata files.
+pub fn generate_yum_repo(rpm_dir: &Path, repo_dir: &Path) -> Result<(), Error> {
+    log::info!(
+        "generate_yum_repo() rpm_dir: {}, repo_dir: {}",
+        rpm_dir.display(),
+        repo_dir.display()
+    );
+    fs::create_dir_all(repo_dir)?;
+    let same_dir = fs::canonicalize(rpm_dir)? == fs::canonicalize(repo_dir)?;
+
+    let pattern = format!("{}/*.rpm", rpm_dir.display());
+    let mut rpm_files = glob::glob(&pattern)?.collect::<Result<Vec<_>, _>>()?;
+    rpm_files.sort();
+    if rpm_files.is_empty() {
+        return Err(Error::from_string(
+            ErrorKind::FilesNotSet,
+            format!("No rpm file found in {}", rpm_dir.display()),
+        ));
+    }
+
+    let mut packages = Vec::with_capacity(rpm_files.len());
+    for rpm_file in &rpm_files {
+        let Some(filename) = rpm_file.file_name() else {
+            continue;
+        };
+        let filename = filename.to_string_lossy();
+        log::info!("generate_yum_repo() add {filename}");
+
+        let target_file = repo_dir.join(filename.as_ref());
+        if !same_dir {
+            let _ = fs::remove_file(&target_file);
+            if fs::hard_link(rpm_file, &target_file).is_err() {
+                fs::copy(rpm_file, &target_file)?;
+            }
+        }
+        packages.push(read_package(&target_file, &filename)?);
+    }
+
+    let repodata_dir = repo_dir.join("repodata");
+    fs::create_dir_all(&repodata_dir)?;
+    let mut metadata_files = Vec::new();
+    for (data_type, content) in [
+        ("primary", primary_xml(&packages)?),
+        ("filelists", filelists_xml(&packages)?),
+        ("other", other_xml(&packages)?),
+    ] {
+        let xml_file = repodata_dir.join(format!("{data_type}.xml"));
+        fs::write(&xml_file, content)?;
+        let gz_file = CompressFormat::Gzip.compress(&xml_file, None)?;
+        metadata_files.push(MetadataFile {
+            data_type,
+            open_checksum: hash::sha256sum(&xml_file)?,
+            open_size: fs::metadata(&xml_file)?.len(),
+            path: gz_file,
+        });
+        fs::remove_file(&xml_file)?;
+    }
+
+    let repomd = repomd_xml(&metadata_files)?;
+    fs::write(repodata_dir.join("repomd.xml"), repomd)?;
+    Ok(())
+}
+
+/// Read headers and file info of rpm package at `path`.
+fn read_package(path: &Path, location: &str) -> Result<RepoPackage, Error> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let (signature, header, header_range) = read_headers_with_range(&mut reader)?;
+    let metadata = fs::metadata(path)?;
+    let file_time = metadata
+        .modified()?
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default();
+
+    Ok(RepoPackage {
+        header,
+        location: location.to_owned(),
+        checksum: hash::sha256sum(path)?,
+        file_size: metadata.len(),
+        file_time,
+        archive_size: signature.get_int(RPMSIGTAG_PAYLOADSIZE).unwrap_or_default(),
+        header_range,
+    })
+}
+
+/// Append xml declaration and opening tag of root element.
+fn write_root(xml: &mut String, root: &str, namespaces: &str, count: usize) -> Result<(), Error> {
+    writeln!(xml, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(xml, r#"<{root} {namespaces} packages="{count}">"#)?;
+    Ok(())
+}
+
+/// Append opening tag of package element in filelists and other metadata.
+fn write_package_id(xml: &mut String, package: &RepoPackage) -> Result<(), Error> {
+    let header = &package.header;
+    writeln!(
+        xml,
+        r#"<package pkgid="{}" name="{}" arch="{}">"#,
+        package.checksum,
+        escape_xml(header.get_string(RPMTAG_NAME).unwrap_or_default()),
+        escape_xml(header.get_string(RPMTAG_ARCH).unwrap_or_default()),
+    )?;
+    write_version(xml, header)
+}
+
+fn write_version(xml: &mut String, header: &Header) -> Result<(), Error> {
+    writeln!(
+        xml,
+        r#"  <version epoch="{}" ver="{}" rel="{}"/>"#,
+        header.get_int(RPMTAG_EPOCH).unwrap_or_default(),
+        escape_xml(header.get_string(RPMTAG_VERSION).unwrap_or_default()),
+        escape_xml(header.get_string(RPMTAG_RELEASE).unwrap_or_default()),
+    )?;
+    Ok(())
+}
+
+/// Generate `primary.xml`, with metadata and dependencies of packages.
+fn primary_xml(packages: &[RepoPackage]) -> Result<String, Error> {
+    let mut xml = String::new();
+    let namespaces = format!(r#"xmlns="{NS_COMMON}" xmlns:rpm="{NS_RPM}""#);
+    write_root(&mut xml, "metadata", &namespaces, packages.len())?;
+    for package in packages {
+        let header = &package.header;
+        let text = |tag| escape_xml(header.get_string(tag).unwrap_or_default());
+        writeln!(xml, r#"<package type="rpm">"#)?;
+        writeln!(xml, "  <name>{}</name>", text(RPMTAG_NAME))?;
+        writeln!(xml, "  <arch>{}</arch>", text(RPMTAG_ARCH))?;
+        write_version(&mut xml, header)?;
+        writeln!(
+            xml,
+            r#"  <checksum type="sha256" pkgid="YES">{}</checksum>"#,
+            package.checksum
+        )?;
+        writeln!(xml, "  <summary>{}</summary>", text(RPMTAG_SUMMARY))?;
+        writeln!(
+            xml,
+            "  <description>{}</description>",
+            text(RPMTAG_DESCRIPTION)
+        )?;
+        writeln!(xml, "  <packager>{}</packager>", text(RPMTAG_PACKAGER))?;
+        writeln!(xml, "  <url>{}</url>", text(RPMTAG_URL))?;
+        writeln!(
+            xml,
+            r#"  <time file="{}" build="{}"/>"#,
+            package.file_time,
+            header.get_int(RPMTAG_BUILDTIME).unwrap_or_default()
+        )?;
+        writeln!(
+            xml,
+            r#"  <size package="{}" installed="{}" archive="{}"/>"#,
+            package.file_size,
+            header.get_int(RPMTAG_SIZE).unwrap_or_default(),
+            package.archive_size
+        )?;
+        writeln!(
+            xml,
+            r#"  <location href="{}"/>"#,
+            escape_xml(&package.location)
+        )?;
+
+        writeln!(xml, "  <format>")?;
+        for (name, tag) in [
+            ("license", RPMTAG_LICENSE),
+            ("vendor", RPMTAG_VENDOR),
+            ("group", RPMTAG_GROUP),
+            ("buildhost", RPMTAG_BUILDHOST),
+            ("sourcerpm", RPMTAG_SOURCERPM),
+        ] {
+            writeln!(xml, "    <rpm:{name}>{}</rpm:{name}>", text(tag))?;
+        }
+        writeln!(
+            xml,
+            r#"    <rpm:header-range start="{}" end="{}"/>"#,
+            package.header_range.start, package.header_range.end
+        )?;
+        for (name, name_tag, flags_tag, version_tag) in DEPENDENCY_TAGS {
+            write_dependencies(&mut xml, header, name, name_tag, flags_tag, version_tag)?;
+        }
+        for (path, file_type) in package_files(header) {
+            if is_primary_file(&path) {
+                write_file(&mut xml, "    ", &path, file_type)?;
+            }
+        }
+        writeln!(xml, "  </format>\n</package>")?;
+    }
+    writeln!(xml, "</metadata>")?;
+    Ok(xml)
+}
+
+/// Append `<rpm:{element}>` list of dependencies, if not empty.
+///
+/// Features of rpm itself like `rpmlib(PayloadIsXz)` are skipped.
+fn write_dependencies(
+    xml: &mut String,
+    header: &Header,
+    element: &str,
+    name_tag: u32,
+    flags_tag: u32,
+    version_tag: u32,
+) -> Result<(), Error> {
+    let names = header.get_strings(name_tag).unwrap_or_default();
+    let flags = header.get_ints(flags_tag).unwrap_or_default();
+    let versions = header.get_strings(version_tag).unwrap_or_default();
+
+    let mut entries = BTreeSet::new();
+    let mut content = String::new();
+    for (index, name) in names.iter().enumerate() {
+        if name.starts_with("rpmlib(") {
+            continue;
+        }
+        let flags = flags.get(index).copied().unwrap_or_default();
+        let version = versions.get(index).map_or("", String::as_str);
+        let mut entry = format!(r#"name="{}""#, escape_xml(name));
+        if let Some(op) = flags_name(flags).filter(|_op| !version.is_empty()) {
+            let (epoch, ver, rel) = split_evr(version);
+            let _ = write!(
+                entry,
+                r#" flags="{op}" epoch="{epoch}" ver="{}""#,
+                escape_xml(ver)
+            );
+            if let Some(rel) = rel {
+                let _ = write!(entry, r#" rel="{}""#, escape_xml(rel));
+            }
+        }
+        if element == "requires" && flags & RPMSENSE_PREREQ != 0 {
+            entry.push_str(r#" pre="1""#);
+        }
+        if entries.insert(entry.clone()) {
+            writeln!(content, "      <rpm:entry {entry}/>")?;
+        }
+    }
+
+    if !content.is_empty() {
+        writeln!(xml, "    <rpm:{element}>\n{content}    </rpm:{element}>")?;
+    }
+    Ok(())
+}
+
+/// Get name of comparison operator in `flags`, like `GE`.
+const fn flags_name(flags: u64) -> Option<&'static str> {
+    match flags & (RPMSENSE_LESS | RPMSENSE_GREATER | RPMSENSE_EQUAL) {
+        RPMSENSE_LESS => Some("LT"),
+        RPMSENSE_GREATER => Some("GT"),
+        RPMSENSE_EQUAL => Some("EQ"),
+        flags if flags == RPMSENSE_LESS | RPMSENSE_EQUAL => Some("LE"),
+        flags if flags == RPMSENSE_GREATER | RPMSENSE_EQUAL => Some("GE"),
+        _ => None,
+    }
+}
+
+/// Split version like `1:2.0-3` into epoch, version and release.
+fn split_evr(evr: &str) -> (&str, &str, Option<&str>) {
+    let (epoch, version) = match evr.split_once(':') {
+        Some((epoch, version)) if epoch.bytes().all(|b| b.is_ascii_digit()) => (epoch, version),
+        _ => ("0", evr),
+    };
+    match version.rsplit_once('-') {
+        Some((version, release)) => (epoch, version, Some(release)),
+        None => (epoch, version, None),
+    }
+}
+
+/// Get paths of files in package, with their types in file list.
+fn package_files(header: &Header) -> Vec<(String, Option<&'static str>)> {
+    let modes = header.get_ints(RPMTAG_FILEMODES).unwrap_or_default();
+    let flags = header.get_ints(RPMTAG_FILEFLAGS).unwrap_or_default();
+    header
+        .file_paths()
+        .into_iter()
+        .enumerate()
+        .map(|(index, path)| {
+            let file_type = if flags.get(index).is_some_and(|f| f & RPMFILE_GHOST != 0) {
+                Some("ghost")
+            } else if modes.get(index).is_some_and(|m| m & S_IFMT == S_IFDIR) {
+                Some("dir")
+            } else {
+                None
+            };
+            (path, file_type)
+        })
+        .collect()
+}
+
+/// Files listed in primary metadata, which are likely to be required by path.
+fn is_primary_file(path: &str) -> bool {
+    path.starts_with("/etc/") || path.contains("bin/") || path == "/usr/lib/sendmail"
+}
+
+fn write_file(
+    xml: &mut String,
+    indent: &str,
+    path: &str,
+    file_type: Option<&str>,
+) -> Result<(), Error> {
+    let path = escape_xml(path);
+    match file_type {
+        Some(file_type) => writeln!(xml, r#"{indent}<file type="{file_type}">{path}</file>"#)?,
+        None => writeln!(xml, "{indent}<file>{path}</file>")?,
+    }
+    Ok(())
+}
+
+/// Generate `filelists.xml`, with all files of packages.
+fn filelists_xml(packages: &[RepoPackage]) -> Result<String, Error> {
+    let mut xml = String::new();
+    let namespaces = format!(r#"xmlns="{NS_FILELISTS}""#);
+    write_root(&mut xml, "filelists", &namespaces, packages.len())?;
+    for package in packages {
+        write_package_id(&mut xml, package)?;
+        for (path, file_type) in package_files(&package.header) {
+            write_file(&mut xml, "  ", &path, file_type)?;
+        }
+        writeln!(xml, "</package>")?;
+    }
+    writeln!(xml, "</filelists>")?;
+    Ok(xml)
+}
+
+/// Generate `other.xml`, with changelog of packages.
+fn other_xml(packages: &[RepoPackage]) -> Result<String, Error> {
+    let mut xml = String::new();
+    let namespaces = format!(r#"xmlns="{NS_OTHER}""#);
+    write_root(&mut xml, "otherdata", &namespaces, packages.len())?;
+    for package in packages {
+        let header = &package.header;
+        write_package_id(&mut xml, package)?;
+        let times = header.get_ints(RPMTAG_CHANGELOGTIME).unwrap_or_default();
+        let names = header.get_strings(RPMTAG_CHANGELOGNAME).unwrap_or_default();
+        let texts = header.get_strings(RPMTAG_CHANGELOGTEXT).unwrap_or_default();
+        for ((time, name), text) in times.iter().zip(names).zip(texts) {
+            writeln!(
+                xml,
+                r#"  <changelog author="{}" date="{time}">{}</changelog>"#,
+                escape_xml(name),
+                escape_xml(text)
+            )?;
+        }
+        writeln!(xml, "</package>")?;
+    }
+    writeln!(xml, "</otherdata>")?;
+    Ok(xml)
+}
+
+/// Compressed metadata file, listed in `repomd.xml`.
+struct MetadataFile {
+    data_type: &'static str,
+    path: PathBuf,
+
+    /// Checksum and size of uncompressed file.
+    open_checksum: String,
+    open_size: u64,
+}
+
+/// Generate `repomd.xml`, with checksums of metadata files.
+fn repomd_xml(metadata_files: &[MetadataFile]) -> Result<String, Error> {
+    let timestamp = OffsetDateTime::now_utc().unix_timestamp();
+    let mut xml = String::new();
+    writeln!(xml, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(xml, r#"<repomd xmlns="{NS_REPO}" xmlns:rpm="{NS_RPM}">"#)?;
+    writeln!(xml, "  <revision>{timestamp}</revision>")?;
+    for file in metadata_files {
+        let filename = file
+            .path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        writeln!(xml, r#"  <data type="{}">"#, file.data_type)?;
+        writeln!(
+            xml,
+            r#"    <checksum type="sha256">{}</checksum>"#,
+            hash::sha256sum(&file.path)?
+        )?;
+        writeln!(
+            xml,
+            r#"    <open-checksum type="sha256">{}</open-checksum>"#,
+            file.open_checksum
+        )?;
+        writeln!(xml, r#"    <location href="repodata/{filename}"/>"#)?;
+        writeln!(xml, "    <timestamp>{timestamp}</timestamp>")?;
+        writeln!(xml, "    <size>{}</size>", fs::metadata(&file.path)?.len())?;
+        writeln!(xml, "    <open-size>{}</open-size>", file.open_size)?;
+        writeln!(xml, "  </data>")?;
+    }
+    writeln!(xml, "</repomd>")?;
+    Ok(xml)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::io::{Read, Seek, SeekFrom};
+    use time::{Date, Month};
+
+    use super::*;
+    use crate::base::changelog::Release;
+    use crate::base::fileset::FileAttr;
+    use crate::base::payload::{EntryKind, PayloadEntry};
+    use crate::base::utils;
+    use crate::base::{Arch, Metadata, PackageArch};
+    use crate::rpm::scriptlet::Scriptlets;
+    use crate::rpm::writer::{write_rpm, RpmDependency, RpmPackage};
+
+    fn build_test_rpm(dir: &Path, rpm_file: &Path) {
+        let source = dir.join("hello");
+        fs::write(&source, "#!/bin/sh\necho hello\n").unwrap();
+        let mut files = BTreeMap::new();
+        files.insert(
+            "/usr/bin/hello".to_owned(),
+            PayloadEntry {
+                kind: EntryKind::File(source),
+                attr: FileAttr {
+                    mode: Some(0o755),
+                    ..FileAttr::default()
+                },
+            },
+        );
+        files.insert(
+            "/usr/share/hello".to_owned(),
+            PayloadEntry {
+                kind: EntryKind::Directory,
+                attr: FileAttr::default(),
+            },
+        );
+        let package = RpmPackage {
+            metadata: Metadata {
+                name: "hello".to_owned(),
+                version: "1.0.0".to_owned(),
+                short_description: Some("Hello app".to_owned()),
+                description: "Hello app\nLong <description>.".to_owned(),
+                author: "Hello <hello@example.com>".to_owned(),
+                ..Default::default()
+            },
+            release: "2".to_owned(),
+            epoch: Some(1),
+            vendor: None,
+            group: "Unspecified".to_owned(),
+            arch: PackageArch::Target(Arch::X86_64),
+            source_rpm: "hello-1.0.0-2.src.rpm".to_owned(),
+            dependencies: vec![(
+                "Requires",
+                RpmDependency {
+                    name: "glibc".to_owned(),
+                    flags: (RPMSENSE_GREATER | RPMSENSE_EQUAL) as u32,
+                    version: "2.36".to_owned(),
+                },
+            )],
+            scriptlets: Scriptlets::default(),
+            files,
+            directives: BTreeMap::new(),
+            changelog: vec![Release {
+                version: "1.0.0".to_owned(),
+                date: Date::from_calendar_date(2024, Month::May, 1).unwrap(),
+                changes: vec!["First release".to_owned()],
+            }],
+        };
+        write_rpm(&package, CompressFormat::Xz, None, rpm_file).unwrap();
+    }
+
+    /// Read decompressed content of metadata file.
+    fn read_gz(path: &Path) -> String {
+        let mut content = String::new();
+        CompressFormat::Gzip
+            .decoder(File::open(path).unwrap())
+            .unwrap()
+            .read_to_string(&mut content)
+            .unwrap();
+        content
+    }
+
+    /// Get text of `element` in `<data>` of `data_type` in `repomd.xml`.
+    fn repomd_value<'a>(repomd: &'a str, data_type: &str, element: &str) -> &'a str {
+        let data = repomd
+            .split_once(&format!(r#"<data type="{data_type}">"#))
+            .unwrap()
+            .1;
+        let data = data.split_once("</data>").unwrap().0;
+        let value = data.split_once(&format!("<{element}")).unwrap().1;
+        let value = value.split_once('>').unwrap().1;
+        value.split_once(&format!("</{element}>")).unwrap().0
+    }
+
+    /// Get value of attribute `name` in first element starting with `prefix`.
+    fn attr_value<'a>(xml: &'a str, prefix: &str, name: &str) -> &'a str {
+        let element = xml.split_once(prefix).unwrap().1;
+        let element = element.split_once('>').unwrap().0;
+        let value = element.split_once(&format!(r#"{name}=""#)).unwrap().1;
+        value.split_once('"').unwrap().0
+    }
+
+    #[test]
+    fn test_generate_yum_repo() {
+        let dir = utils::test_dir("yum-repo");
+        let rpm_dir = dir.join("rpms");
+        let repo_dir = dir.join("yum");
+        fs::create_dir_all(&rpm_dir).unwrap();
+        let rpm_name = "hello-1.0.0-2.x86_64.rpm";
+        build_test_rpm(&dir, &rpm_dir.join(rpm_name));
+        generate_yum_repo(&rpm_dir, &repo_dir).unwrap();
+
+        let rpm_file = repo_dir.join(rpm_name);
+        let rpm_content = fs::read(&rpm_file).unwrap();
+        let repodata_dir = repo_dir.join("repodata");
+        let primary = read_gz(&repodata_dir.join("primary.xml.gz"));
+        assert!(primary.contains(r#"<metadata xmlns="http://linux.duke.edu/metadata/common""#));
+        assert!(primary.contains(r#"packages="1">"#));
+        assert!(primary.contains("<name>hello</name>"));
+        assert!(primary.contains("<arch>x86_64</arch>"));
+        assert!(primary.contains(r#"<version epoch="1" ver="1.0.0" rel="2"/>"#));
+        assert!(primary.contains(&format!(
+            r#"<checksum type="sha256" pkgid="YES">{}</checksum>"#,
+            hash::sha256sum(&rpm_file).unwrap()
+        )));
+        assert!(primary.contains("Long &lt;description&gt;."));
+        assert!(primary.contains(&format!(r#"<location href="{rpm_name}"/>"#)));
+        assert_eq!(
+            attr_value(&primary, "<size ", "package"),
+            rpm_content.len().to_string()
+        );
+        assert!(primary.contains(
+            r#"<rpm:entry name="glibc" flags="GE" epoch="0" ver="2.36"/>"#
+        ));
+        assert!(!primary.contains("rpmlib("));
+        assert!(primary.contains("<file>/usr/bin/hello</file>"));
+        assert!(!primary.contains("/usr/share/hello"));
+
+        // Header range points to main header, followed by payload.
+        let start: usize = attr_value(&primary, "<rpm:header-range ", "start")
+            .parse()
+            .unwrap();
+        let end: u64 = attr_value(&primary, "<rpm:header-range ", "end")
+            .parse()
+            .unwrap();
+        assert_eq!(&rpm_content[start..start + 4], [0x8e, 0xad, 0xe8, 0x01]);
+        let mut reader = File::open(&rpm_file).unwrap();
+        reader.seek(SeekFrom::Start(end)).unwrap();
+        let mut magic = [0; 6];
+        CompressFormat::Xz
+            .decoder(reader)
+            .unwrap()
+            .read_exact(&mut magic)
+            .unwrap();
+        assert_eq!(&magic, b"070701");
+
+        let filelists = read_gz(&repodata_dir.join("filelists.xml.gz"));
+        assert!(filelists.contains(r#"<package pkgid=""#));
+        assert!(filelists.contains("  <file>/usr/bin/hello</file>"));
+        assert!(filelists.contains(r#"  <file type="dir">/usr/share/hello</file>"#));
+
+        let other = read_gz(&repodata_dir.join("other.xml.gz"));
+        assert!(other.contains(r#"<otherdata xmlns="http://linux.duke.edu/metadata/other""#));
+        assert!(other.contains("- First release</changelog>"));
+
+        // Checksums and sizes of metadata files match files on disk.
+        let repomd = fs::read_to_string(repodata_dir.join("repomd.xml")).unwrap();
+        for data_type in ["primary", "filelists", "other"] {
+            let gz_file = repodata_dir.join(format!("{data_type}.xml.gz"));
+            let content = read_gz(&gz_file);
+            assert_eq!(
+                repomd_value(&repomd, data_type, "checksum"),
+                hash::sha256sum(&gz_file).unwrap()
+            );
+            assert_eq!(
+                repomd_value(&repomd, data_type, "size"),
+                fs::metadata(&gz_file).unwrap().len().to_string()
+            );
+            assert_eq!(
+                repomd_value(&repomd, data_type, "open-size"),
+                content.len().to_string()
+            );
+            let open_file = dir.join(format!("{data_type}.xml"));
+            fs::write(&open_file, &content).unwrap();
+            assert_eq!(
+                repomd_value(&repomd, data_type, "open-checksum"),
+                hash::sha256sum(&open_file).unwrap()
+            );
+            assert!(repomd.contains(&format!(
+                r#"<location href="repodata/{data_type}.xml.gz"/>"#
+            )));
+        }
+    }
+
+    #[test]
+    fn test_split_evr() {
+        assert_eq!(split_evr("1:2.0-3.fc40"), ("1", "2.0", Some("3.fc40")));
+        assert_eq!(split_evr("2.36"), ("0", "2.36", None));
+        assert_eq!(flags_name(RPMSENSE_GREATER | RPMSENSE_EQUAL), Some("GE"));
+        assert_eq!(flags_name(RPMSENSE_EQUAL | (1 << 24)), Some("EQ"));
+        assert_eq!(flags_name(0), None);
+    }
+}
@@ -0,0 +1,190 @@
+// Copyright (c) 2021 Xu Shaohua <shaohua@biofan.org>. All rights reserved.
+// Use of this source is governed by General Public License that can be found
+// in the LICENSE file.
+
+//! Scriptlets of rpm package, read from config and merged with generated snippets.
+
+use std::path::Path;
+
+use crate::base::script;
+use crate::base::systemd;
+use crate::base::{PackageArch, PlatformTarget};
+use crate::config::{Config, LinuxConfig};
+use crate::error::{Error, ErrorKind};
+use crate::rpm::config::{FileTriggerKind, RpmScript};
+
+/// Interpreter of scriptlets if not set.
+pub const DEFAULT_INTERPRETER: &str = "/bin/sh";
+
+/// Name of lua interpreter embedded in rpm.
+pub const LUA_INTERPRETER: &str = "<lua>";
+
+/// Content of scriptlet, with its interpreter.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Scriptlet {
+    pub content: String,
+    pub interpreter: String,
+}
+
+/// File trigger with content of its scriptlet.
+#[derive(Debug, Clone)]
+pub struct TriggerScriptlet {
+    pub kind: FileTriggerKind,
+    pub paths: Vec<String>,
+    pub priority: u32,
+    pub scriptlet: Scriptlet,
+}
+
+/// Scriptlets and file triggers of a package.
+#[derive(Debug, Default, Clone)]
+pub struct Scriptlets {
+    /// Scriptlets with their names in spec file, like `post`.
+    pub scriptlets: Vec<(&'static str, Scriptlet)>,
+
+    pub file_triggers: Vec<TriggerScriptlet>,
+}
+
+/// Read scriptlets and file triggers of main package,
+/// with snippets to manage systemd units inserted.
+///
+/// If `spec` is true, `%` in scripts is escaped to prevent macro expansion,
+/// and systemd macros are used in snippets.
+///
+/// # Errors
+/// Returns error if failed to read scripts, or snippets can not be inserted.
+pub fn read_scriptlets(
+    conf: &Config,
+    linux_conf: &LinuxConfig,
+    arch: PackageArch,
+    spec: bool,
+) -> Result<Scriptlets, Error> {
+    log::info!("read_scriptlets()");
+    let rpm_conf = &linux_conf.rpm;
+    let [post, preun, postun] = systemd::rpm_scriptlets(&linux_conf.systemd_units, spec)?;
+    let snippets = [("post", post), ("preun", preun), ("postun", postun)];
+
+    let mut scriptlets = Scriptlets::default();
+    for (name, script) in rpm_conf.scriptlets() {
+        let scriptlet = script
+            .map(|script| read_script(script, conf, arch, spec))
+            .transpose()?;
+        let snippet = snippets
+            .iter()
+            .find(|(snippet_name, _snippet)| *snippet_name == name)
+            .map_or("", |(_name, snippet)| snippet.as_str());
+        if let Some(scriptlet) = insert_snippet(name, scriptlet, snippet)? {
+            scriptlets.scriptlets.push((name, scriptlet));
+        }
+    }
+    for trigger in &rpm_conf.file_triggers {
+        scriptlets.file_triggers.push(TriggerScriptlet {
+            kind: trigger.kind,
+            paths: trigger.paths.clone(),
+            priority: trigger.priority,
+            scriptlet: read_script(&trigger.script, conf, arch, spec)?,
+        });
+    }
+    Ok(scriptlets)
+}
+
+fn read_script(
+    script: &RpmScript,
+    conf: &Config,
+    arch: PackageArch,
+    spec: bool,
+) -> Result<Scriptlet, Error> {
+    let mut content = script.script.expand(conf, arch, PlatformTarget::Rpm)?;
+    if !content.ends_with('\n') {
+        content.push('\n');
+    }
+    let interpreter = script
+        .interpreter
+        .clone()
+        .or_else(|| shebang_program(&content))
+        .unwrap_or_else(|| DEFAULT_INTERPRETER.to_owned());
+    if spec {
+        content = content.replace('%', "%%");
+    }
+    Ok(Scriptlet {
+        content,
+        interpreter,
+    })
+}
+
+/// Get program in shebang line, like `/bin/bash` in `#!/bin/bash -e`.
+fn shebang_program(content: &str) -> Option<String> {
+    content
+        .lines()
+        .next()?
+        .strip_prefix("#!")?
+        .split_whitespace()
+        .next()
+        .map(ToOwned::to_owned)
+}
+
+/// Insert generated shell `snippet` into `scriptlet`, or create a new one.
+fn insert_snippet(
+    name: &str,
+    scriptlet: Option<Scriptlet>,
+    snippet: &str,
+) -> Result<Option<Scriptlet>, Error> {
+    if snippet.is_empty() {
+        return Ok(scriptlet);
+    }
+    match scriptlet {
+        None => Ok(Some(Scriptlet {
+            content: snippet.to_owned(),
+            interpreter: DEFAULT_INTERPRETER.to_owned(),
+        })),
+        Some(scriptlet) if is_shell(&scriptlet.interpreter) => Ok(Some(Scriptlet {
+            content: script::insert_snippet(Some(scriptlet.content), snippet),
+            interpreter: scriptlet.interpreter,
+        })),
+        Some(scriptlet) => Err(Error::from_string(
+            ErrorKind::InvalidConfError,
+            format!(
+                "Systemd snippets can not be inserted into `{name}` scriptlet with interpreter `{}`",
+                scriptlet.interpreter
+            ),
+        )),
+    }
+}
+
+fn is_shell(interpreter: &str) -> bool {
+    Path::new(interpreter)
+        .file_name()
+        .is_some_and(|name| ["sh", "bash", "dash"].iter().any(|shell| name == *shell))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_insert_snippet() {
+        assert_eq!(
+            shebang_program("#!/bin/bash -e\necho hi\n").as_deref(),
+            Some("/bin/bash")
+        );
+        assert_eq!(shebang_program("echo hi\n"), None);
+
+        let scriptlet = insert_snippet("post", None, "systemctl daemon-reload\n").unwrap();
+        assert_eq!(
+            scriptlet,
+            Some(Scriptlet {
+                content: "systemctl daemon-reload\n".to_owned(),
+                interpreter: DEFAULT_INTERPRETER.to_owned(),
+            })
+        );
+
+        let lua = Scriptlet {
+            content: "print('hi')\n".to_owned(),
+            interpreter: LUA_INTERPRETER.to_owned(),
+        };
+        assert_eq!(
+            insert_snippet("post", Some(lua.clone()), "").unwrap(),
+            Some(lua.clone())
+        );
+        assert!(insert_snippet("post", Some(lua), "systemctl daemon-reload\n").is_err());
+    }
+}
@@ -0,0 +1,1177 @@
+// Copyright (c) 2021 Xu Shaohua <shaohua@biofan.org>. All rights reserved.
+// Use of this source is governed by General Public License that can be found
+// in the LICENSE file.
+
+//! Native writer of binary rpm packages, without `rpmbuild`.
+//!
+//! A package consists of lead, signature header, main header and cpio payload.
+//! See [rpm file format](https://rpm-software-management.github.io/rpm/manual/format.html).
+
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+#[cfg(not(target_os = "windows"))]
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::base::changelog::Release;
+use crate::base::compress::CompressFormat;
+use crate::base::payload::{EntryKind, PayloadEntry};
+use crate::base::relation::{Relations, VersionOp};
+use crate::base::utils;
+use crate::base::{Metadata, PackageArch};
+use crate::error::{Error, ErrorKind};
+use crate::rpm::build::package_arch_name;
+use crate::rpm::config::FileTriggerKind;
+use crate::rpm::cpio::{CpioEntry, CpioWriter};
+use crate::rpm::header::{
+    Header, TagValue, LEAD_MAGIC, LEAD_SIZE, RPMSIGTAG_MD5, RPMSIGTAG_PAYLOADSIZE,
+    RPMSIGTAG_SHA256, RPMSIGTAG_SIZE, RPMTAG_ARCH, RPMTAG_BASENAMES, RPMTAG_BUILDHOST,
+    RPMTAG_BUILDTIME, RPMTAG_CHANGELOGNAME, RPMTAG_CHANGELOGTEXT, RPMTAG_CHANGELOGTIME,
+    RPMTAG_CONFLICTFLAGS, RPMTAG_CONFLICTNAME, RPMTAG_CONFLICTVERSION, RPMTAG_DESCRIPTION,
+    RPMTAG_DIRINDEXES, RPMTAG_DIRNAMES, RPMTAG_ENCODING, RPMTAG_EPOCH, RPMTAG_FILEDEVICES,
+    RPMTAG_FILEDIGESTALGO, RPMTAG_FILEDIGESTS, RPMTAG_FILEFLAGS, RPMTAG_FILEGROUPNAME,
+    RPMTAG_FILEINODES, RPMTAG_FILELANGS, RPMTAG_FILELINKTOS, RPMTAG_FILEMODES, RPMTAG_FILEMTIMES,
+    RPMTAG_FILERDEVS, RPMTAG_FILESIZES, RPMTAG_FILETRIGGERFLAGS, RPMTAG_FILETRIGGERINDEX,
+    RPMTAG_FILETRIGGERNAME, RPMTAG_FILETRIGGERPRIORITIES, RPMTAG_FILETRIGGERSCRIPTFLAGS,
+    RPMTAG_FILETRIGGERSCRIPTPROG, RPMTAG_FILETRIGGERSCRIPTS, RPMTAG_FILETRIGGERVERSION,
+    RPMTAG_FILEUSERNAME, RPMTAG_FILEVERIFYFLAGS, RPMTAG_GROUP, RPMTAG_HEADERI18NTABLE,
+    RPMTAG_HEADERIMMUTABLE, RPMTAG_HEADERSIGNATURES, RPMTAG_LICENSE, RPMTAG_NAME,
+    RPMTAG_OBSOLETEFLAGS, RPMTAG_OBSOLETENAME, RPMTAG_OBSOLETEVERSION, RPMTAG_OS, RPMTAG_PACKAGER,
+    RPMTAG_PAYLOADCOMPRESSOR, RPMTAG_PAYLOADDIGEST, RPMTAG_PAYLOADDIGESTALGO, RPMTAG_PAYLOADFLAGS,
+    RPMTAG_PAYLOADFORMAT, RPMTAG_POSTIN, RPMTAG_POSTINPROG, RPMTAG_POSTTRANS, RPMTAG_POSTTRANSPROG,
+    RPMTAG_POSTUN, RPMTAG_POSTUNPROG, RPMTAG_PREIN, RPMTAG_PREINPROG, RPMTAG_PRETRANS,
+    RPMTAG_PRETRANSPROG, RPMTAG_PREUN, RPMTAG_PREUNPROG, RPMTAG_PROVIDEFLAGS, RPMTAG_PROVIDENAME,
+    RPMTAG_PROVIDEVERSION, RPMTAG_RECOMMENDFLAGS, RPMTAG_RECOMMENDNAME, RPMTAG_RECOMMENDVERSION,
+    RPMTAG_RELEASE, RPMTAG_REQUIREFLAGS, RPMTAG_REQUIRENAME, RPMTAG_REQUIREVERSION, RPMTAG_SIZE,
+    RPMTAG_SOURCERPM, RPMTAG_SUGGESTFLAGS, RPMTAG_SUGGESTNAME, RPMTAG_SUGGESTVERSION,
+    RPMTAG_SUMMARY, RPMTAG_TRANSFILETRIGGERFLAGS, RPMTAG_TRANSFILETRIGGERINDEX,
+    RPMTAG_TRANSFILETRIGGERNAME, RPMTAG_TRANSFILETRIGGERPRIORITIES,
+    RPMTAG_TRANSFILETRIGGERSCRIPTFLAGS, RPMTAG_TRANSFILETRIGGERSCRIPTPROG,
+    RPMTAG_TRANSFILETRIGGERSCRIPTS, RPMTAG_TRANSFILETRIGGERVERSION, RPMTAG_URL, RPMTAG_VENDOR,
+    RPMTAG_VERSION,
+};
+use crate::rpm::scriptlet::{Scriptlets, TriggerScriptlet, LUA_INTERPRETER};
+
+/// Dependency is a feature of rpm itself, like `rpmlib(PayloadIsXz)`.
+const RPMSENSE_RPMLIB: u32 = 1 << 24;
+
+/// Dependency is interpreter of a scriptlet.
+const RPMSENSE_INTERP: u32 = 1 << 8;
+const RPMSENSE_POSTTRANS: u32 = 1 << 5;
+const RPMSENSE_PRETRANS: u32 = 1 << 7;
+const RPMSENSE_SCRIPT_PRE: u32 = 1 << 9;
+const RPMSENSE_SCRIPT_POST: u32 = 1 << 10;
+const RPMSENSE_SCRIPT_PREUN: u32 = 1 << 11;
+const RPMSENSE_SCRIPT_POSTUN: u32 = 1 << 12;
+
+/// Types of trigger.
+const RPMSENSE_TRIGGERIN: u32 = 1 << 16;
+const RPMSENSE_TRIGGERUN: u32 = 1 << 17;
+const RPMSENSE_TRIGGERPOSTUN: u32 = 1 << 18;
+
+/// Flags of files in `FILEFLAGS` tag.
+const RPMFILE_CONFIG: u32 = 1 << 0;
+const RPMFILE_DOC: u32 = 1 << 1;
+const RPMFILE_NOREPLACE: u32 = 1 << 4;
+const RPMFILE_LICENSE: u32 = 1 << 7;
+
+/// Id of SHA-256 in `FILEDIGESTALGO` and `PAYLOADDIGESTALGO` tags.
+const PGPHASHALGO_SHA256: u32 = 8;
+
+const S_IFDIR: u32 = 0o040_000;
+const S_IFREG: u32 = 0o100_000;
+const S_IFLNK: u32 = 0o120_000;
+
+/// Scriptlet names in spec file, with tags of script and interpreter in header,
+/// and sense flags of interpreter dependency.
+const SCRIPTLET_TAGS: [(&str, u32, u32, u32); 6] = [
+    (
+        "pretrans",
+        RPMTAG_PRETRANS,
+        RPMTAG_PRETRANSPROG,
+        RPMSENSE_PRETRANS,
+    ),
+    ("pre", RPMTAG_PREIN, RPMTAG_PREINPROG, RPMSENSE_SCRIPT_PRE),
+    (
+        "post",
+        RPMTAG_POSTIN,
+        RPMTAG_POSTINPROG,
+        RPMSENSE_SCRIPT_POST,
+    ),
+    (
+        "preun",
+        RPMTAG_PREUN,
+        RPMTAG_PREUNPROG,
+        RPMSENSE_SCRIPT_PREUN,
+    ),
+    (
+        "postun",
+        RPMTAG_POSTUN,
+        RPMTAG_POSTUNPROG,
+        RPMSENSE_SCRIPT_POSTUN,
+    ),
+    (
+        "posttrans",
+        RPMTAG_POSTTRANS,
+        RPMTAG_POSTTRANSPROG,
+        RPMSENSE_POSTTRANS,
+    ),
+];
+
+/// Tags of scripts, interpreters, script flags, priorities, names, indexes,
+/// versions and flags of file triggers.
+const FILE_TRIGGER_TAGS: [u32; 8] = [
+    RPMTAG_FILETRIGGERSCRIPTS,
+    RPMTAG_FILETRIGGERSCRIPTPROG,
+    RPMTAG_FILETRIGGERSCRIPTFLAGS,
+    RPMTAG_FILETRIGGERPRIORITIES,
+    RPMTAG_FILETRIGGERNAME,
+    RPMTAG_FILETRIGGERINDEX,
+    RPMTAG_FILETRIGGERVERSION,
+    RPMTAG_FILETRIGGERFLAGS,
+];
+
+/// Same as `FILE_TRIGGER_TAGS`, of transaction file triggers.
+const TRANS_FILE_TRIGGER_TAGS: [u32; 8] = [
+    RPMTAG_TRANSFILETRIGGERSCRIPTS,
+    RPMTAG_TRANSFILETRIGGERSCRIPTPROG,
+    RPMTAG_TRANSFILETRIGGERSCRIPTFLAGS,
+    RPMTAG_TRANSFILETRIGGERPRIORITIES,
+    RPMTAG_TRANSFILETRIGGERNAME,
+    RPMTAG_TRANSFILETRIGGERINDEX,
+    RPMTAG_TRANSFILETRIGGERVERSION,
+    RPMTAG_TRANSFILETRIGGERFLAGS,
+];
+
+/// Dependency tags in spec file, with tags of name, flags and version in header.
+const DEPENDENCY_TAGS: [(&str, u32, u32, u32); 6] = [
+    (
+        "Requires",
+        RPMTAG_REQUIRENAME,
+        RPMTAG_REQUIREFLAGS,
+        RPMTAG_REQUIREVERSION,
+    ),
+    (
+        "Recommends",
+        RPMTAG_RECOMMENDNAME,
+        RPMTAG_RECOMMENDFLAGS,
+        RPMTAG_RECOMMENDVERSION,
+    ),
+    (
+        "Suggests",
+        RPMTAG_SUGGESTNAME,
+        RPMTAG_SUGGESTFLAGS,
+        RPMTAG_SUGGESTVERSION,
+    ),
+    (
+        "Obsoletes",
+        RPMTAG_OBSOLETENAME,
+        RPMTAG_OBSOLETEFLAGS,
+        RPMTAG_OBSOLETEVERSION,
+    ),
+    (
+        "Conflicts",
+        RPMTAG_CONFLICTNAME,
+        RPMTAG_CONFLICTFLAGS,
+        RPMTAG_CONFLICTVERSION,
+    ),
+    (
+        "Provides",
+        RPMTAG_PROVIDENAME,
+        RPMTAG_PROVIDEFLAGS,
+        RPMTAG_PROVIDEVERSION,
+    ),
+];
+
+/// Dependency entry in rpm header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RpmDependency {
+    pub name: String,
+
+    /// Sense flags, like `RPMSENSE_LESS | RPMSENSE_EQUAL`.
+    pub flags: u32,
+
+    /// Version constraint, empty if not set.
+    pub version: String,
+}
+
+impl RpmDependency {
+    /// Dependency on exact `version` of `name`, like `hello = 1.0-1`.
+    #[must_use]
+    pub fn exact(name: &str, version: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            flags: VersionOp::Equal.rpm_flags(),
+            version: version.to_owned(),
+        }
+    }
+
+    /// Get dependencies of `relations` applied to `arch`.
+    #[must_use]
+    pub fn from_relations(relations: &Relations, arch: PackageArch) -> Vec<Self> {
+        relations
+            .to_rpm_deps(arch)
+            .into_iter()
+            .map(|(name, version)| match version {
+                Some((op, version)) => Self {
+                    name,
+                    flags: op.rpm_flags(),
+                    version,
+                },
+                None => Self {
+                    name,
+                    flags: 0,
+                    version: String::new(),
+                },
+            })
+            .collect()
+    }
+
+    /// Dependency on a feature of rpm, which is checked by rpm itself.
+    fn rpmlib(feature: &str, version: &str) -> Self {
+        Self {
+            name: format!("rpmlib({feature})"),
+            flags: RPMSENSE_RPMLIB | VersionOp::LessEqual.rpm_flags(),
+            version: version.to_owned(),
+        }
+    }
+}
+
+/// Special kinds of files, marked with directives in `%files` section.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileDirective {
+    /// Config file, which is not replaced on upgrade if modified locally.
+    Config,
+    Doc,
+    License,
+}
+
+impl FileDirective {
+    /// Get directive in spec file.
+    #[must_use]
+    pub const fn spec_directive(self) -> &'static str {
+        match self {
+            Self::Config => "%config(noreplace)",
+            Self::Doc => "%doc",
+            Self::License => "%license",
+        }
+    }
+
+    const fn flags(self) -> u32 {
+        match self {
+            Self::Config => RPMFILE_CONFIG | RPMFILE_NOREPLACE,
+            Self::Doc => RPMFILE_DOC,
+            Self::License => RPMFILE_LICENSE,
+        }
+    }
+}
+
+/// Binary package to be written.
+#[derive(Debug, Clone)]
+pub struct RpmPackage {
+    pub metadata: Metadata,
+
+    /// Release with distribution tag, like `1.el9`.
+    pub release: String,
+
+    pub epoch: Option<u32>,
+    pub vendor: Option<String>,
+    pub group: String,
+    pub arch: PackageArch,
+
+    /// File name of source package, binary packages are recognized by this tag.
+    pub source_rpm: String,
+
+    /// Dependencies with their spec tags, like `Requires`.
+    pub dependencies: Vec<(&'static str, RpmDependency)>,
+
+    pub scriptlets: Scriptlets,
+
+    /// Entries keyed by absolute path in package, like `/usr/bin/hello`.
+    ///
+    /// Parent directories are not owned by package unless they are listed.
+    pub files: BTreeMap<String, PayloadEntry>,
+
+    /// Directives of files, keyed by path in `files`.
+    pub directives: BTreeMap<String, FileDirective>,
+
+    /// Changelog entries, newest first.
+    pub changelog: Vec<Release>,
+}
+
+impl RpmPackage {
+    /// Get name with version and release, like `hello-1.0.0-1`.
+    fn full_name(&self) -> String {
+        format!(
+            "{}-{}-{}",
+            self.metadata.name, self.metadata.version, self.release
+        )
+    }
+
+    /// Get `version` with epoch and release, like `1:1.0.0-1`.
+    fn full_version(&self, version: &str) -> String {
+        self.epoch.map_or_else(
+            || format!("{version}-{}", self.release),
+            |epoch| format!("{epoch}:{version}-{}", self.release),
+        )
+    }
+}
+
+/// Attributes of file in main header, collected while writing payload.
+struct FileInfo {
+    mode: u32,
+    size: u32,
+    mtime: u32,
+
+    /// Hex string of SHA-256 hash, empty if not a regular file.
+    digest: String,
+
+    /// Target of symbolic link, empty if not a link.
+    link_to: String,
+}
+
+/// Compressed payload saved in a temporary file.
+struct PayloadInfo {
+    path: PathBuf,
+
+    /// Size of compressed payload.
+    size: u64,
+
+    /// Size of cpio archive before compression.
+    archive_size: usize,
+
+    /// Hex string of SHA-256 hash of compressed payload.
+    digest: String,
+}
+
+/// Write `package` to `rpm_file`.
+///
+/// Compressed payload is written to a temporary file next to `rpm_file` first.
+///
+/// # Errors
+/// Returns error if failed to read files of package or failed to write rpm file.
+pub fn write_rpm(
+    package: &RpmPackage,
+    compression: CompressFormat,
+    level: Option<u32>,
+    rpm_file: &Path,
+) -> Result<(), Error> {
+    log::info!("write_rpm() rpm file: {}", rpm_file.display());
+
+    let mut payload_file = rpm_file.as_os_str().to_owned();
+    payload_file.push(".payload");
+    let (files, payload) = write_payload(package, compression, level, payload_file.into())?;
+    let header = main_header(package, &files, &payload, compression, level)?
+        .to_bytes(RPMTAG_HEADERIMMUTABLE)?;
+
+    // Md5 hash of header and payload is checked by legacy rpm.
+    let mut md5_context = md5::Context::new();
+    md5_context.consume(&header);
+    io::copy(&mut File::open(&payload.path)?, &mut md5_context)?;
+    let signature = signature_header(&header, &payload, &md5_context.compute())?
+        .to_bytes(RPMTAG_HEADERSIGNATURES)?;
+
+    let mut writer = BufWriter::new(File::create(rpm_file)?);
+    writer.write_all(&lead(package))?;
+    writer.write_all(&signature)?;
+    // Signature header is aligned to 8 bytes.
+    let padding = signature.len().next_multiple_of(8) - signature.len();
+    writer.write_all(&[0; 7][..padding])?;
+    writer.write_all(&header)?;
+    io::copy(&mut File::open(&payload.path)?, &mut writer)?;
+    writer.flush()?;
+    fs::remove_file(&payload.path)?;
+
+    Ok(())
+}
+
+/// Lead of package, only magic number and name are used by modern rpm.
+fn lead(package: &RpmPackage) -> [u8; LEAD_SIZE] {
+    const NAME_SIZE: usize = 66;
+    const OS_LINUX: u16 = 1;
+    const SIGNATURE_TYPE_HEADER: u16 = 5;
+
+    let mut lead = [0_u8; LEAD_SIZE];
+    lead[..4].copy_from_slice(&LEAD_MAGIC);
+    // Version 3.0, binary package type and arch number are zeros.
+    lead[4] = 3;
+    let name = package.full_name();
+    let name_len = name.len().min(NAME_SIZE - 1);
+    lead[10..10 + name_len].copy_from_slice(&name.as_bytes()[..name_len]);
+    lead[76..78].copy_from_slice(&OS_LINUX.to_be_bytes());
+    lead[78..80].copy_from_slice(&SIGNATURE_TYPE_HEADER.to_be_bytes());
+    lead
+}
+
+fn signature_header(
+    header: &[u8],
+    payload: &PayloadInfo,
+    md5_digest: &md5::Digest,
+) -> Result<Header, Error> {
+    let package_size =
+        u32::try_from(header.len() as u64 + payload.size).map_err(|_err| too_large("Package"))?;
+    let archive_size = u32::try_from(payload.archive_size).map_err(|_err| too_large("Payload"))?;
+
+    let mut signature = Header::default();
+    signature.insert(
+        RPMSIGTAG_SHA256,
+        TagValue::String(hex::encode(Sha256::digest(header))),
+    );
+    signature.insert(RPMSIGTAG_SIZE, TagValue::Int32(vec![package_size]));
+    signature.insert(RPMSIGTAG_MD5, TagValue::Bin(md5_digest.0.to_vec()));
+    signature.insert(RPMSIGTAG_PAYLOADSIZE, TagValue::Int32(vec![archive_size]));
+    Ok(signature)
+}
+
+/// Write files of `package` to compressed cpio archive at `path`.
+///
+/// File names are prefixed with `.`, like `./usr/bin/hello`.
+fn write_payload(
+    package: &RpmPackage,
+    compression: CompressFormat,
+    level: Option<u32>,
+    path: PathBuf,
+) -> Result<(Vec<FileInfo>, PayloadInfo), Error> {
+    let writer = Sha256Writer {
+        inner: BufWriter::new(File::create(&path)?),
+        digest: Sha256::new(),
+        size: 0,
+    };
+    let mut cpio = CpioWriter::new(compression.encoder(writer, level)?);
+    let entry_mtime = u32::try_from(utils::entry_mtime()).map_err(|_err| too_large("Timestamp"))?;
+
+    let mut files = Vec::with_capacity(package.files.len());
+    for (index, (file_path, entry)) in package.files.iter().enumerate() {
+        let name = format!(".{file_path}");
+        let inode = u32::try_from(index + 1).map_err(|_err| too_large("File list"))?;
+        let info = match &entry.kind {
+            EntryKind::Directory => {
+                let info = FileInfo {
+                    mode: S_IFDIR | entry.attr.mode.unwrap_or(0o755),
+                    size: 0,
+                    mtime: entry_mtime,
+                    digest: String::new(),
+                    link_to: String::new(),
+                };
+                let cpio_entry = new_cpio_entry(&name, inode, 2, &info);
+                cpio.append(&cpio_entry, &mut io::empty())?;
+                info
+            }
+            EntryKind::Symlink(target) => {
+                let link_to = target.to_string_lossy().to_string();
+                let info = FileInfo {
+                    mode: S_IFLNK | 0o777,
+                    size: u32::try_from(link_to.len()).map_err(|_err| too_large(&name))?,
+                    mtime: entry_mtime,
+                    digest: String::new(),
+                    link_to,
+                };
+                let cpio_entry = new_cpio_entry(&name, inode, 1, &info);
+                cpio.append(&cpio_entry, &mut info.link_to.as_bytes())?;
+                info
+            }
+            EntryKind::File(source) => {
+                let fd = File::open(source).map_err(|err| {
+                    Error::from_string(
+                        ErrorKind::IoError,
+                        format!("Failed to open {}, err: {err}", source.display()),
+                    )
+                })?;
+                let metadata = fd.metadata()?;
+                #[cfg(not(target_os = "windows"))]
+                let source_mode = metadata.permissions().mode() & 0o7777;
+                #[cfg(target_os = "windows")]
+                let source_mode = 0o644;
+                let mut info = FileInfo {
+                    mode: S_IFREG | entry.attr.mode.unwrap_or(source_mode),
+                    size: u32::try_from(metadata.len()).map_err(|_err| too_large(&name))?,
+                    mtime: unix_time(metadata.modified()?)?,
+                    digest: String::new(),
+                    link_to: String::new(),
+                };
+                let cpio_entry = new_cpio_entry(&name, inode, 1, &info);
+                let mut reader = Sha256Reader {
+                    inner: BufReader::new(fd),
+                    digest: Sha256::new(),
+                };
+                cpio.append(&cpio_entry, &mut reader)?;
+                info.digest = hex::encode(reader.digest.finalize());
+                info
+            }
+        };
+        files.push(info);
+    }
+
+    let (encoder, archive_size) = cpio.finish()?;
+    let mut writer = encoder.finish()?;
+    writer.inner.flush()?;
+    let payload = PayloadInfo {
+        path,
+        size: writer.size,
+        archive_size,
+        digest: hex::encode(writer.digest.finalize()),
+    };
+    Ok((files, payload))
+}
+
+const fn new_cpio_entry<'a>(
+    name: &'a str,
+    inode: u32,
+    nlink: u32,
+    info: &FileInfo,
+) -> CpioEntry<'a> {
+    CpioEntry {
+        name,
+        inode,
+        mode: info.mode,
+        nlink,
+        mtime: info.mtime,
+        size: info.size,
+    }
+}
+
+fn main_header(
+    package: &RpmPackage,
+    files: &[FileInfo],
+    payload: &PayloadInfo,
+    compression: CompressFormat,
+    level: Option<u32>,
+) -> Result<Header, Error> {
+    let mut header = Header::default();
+    insert_metadata(&mut header, package, files)?;
+    insert_changelog(&mut header, package)?;
+    insert_files(&mut header, package, files)?;
+    insert_dependencies(&mut header, package, compression);
+
+    insert_scriptlets(&mut header, &package.scriptlets)?;
+
+    let level = level.unwrap_or_else(|| compression.default_level());
+    header.insert(RPMTAG_PAYLOADFORMAT, TagValue::String("cpio".to_owned()));
+    header.insert(
+        RPMTAG_PAYLOADCOMPRESSOR,
+        TagValue::String(compression.to_string()),
+    );
+    header.insert(RPMTAG_PAYLOADFLAGS, TagValue::String(level.to_string()));
+    header.insert(
+        RPMTAG_PAYLOADDIGEST,
+        TagValue::StringArray(vec![payload.digest.clone()]),
+    );
+    header.insert(
+        RPMTAG_PAYLOADDIGESTALGO,
+        TagValue::Int32(vec![PGPHASHALGO_SHA256]),
+    );
+    Ok(header)
+}
+
+fn insert_metadata(
+    header: &mut Header,
+    package: &RpmPackage,
+    files: &[FileInfo],
+) -> Result<(), Error> {
+    let metadata = &package.metadata;
+    let i18n_string = |value: String| TagValue::I18nString(vec![value]);
+    let installed_size: u64 = files.iter().map(|file| u64::from(file.size)).sum();
+    let installed_size =
+        u32::try_from(installed_size).map_err(|_err| too_large("Installed size"))?;
+
+    header.insert(
+        RPMTAG_HEADERI18NTABLE,
+        TagValue::StringArray(vec!["C".to_owned()]),
+    );
+    header.insert(RPMTAG_NAME, TagValue::String(metadata.name.clone()));
+    header.insert(RPMTAG_VERSION, TagValue::String(metadata.version.clone()));
+    header.insert(RPMTAG_RELEASE, TagValue::String(package.release.clone()));
+    if let Some(epoch) = package.epoch {
+        header.insert(RPMTAG_EPOCH, TagValue::Int32(vec![epoch]));
+    }
+    header.insert(RPMTAG_SUMMARY, i18n_string(metadata.summary()));
+    header.insert(
+        RPMTAG_DESCRIPTION,
+        i18n_string(
+            metadata
+                .long_description()
+                .unwrap_or_else(|| metadata.summary()),
+        ),
+    );
+    header.insert(
+        RPMTAG_BUILDTIME,
+        TagValue::Int32(vec![unix_time(SystemTime::now())?]),
+    );
+    header.insert(RPMTAG_BUILDHOST, TagValue::String(build_host()));
+    header.insert(RPMTAG_SIZE, TagValue::Int32(vec![installed_size]));
+    header.insert(RPMTAG_LICENSE, TagValue::String(metadata.license.clone()));
+    header.insert(RPMTAG_PACKAGER, TagValue::String(metadata.author.clone()));
+    if let Some(vendor) = &package.vendor {
+        header.insert(RPMTAG_VENDOR, TagValue::String(vendor.clone()));
+    }
+    header.insert(RPMTAG_GROUP, i18n_string(package.group.clone()));
+    header.insert(RPMTAG_URL, TagValue::String(metadata.homepage.clone()));
+    header.insert(RPMTAG_OS, TagValue::String("linux".to_owned()));
+    header.insert(
+        RPMTAG_ARCH,
+        TagValue::String(package_arch_name(package.arch).to_owned()),
+    );
+    header.insert(
+        RPMTAG_SOURCERPM,
+        TagValue::String(package.source_rpm.clone()),
+    );
+    header.insert(RPMTAG_ENCODING, TagValue::String("utf-8".to_owned()));
+    Ok(())
+}
+
+fn insert_changelog(header: &mut Header, package: &RpmPackage) -> Result<(), Error> {
+    let changelog = &package.changelog;
+    let times = changelog
+        .iter()
+        .map(|release| {
+            u32::try_from(release.rpm_time()).map_err(|_err| too_large("Changelog time"))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    header.insert(RPMTAG_CHANGELOGTIME, TagValue::Int32(times));
+    header.insert(
+        RPMTAG_CHANGELOGNAME,
+        TagValue::StringArray(
+            changelog
+                .iter()
+                .map(|release| {
+                    release.rpm_name(&package.metadata.author, |version| {
+                        package.full_version(version)
+                    })
+                })
+                .collect(),
+        ),
+    );
+    header.insert(
+        RPMTAG_CHANGELOGTEXT,
+        TagValue::StringArray(changelog.iter().map(Release::rpm_text).collect()),
+    );
+    Ok(())
+}
+
+/// Insert file list, with paths split into directory names and base names.
+fn insert_files(
+    header: &mut Header,
+    package: &RpmPackage,
+    files: &[FileInfo],
+) -> Result<(), Error> {
+    let mut dir_names: Vec<String> = Vec::new();
+    let mut dir_name_indexes: HashMap<String, u32> = HashMap::new();
+    let mut dir_indexes = Vec::with_capacity(files.len());
+    let mut base_names = Vec::with_capacity(files.len());
+    for path in package.files.keys() {
+        let (dir_name, base_name) = path.rsplit_once('/').unwrap_or(("", path));
+        let dir_name = format!("{dir_name}/");
+        let index = if let Some(index) = dir_name_indexes.get(&dir_name) {
+            *index
+        } else {
+            let index = u32::try_from(dir_names.len()).map_err(|_err| too_large("File list"))?;
+            dir_name_indexes.insert(dir_name.clone(), index);
+            dir_names.push(dir_name);
+            index
+        };
+        dir_indexes.push(index);
+        base_names.push(base_name.to_owned());
+    }
+    header.insert(RPMTAG_DIRINDEXES, TagValue::Int32(dir_indexes));
+    header.insert(RPMTAG_BASENAMES, TagValue::StringArray(base_names));
+    header.insert(RPMTAG_DIRNAMES, TagValue::StringArray(dir_names));
+
+    let attrs = package.files.values().map(|entry| &entry.attr);
+    header.insert(
+        RPMTAG_FILEUSERNAME,
+        TagValue::StringArray(attrs.clone().map(|attr| attr.owner.clone()).collect()),
+    );
+    header.insert(
+        RPMTAG_FILEGROUPNAME,
+        TagValue::StringArray(attrs.map(|attr| attr.group.clone()).collect()),
+    );
+
+    // File modes are 16 bits in rpm header.
+    #[allow(clippy::cast_possible_truncation)]
+    let modes = files.iter().map(|file| file.mode as u16).collect();
+    header.insert(RPMTAG_FILEMODES, TagValue::Int16(modes));
+    header.insert(
+        RPMTAG_FILESIZES,
+        TagValue::Int32(files.iter().map(|file| file.size).collect()),
+    );
+    header.insert(
+        RPMTAG_FILEMTIMES,
+        TagValue::Int32(files.iter().map(|file| file.mtime).collect()),
+    );
+    header.insert(
+        RPMTAG_FILEDIGESTS,
+        TagValue::StringArray(files.iter().map(|file| file.digest.clone()).collect()),
+    );
+    header.insert(
+        RPMTAG_FILELINKTOS,
+        TagValue::StringArray(files.iter().map(|file| file.link_to.clone()).collect()),
+    );
+
+    let count = files.len();
+    let inodes = (1..=count)
+        .map(|inode| u32::try_from(inode).map_err(|_err| too_large("File list")))
+        .collect::<Result<Vec<_>, _>>()?;
+    header.insert(RPMTAG_FILEINODES, TagValue::Int32(inodes));
+    header.insert(RPMTAG_FILEDEVICES, TagValue::Int32(vec![1; count]));
+    header.insert(RPMTAG_FILERDEVS, TagValue::Int16(vec![0; count]));
+    let flags = package
+        .files
+        .keys()
+        .map(|path| {
+            package
+                .directives
+                .get(path)
+                .map_or(0, |directive| directive.flags())
+        })
+        .collect();
+    header.insert(RPMTAG_FILEFLAGS, TagValue::Int32(flags));
+    header.insert(
+        RPMTAG_FILEVERIFYFLAGS,
+        TagValue::Int32(vec![u32::MAX; count]),
+    );
+    header.insert(
+        RPMTAG_FILELANGS,
+        TagValue::StringArray(vec![String::new(); count]),
+    );
+    header.insert(
+        RPMTAG_FILEDIGESTALGO,
+        TagValue::Int32(vec![PGPHASHALGO_SHA256]),
+    );
+    Ok(())
+}
+
+/// Insert dependencies of package, with rpmlib features used by this package
+/// and interpreter of scriptlets.
+fn insert_dependencies(header: &mut Header, package: &RpmPackage, compression: CompressFormat) {
+    let metadata = &package.metadata;
+    let mut dependencies = package.dependencies.clone();
+    dependencies.push((
+        "Provides",
+        RpmDependency::exact(&metadata.name, &package.full_version(&metadata.version)),
+    ));
+    let scriptlets = &package.scriptlets;
+    let interpreters = scriptlets
+        .scriptlets
+        .iter()
+        .map(|(name, scriptlet)| {
+            let flags = SCRIPTLET_TAGS
+                .iter()
+                .find(|(tag_name, ..)| tag_name == name)
+                .map_or(0, |(.., flags)| *flags);
+            (&scriptlet.interpreter, flags)
+        })
+        .chain(
+            scriptlets
+                .file_triggers
+                .iter()
+                .map(|trigger| (&trigger.scriptlet.interpreter, 0)),
+        );
+    let mut uses_lua = false;
+    for (interpreter, flags) in interpreters {
+        if interpreter == LUA_INTERPRETER {
+            uses_lua = true;
+            continue;
+        }
+        let dependency = RpmDependency {
+            name: interpreter.clone(),
+            flags: RPMSENSE_INTERP | flags,
+            version: String::new(),
+        };
+        // File triggers may share the same interpreter.
+        if !dependencies.contains(&("Requires", dependency.clone())) {
+            dependencies.push(("Requires", dependency));
+        }
+    }
+
+    let mut features = vec![
+        ("CompressedFileNames", "3.0.4-1"),
+        ("FileDigests", "4.6.0-1"),
+        ("PayloadFilesHavePrefix", "4.0-1"),
+    ];
+    match compression {
+        CompressFormat::Xz => features.push(("PayloadIsXz", "5.2-1")),
+        CompressFormat::Zstd => features.push(("PayloadIsZstd", "5.4.18-1")),
+        CompressFormat::None | CompressFormat::Gzip => {}
+    }
+    if dependencies
+        .iter()
+        .any(|(_tag, dependency)| dependency.name.starts_with('('))
+    {
+        features.push(("RichDependencies", "4.12.0-1"));
+    }
+    if uses_lua {
+        features.push(("BuiltinLuaScripts", "4.2.2-1"));
+    }
+    let file_triggers = &scriptlets.file_triggers;
+    if file_triggers
+        .iter()
+        .any(|trigger| !trigger.kind.is_transaction())
+    {
+        features.push(("FileTriggers", "4.13.0-1"));
+    }
+    if file_triggers
+        .iter()
+        .any(|trigger| trigger.kind.is_transaction())
+    {
+        features.push(("TransactionFileTriggers", "4.13.0-1"));
+    }
+    dependencies.extend(
+        features
+            .into_iter()
+            .map(|(feature, version)| ("Requires", RpmDependency::rpmlib(feature, version))),
+    );
+
+    for (spec_tag, name_tag, flags_tag, version_tag) in DEPENDENCY_TAGS {
+        let dependencies: Vec<&RpmDependency> = dependencies
+            .iter()
+            .filter(|(tag, _dependency)| *tag == spec_tag)
+            .map(|(_tag, dependency)| dependency)
+            .collect();
+        header.insert(
+            name_tag,
+            TagValue::StringArray(dependencies.iter().map(|dep| dep.name.clone()).collect()),
+        );
+        header.insert(
+            flags_tag,
+            TagValue::Int32(dependencies.iter().map(|dep| dep.flags).collect()),
+        );
+        header.insert(
+            version_tag,
+            TagValue::StringArray(dependencies.iter().map(|dep| dep.version.clone()).collect()),
+        );
+    }
+}
+
+/// Insert scriptlets and file triggers.
+///
+/// Each file trigger has a script, and a condition for each of its paths.
+fn insert_scriptlets(header: &mut Header, scriptlets: &Scriptlets) -> Result<(), Error> {
+    for (name, scriptlet) in &scriptlets.scriptlets {
+        if let Some((_name, tag, prog_tag, _flags)) = SCRIPTLET_TAGS
+            .iter()
+            .find(|(tag_name, ..)| tag_name == name)
+        {
+            header.insert(*tag, TagValue::String(scriptlet.content.clone()));
+            header.insert(
+                *prog_tag,
+                TagValue::StringArray(vec![scriptlet.interpreter.clone()]),
+            );
+        }
+    }
+
+    for (transaction, tags) in [(false, FILE_TRIGGER_TAGS), (true, TRANS_FILE_TRIGGER_TAGS)] {
+        let [scripts_tag, progs_tag, script_flags_tag, priorities_tag, names_tag, indexes_tag, versions_tag, flags_tag] =
+            tags;
+        let triggers: Vec<&TriggerScriptlet> = scriptlets
+            .file_triggers
+            .iter()
+            .filter(|trigger| trigger.kind.is_transaction() == transaction)
+            .collect();
+        let mut names = Vec::new();
+        let mut indexes = Vec::new();
+        let mut flags = Vec::new();
+        for (index, trigger) in triggers.iter().enumerate() {
+            let index = u32::try_from(index).map_err(|_err| too_large("File triggers"))?;
+            let trigger_flags = match trigger.kind {
+                FileTriggerKind::FileTriggerIn | FileTriggerKind::TransFileTriggerIn => {
+                    RPMSENSE_TRIGGERIN
+                }
+                FileTriggerKind::FileTriggerUn | FileTriggerKind::TransFileTriggerUn => {
+                    RPMSENSE_TRIGGERUN
+                }
+                FileTriggerKind::FileTriggerPostUn | FileTriggerKind::TransFileTriggerPostUn => {
+                    RPMSENSE_TRIGGERPOSTUN
+                }
+            };
+            for path in &trigger.paths {
+                names.push(path.clone());
+                indexes.push(index);
+                flags.push(trigger_flags);
+            }
+        }
+        header.insert(
+            scripts_tag,
+            TagValue::StringArray(
+                triggers
+                    .iter()
+                    .map(|trigger| trigger.scriptlet.content.clone())
+                    .collect(),
+            ),
+        );
+        header.insert(
+            progs_tag,
+            TagValue::StringArray(
+                triggers
+                    .iter()
+                    .map(|trigger| trigger.scriptlet.interpreter.clone())
+                    .collect(),
+            ),
+        );
+        header.insert(script_flags_tag, TagValue::Int32(vec![0; triggers.len()]));
+        header.insert(
+            priorities_tag,
+            TagValue::Int32(triggers.iter().map(|trigger| trigger.priority).collect()),
+        );
+        header.insert(
+            versions_tag,
+            TagValue::StringArray(vec![String::new(); names.len()]),
+        );
+        header.insert(names_tag, TagValue::StringArray(names));
+        header.insert(indexes_tag, TagValue::Int32(indexes));
+        header.insert(flags_tag, TagValue::Int32(flags));
+    }
+    Ok(())
+}
+
+fn too_large(name: &str) -> Error {
+    Error::from_string(
+        ErrorKind::InvalidPackageError,
+        format!("{name} is too large for rpm package"),
+    )
+}
+
+/// Get seconds since unix epoch, which is 32 bits in rpm header.
+///
+/// Timestamp is clamped to `SOURCE_DATE_EPOCH` if it is set.
+fn unix_time(time: SystemTime) -> Result<u32, Error> {
+    let secs = utils::clamp_mtime(time.duration_since(UNIX_EPOCH)?.as_secs());
+    u32::try_from(secs).map_err(|_err| too_large("Timestamp"))
+}
+
+/// Get hostname of build host.
+fn build_host() -> String {
+    fs::read_to_string("/proc/sys/kernel/hostname")
+        .or_else(|_err| fs::read_to_string("/etc/hostname"))
+        .map(|name| name.trim().to_owned())
+        .ok()
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| "localhost".to_owned())
+}
+
+/// Writer which computes SHA-256 hash and size of data written through it.
+struct Sha256Writer<W: Write> {
+    inner: W,
+    digest: Sha256,
+    size: u64,
+}
+
+impl<W: Write> Write for Sha256Writer<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n_written = self.inner.write(buf)?;
+        self.digest.update(&buf[..n_written]);
+        self.size += n_written as u64;
+        Ok(n_written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Reader which computes SHA-256 hash of data read through it.
+struct Sha256Reader<R: Read> {
+    inner: R,
+    digest: Sha256,
+}
+
+impl<R: Read> Read for Sha256Reader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n_read = self.inner.read(buf)?;
+        self.digest.update(&buf[..n_read]);
+        Ok(n_read)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::base::fileset::FileAttr;
+    use crate::base::Arch;
+    use crate::inspect::rpm::read_rpm;
+    use crate::rpm::header::read_headers;
+    use crate::rpm::scriptlet::Scriptlet;
+
+    const CONTENT: &[u8] = b"#!/bin/sh\necho hello\n";
+
+    fn new_package(source: &Path) -> RpmPackage {
+        let mut files = BTreeMap::new();
+        files.insert(
+            "/usr/bin/hello".to_owned(),
+            PayloadEntry {
+                kind: EntryKind::File(source.to_path_buf()),
+                attr: FileAttr {
+                    mode: Some(0o755),
+                    ..FileAttr::default()
+                },
+            },
+        );
+        files.insert(
+            "/usr/share/hello".to_owned(),
+            PayloadEntry {
+                kind: EntryKind::Directory,
+                attr: FileAttr::default(),
+            },
+        );
+        files.insert(
+            "/usr/share/hello/hello".to_owned(),
+            PayloadEntry {
+                kind: EntryKind::Symlink(PathBuf::from("/usr/bin/hello")),
+                attr: FileAttr::default(),
+            },
+        );
+        RpmPackage {
+            metadata: Metadata {
+                name: "hello".to_owned(),
+                version: "1.0.0".to_owned(),
+                ..Default::default()
+            },
+            release: "2".to_owned(),
+            epoch: None,
+            vendor: None,
+            group: "Unspecified".to_owned(),
+            arch: PackageArch::Target(Arch::X86_64),
+            source_rpm: "hello-1.0.0-2.src.rpm".to_owned(),
+            dependencies: Vec::new(),
+            scriptlets: Scriptlets::default(),
+            files,
+            directives: BTreeMap::new(),
+            changelog: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_write_rpm() {
+        let dir = utils::test_dir("rpm-writer");
+        let source = dir.join("hello");
+        fs::write(&source, CONTENT).unwrap();
+        let rpm_file = dir.join("hello-1.0.0-2.x86_64.rpm");
+        let package = new_package(&source);
+        write_rpm(&package, CompressFormat::Xz, None, &rpm_file).unwrap();
+
+        let mut reader = BufReader::new(File::open(&rpm_file).unwrap());
+        let (_signature, header) = read_headers(&mut reader).unwrap();
+        assert_eq!(header.get_string(RPMTAG_NAME), Some("hello"));
+        assert_eq!(header.get_string(RPMTAG_VERSION), Some("1.0.0"));
+        assert_eq!(header.get_string(RPMTAG_RELEASE), Some("2"));
+        assert_eq!(header.get_string(RPMTAG_ARCH), Some("x86_64"));
+        assert_eq!(header.get_string(RPMTAG_PAYLOADCOMPRESSOR), Some("xz"));
+        assert_eq!(
+            header.file_paths(),
+            [
+                "/usr/bin/hello",
+                "/usr/share/hello",
+                "/usr/share/hello/hello"
+            ]
+        );
+        assert_eq!(
+            header.get_ints(RPMTAG_FILEMODES),
+            Some(vec![
+                u64::from(S_IFREG | 0o755),
+                u64::from(S_IFDIR | 0o755),
+                u64::from(S_IFLNK | 0o777),
+            ])
+        );
+        let digests = header.get_strings(RPMTAG_FILEDIGESTS).unwrap();
+        assert_eq!(digests[0], hex::encode(Sha256::digest(CONTENT)));
+        assert!(digests[1].is_empty());
+        assert!(digests[2].is_empty());
+
+        // Payload follows main header directly.
+        let mut payload = Vec::new();
+        CompressFormat::Xz
+            .decoder(reader)
+            .unwrap()
+            .read_to_end(&mut payload)
+            .unwrap();
+        assert!(payload.starts_with(b"070701"));
+        assert!(payload
+            .windows(CONTENT.len())
+            .any(|window| window == CONTENT));
+        assert!(payload.windows(10).any(|window| window == b"TRAILER!!!"));
+
+        let info = read_rpm(&rpm_file).unwrap();
+        let field = |name: &str| {
+            info.fields
+                .iter()
+                .find(|field| field.name == name)
+                .map(|field| field.value.as_str())
+        };
+        assert_eq!(field("Name"), Some("hello"));
+        assert_eq!(field("Version"), Some("1.0.0"));
+        assert_eq!(field("Release"), Some("2"));
+        assert_eq!(field("Arch"), Some("x86_64"));
+        assert_eq!(field("Payload"), Some("cpio, xz"));
+        assert_eq!(info.files.len(), 3);
+        assert_eq!(info.files[0].path, "/usr/bin/hello");
+        assert_eq!(info.files[0].mode, S_IFREG | 0o755);
+        assert_eq!(info.files[0].owner, "root");
+        assert_eq!(info.files[0].size, CONTENT.len() as u64);
+        assert_eq!(info.files[2].link_target.as_deref(), Some("/usr/bin/hello"));
+    }
+
+    #[test]
+    fn test_write_file_triggers() {
+        let dir = utils::test_dir("rpm-writer-triggers");
+        let source = dir.join("hello");
+        fs::write(&source, CONTENT).unwrap();
+        let rpm_file = dir.join("hello-1.0.0-2.x86_64.rpm");
+        let mut package = new_package(&source);
+        let new_trigger = |kind, path: &str, priority, content: &str| TriggerScriptlet {
+            kind,
+            paths: vec![path.to_owned()],
+            priority,
+            scriptlet: Scriptlet {
+                content: content.to_owned(),
+                interpreter: "/bin/sh".to_owned(),
+            },
+        };
+        package.scriptlets.file_triggers = vec![
+            new_trigger(
+                FileTriggerKind::FileTriggerIn,
+                "/usr/lib/hello",
+                100,
+                "echo file",
+            ),
+            new_trigger(
+                FileTriggerKind::TransFileTriggerIn,
+                "/usr/share/hello",
+                200,
+                "echo trans",
+            ),
+        ];
+        write_rpm(&package, CompressFormat::Xz, None, &rpm_file).unwrap();
+
+        let mut reader = BufReader::new(File::open(&rpm_file).unwrap());
+        let (_signature, header) = read_headers(&mut reader).unwrap();
+        let strings = |tag| header.get_strings(tag).map(<[String]>::to_vec);
+
+        // Tag numbers are written literally here, as defined in rpmtag.h.
+        assert_eq!(strings(5066), Some(vec!["echo file".to_owned()]));
+        assert_eq!(strings(5067), Some(vec!["/bin/sh".to_owned()]));
+        assert_eq!(header.get_ints(5068), Some(vec![0]));
+        assert_eq!(strings(5069), Some(vec!["/usr/lib/hello".to_owned()]));
+        assert_eq!(header.get_ints(5070), Some(vec![0]));
+        assert_eq!(strings(5071), Some(vec![String::new()]));
+        assert_eq!(header.get_ints(5072), Some(vec![1 << 16]));
+
+        assert_eq!(strings(5073), Some(vec!["echo trans".to_owned()]));
+        assert_eq!(strings(5074), Some(vec!["/bin/sh".to_owned()]));
+        assert_eq!(header.get_ints(5075), Some(vec![0]));
+        assert_eq!(strings(5076), Some(vec!["/usr/share/hello".to_owned()]));
+        assert_eq!(header.get_ints(5077), Some(vec![0]));
+        assert_eq!(strings(5078), Some(vec![String::new()]));
+        assert_eq!(header.get_ints(5079), Some(vec![1 << 16]));
+
+        assert_eq!(header.get_ints(5081), Some(vec![100]));
+        assert_eq!(header.get_ints(5082), Some(vec![200]));
+
+        // Neighbouring tags of other meanings are left untouched.
+        for tag in [5080, 5083, 5084, 5085] {
+            assert!(strings(tag).is_none(), "tag {tag}");
+            assert!(header.get_ints(tag).is_none(), "tag {tag}");
+        }
+    }
+}